@@ -1,33 +1,270 @@
+use crate::auto_complete::completion_spec::{SharedCompletions, is_directory, path_candidates};
+use crate::auto_complete::highlighter::{HighlightTheme, SyntaxHighlighter};
+use crate::commands::builtin::SharedBuiltins;
+use crate::commands::command_hash::SharedCommandHash;
+use crate::commands::frecency::{self, FrecencyDatabase, RankMode};
+use crate::commands::function_state::SharedFunctions;
+use crate::commands::history_state::SharedHistory;
+use crate::commands::shell_state::SharedState;
+use crate::parsing::command_parser::CommandParser;
 use rustyline::completion::{Completer, Pair};
-use rustyline::highlight::Highlighter;
+use rustyline::highlight::{CmdKind, Highlighter};
 use rustyline::hint::Hinter;
 use rustyline::validate::Validator;
-use rustyline::{Context, Helper};
+use rustyline::{Cmd, ConditionalEventHandler, Context, Event, EventContext, Helper, RepeatCount};
+use std::borrow::Cow;
 use std::cell::{Cell, RefCell};
 use std::io::{self, Write};
+use std::rc::Rc;
+
+/// Runs the shell function behind a `complete -F` spec.
+pub trait CompletionFunctionRunner {
+    /// Calls `function` with `COMP_WORDS`, `COMP_CWORD`, `COMP_LINE` and `COMP_POINT`
+    /// set, and returns the contents of `COMPREPLY` afterwards.
+    fn run_completion_function(
+        &self,
+        function: &str,
+        words: &[String],
+        cword: usize,
+        line: &str,
+    ) -> Vec<String>;
+}
 
 pub struct AutoCompleteHelper {
-    commands: Vec<String>,
+    /// The registered builtins; executables come from the command hash table on
+    /// every completion.
+    builtins: SharedBuiltins,
+    hash: SharedCommandHash,
+    completions: SharedCompletions,
+    functions: SharedFunctions,
+    /// Shared history, the source of autosuggestions.
+    history: SharedHistory,
+    /// The shell's state, for the working directory and `PATH`, and consulted for
+    /// `HIGHLIGHT_COLORS`, `NO_COLOR` and `TERM`.
+    state: SharedState,
+    runner: Rc<dyn CompletionFunctionRunner>,
     last_input: RefCell<String>,
     tab_count: Cell<usize>,
 }
 
 impl AutoCompleteHelper {
-    pub fn new(commands: Vec<String>) -> Self {
+    pub fn new(
+        builtins: SharedBuiltins,
+        hash: SharedCommandHash,
+        completions: SharedCompletions,
+        functions: SharedFunctions,
+        history: SharedHistory,
+        state: SharedState,
+        runner: Rc<dyn CompletionFunctionRunner>,
+    ) -> Self {
         AutoCompleteHelper {
-            commands,
+            builtins,
+            hash,
+            completions,
+            functions,
+            history,
+            state,
+            runner,
             last_input: RefCell::new(String::new()),
             tab_count: Cell::new(0),
         }
     }
+
+    fn command_names(&self) -> Vec<String> {
+        let mut names = self.builtins.lock().unwrap().names();
+        let search_path = self.state.lock().unwrap().search_path();
+        names.extend(self.hash.lock().unwrap().executables(&search_path));
+        names.extend(self.functions.lock().unwrap().names());
+        names
+    }
+
+    /// Whether the command a history entry starts with can still be run: a builtin,
+    /// a function, a variable assignment, or an executable that can be found.
+    fn command_exists(&self, command: &str) -> bool {
+        let Some(name) = CommandParser::split_words(command).into_iter().next() else {
+            return false;
+        };
+        name.contains('=') || self.is_command(&name)
+    }
+
+    /// Whether `name` is a builtin, a function, an executable that can be found, or
+    /// with `autocd` a directory.
+    fn is_command(&self, name: &str) -> bool {
+        if self.state.lock().unwrap().options.autocd && is_directory(name, &self.cwd()) {
+            return true;
+        }
+        self.builtins.lock().unwrap().contains(name)
+            || self.functions.lock().unwrap().contains(name)
+            || self
+                .hash
+                .lock()
+                .unwrap()
+                .lookup(name, &self.state.lock().unwrap().search_path())
+                .is_some()
+    }
+
+    fn cwd(&self) -> String {
+        self.state.lock().unwrap().cwd().to_string()
+    }
+
+    /// The highlighting theme, or `None` when colour is turned off by `NO_COLOR` or a
+    /// dumb terminal.
+    fn theme(&self) -> Option<HighlightTheme> {
+        let variables = &self.state.lock().unwrap().variables;
+        let no_color = variables.get("NO_COLOR").is_some_and(|v| !v.is_empty());
+        let dumb = variables.get("TERM").is_some_and(|term| term == "dumb");
+        if no_color || dumb {
+            return None;
+        }
+        let spec = variables.get("HIGHLIGHT_COLORS").unwrap_or_default();
+        Some(HighlightTheme::from_spec(&spec))
+    }
+
+    /// Directories from the `z` database matching the fragments typed so far.
+    fn ranked_directories(&self, fragments: &[String], word: &str) -> Vec<String> {
+        let Some(path) = FrecencyDatabase::data_path(&self.state.lock().unwrap().variables) else {
+            return Vec::new();
+        };
+        let mut fragments: Vec<String> = fragments
+            .iter()
+            .filter(|f| !f.starts_with('-'))
+            .cloned()
+            .collect();
+        fragments.push(word.to_string());
+        FrecencyDatabase::load(&path)
+            .rank(&fragments, RankMode::Frecency, frecency::now())
+            .into_iter()
+            .map(|(_, dir)| dir)
+            .collect()
+    }
+
+    /// The options a builtin declares, as `-x` words starting with `word`.
+    fn builtin_flags(&self, name: &str, word: &str) -> Vec<String> {
+        let Some(builtin) = self.builtins.lock().unwrap().get(name) else {
+            return Vec::new();
+        };
+        builtin
+            .flags
+            .chars()
+            .map(|flag| format!("-{}", flag))
+            .filter(|flag| flag.starts_with(word))
+            .collect()
+    }
+
+    /// Completes an argument using the command's spec, falling back to paths; a
+    /// builtin completes its options for a word starting with `-`, and `z` completes
+    /// to ranked directories.
+    fn argument_candidates(&self, preceding: &[String], word: &str, line: &str) -> Vec<String> {
+        let spec = self.completions.lock().unwrap().get(&preceding[0]);
+        let Some(spec) = spec else {
+            if word.starts_with('-') {
+                let flags = self.builtin_flags(&preceding[0], word);
+                if !flags.is_empty() {
+                    return flags;
+                }
+            }
+            if preceding[0] == "z" {
+                return self.ranked_directories(&preceding[1..], word);
+            }
+            return path_candidates(word, false, &self.cwd());
+        };
+
+        let mut candidates = spec.generate(word, &self.command_names(), &self.cwd());
+        if let Some(ref function) = spec.function {
+            let mut words = preceding.to_vec();
+            words.push(word.to_string());
+            let cword = words.len() - 1;
+            candidates.extend(
+                self.runner
+                    .run_completion_function(function, &words, cword, line),
+            );
+        }
+        candidates
+    }
 }
 
 impl Helper for AutoCompleteHelper {}
 impl Hinter for AutoCompleteHelper {
     type Hint = String;
+
+    /// Suggests the rest of the most recent history entry that starts with the line,
+    /// preferring entries run in the current directory.
+    fn hint(&self, line: &str, pos: usize, _ctx: &Context<'_>) -> Option<String> {
+        if line.trim().is_empty() || pos < line.len() {
+            return None;
+        }
+
+        let cwd = Some(self.cwd());
+        let history = self.history.lock().unwrap();
+        let matching: Vec<_> = history
+            .entries
+            .iter()
+            .rev()
+            .filter(|entry| {
+                entry.command.len() > line.len()
+                    && entry.command.starts_with(line)
+                    && !entry.command.contains('\n')
+            })
+            .collect();
+
+        let entry = matching
+            .iter()
+            .filter(|entry| entry.cwd == cwd)
+            .chain(matching.iter())
+            .find(|entry| self.command_exists(&entry.command))?;
+        Some(entry.command[line.len()..].to_string())
+    }
 }
 impl Validator for AutoCompleteHelper {}
-impl Highlighter for AutoCompleteHelper {}
+impl Highlighter for AutoCompleteHelper {
+    fn highlight<'l>(&self, line: &'l str, _pos: usize) -> Cow<'l, str> {
+        let Some(theme) = self.theme() else {
+            return Cow::Borrowed(line);
+        };
+        let is_command = |name: &str| self.is_command(name);
+        let cwd = self.cwd();
+        Cow::Owned(SyntaxHighlighter::new(&theme, &is_command, &cwd).highlight(line))
+    }
+
+    fn highlight_hint<'h>(&self, hint: &'h str) -> Cow<'h, str> {
+        if self.theme().is_none() {
+            return Cow::Borrowed(hint);
+        }
+        Cow::Owned(format!("\x1b[2m{}\x1b[0m", hint))
+    }
+
+    /// Every edit can change how the line is coloured, e.g. when a command name is
+    /// completed, so the whole line is redrawn each time.
+    fn highlight_char(&self, _line: &str, _pos: usize, _kind: CmdKind) -> bool {
+        true
+    }
+}
+
+/// Binds to Alt-Right: accepts the next word of the autosuggestion, or falls back to
+/// the default action when there is none.
+pub struct AcceptHintWordHandler;
+
+impl ConditionalEventHandler for AcceptHintWordHandler {
+    fn handle(
+        &self,
+        _evt: &Event,
+        _n: RepeatCount,
+        _positive: bool,
+        ctx: &EventContext,
+    ) -> Option<Cmd> {
+        let hint = ctx.hint_text()?;
+        if ctx.pos() < ctx.line().len() {
+            return None;
+        }
+
+        // Take any leading whitespace, then the word itself.
+        let word_start = hint.len() - hint.trim_start().len();
+        let word_end = hint[word_start..]
+            .find(char::is_whitespace)
+            .map_or(hint.len(), |i| word_start + i);
+        Some(Cmd::Insert(1, hint[..word_end].to_string()))
+    }
+}
 
 impl Completer for AutoCompleteHelper {
     type Candidate = Pair;
@@ -35,27 +272,47 @@ impl Completer for AutoCompleteHelper {
     fn complete(
         &self,
         line: &str,
-        _pos: usize,
+        pos: usize,
         _ctx: &Context<'_>,
     ) -> rustyline::Result<(usize, Vec<Pair>)> {
-        let mut matches: Vec<String> = self
-            .commands
-            .iter()
-            .filter(|c| c.starts_with(line))
-            .cloned()
-            .collect();
+        let line = &line[..pos];
+        let (command_start, start) = word_start(line);
+        let word = &line[start..];
+        let preceding = CommandParser::split_words(&line[command_start..start]);
+
+        let mut matches: Vec<String> = if preceding.is_empty() {
+            let mut names: Vec<String> = self
+                .command_names()
+                .into_iter()
+                .filter(|c| c.starts_with(word))
+                .collect();
+            // With `autocd` a directory can be typed as a command.
+            if self.state.lock().unwrap().options.autocd {
+                names.extend(path_candidates(word, true, &self.cwd()));
+            }
+            names
+        } else {
+            self.argument_candidates(&preceding, word, line)
+        };
 
         matches.sort();
         matches.dedup();
 
         if matches.is_empty() {
-            return Ok((0, vec![]));
+            return Ok((start, vec![]));
         }
 
         if matches.len() == 1 {
-            let cmd = format!("{} ", matches[0]);
+            let candidate = &matches[0];
+            let is_command = preceding.is_empty() && !self.state.lock().unwrap().options.autocd;
+            let suffix = if !is_command && is_directory(candidate, &self.cwd()) {
+                if candidate.ends_with('/') { "" } else { "/" }
+            } else {
+                " "
+            };
+            let cmd = format!("{}{}", candidate, suffix);
             return Ok((
-                0,
+                start,
                 vec![Pair {
                     display: cmd.clone(),
                     replacement: cmd,
@@ -64,9 +321,9 @@ impl Completer for AutoCompleteHelper {
         }
 
         let lcp = longest_common_prefix(&matches);
-        if lcp.len() > line.len() {
+        if lcp.len() > word.len() {
             return Ok((
-                0,
+                start,
                 vec![Pair {
                     display: lcp.clone(),
                     replacement: lcp,
@@ -90,10 +347,39 @@ impl Completer for AutoCompleteHelper {
         }
         io::stdout().flush().unwrap();
 
-        Ok((0, vec![]))
+        Ok((start, vec![]))
     }
 }
 
+/// Returns the byte offsets where the current command and the word under the
+/// cursor start, skipping separators that are quoted or escaped.
+fn word_start(line: &str) -> (usize, usize) {
+    let mut command_start = 0;
+    let mut start = 0;
+    let mut quote: Option<char> = None;
+    let mut escaped = false;
+
+    for (i, c) in line.char_indices() {
+        if escaped {
+            escaped = false;
+            continue;
+        }
+        match (c, quote) {
+            ('\\', q) if q != Some('\'') => escaped = true,
+            ('\'', None) | ('"', None) => quote = Some(c),
+            (_, Some(q)) if c == q => quote = None,
+            (' ' | '\t', None) => start = i + 1,
+            ('|' | ';' | '&', None) => {
+                command_start = i + 1;
+                start = i + 1;
+            }
+            _ => {}
+        }
+    }
+
+    (command_start, start)
+}
+
 fn longest_common_prefix(strs: &[String]) -> String {
     if strs.is_empty() {
         return String::new();
@@ -0,0 +1,202 @@
+use crate::parsing::command_parser::CommandParser;
+use anyhow::{Result, bail};
+use std::collections::HashMap;
+use std::fs;
+use std::path::Path;
+use std::sync::{Arc, Mutex};
+
+/// How the arguments of a command are completed, as configured with `complete`.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct CompletionSpec {
+    /// `-W wordlist`
+    pub word_list: Option<String>,
+    /// `-F function`
+    pub function: Option<String>,
+    /// `-d`
+    pub directories: bool,
+    /// `-f`
+    pub files: bool,
+    /// `-c`
+    pub commands: bool,
+}
+
+/// Options shared by `complete` and `compgen`, parsed from their arguments.
+#[derive(Debug, Default)]
+pub struct CompletionOptions {
+    pub spec: CompletionSpec,
+    pub print: bool,
+    pub remove: bool,
+    /// Remaining operands: command names for `complete`, the word for `compgen`.
+    pub operands: Vec<String>,
+}
+
+impl CompletionOptions {
+    pub fn parse(builtin: &str, args: &[String]) -> Result<Self> {
+        let mut options = Self::default();
+        let mut iter = args.iter();
+
+        while let Some(arg) = iter.next() {
+            if arg == "--" {
+                options.operands.extend(iter.cloned());
+                break;
+            }
+
+            let Some(flags) = arg.strip_prefix('-').filter(|f| !f.is_empty()) else {
+                options.operands.push(arg.clone());
+                continue;
+            };
+
+            for flag in flags.chars() {
+                match flag {
+                    'd' => options.spec.directories = true,
+                    'f' => options.spec.files = true,
+                    'c' => options.spec.commands = true,
+                    'p' => options.print = true,
+                    'r' => options.remove = true,
+                    'W' | 'F' => {
+                        let Some(value) = iter.next() else {
+                            bail!("{}: -{}: option requires an argument", builtin, flag);
+                        };
+                        if flag == 'W' {
+                            options.spec.word_list = Some(value.clone());
+                        } else {
+                            options.spec.function = Some(value.clone());
+                        }
+                    }
+                    _ => bail!("{}: -{}: invalid option", builtin, flag),
+                }
+            }
+        }
+
+        Ok(options)
+    }
+}
+
+impl CompletionSpec {
+    pub fn is_empty(&self) -> bool {
+        *self == Self::default()
+    }
+
+    /// Generates the candidates that do not need a shell function: word lists,
+    /// directories, files and command names. `commands` lists every known command name,
+    /// and paths are relative to `cwd`.
+    pub fn generate(&self, word: &str, commands: &[String], cwd: &str) -> Vec<String> {
+        let mut candidates = Vec::new();
+
+        if let Some(ref words) = self.word_list {
+            candidates.extend(
+                CommandParser::split_words(words)
+                    .into_iter()
+                    .filter(|w| w.starts_with(word)),
+            );
+        }
+        if self.directories && !self.files {
+            candidates.extend(path_candidates(word, true, cwd));
+        }
+        if self.files {
+            candidates.extend(path_candidates(word, false, cwd));
+        }
+        if self.commands {
+            candidates.extend(commands.iter().filter(|c| c.starts_with(word)).cloned());
+        }
+
+        candidates
+    }
+
+    /// Renders the spec the way `complete -p` prints it.
+    pub fn to_command_line(&self, name: &str) -> String {
+        let mut line = String::from("complete");
+        if self.directories {
+            line.push_str(" -d");
+        }
+        if self.files {
+            line.push_str(" -f");
+        }
+        if self.commands {
+            line.push_str(" -c");
+        }
+        if let Some(ref words) = self.word_list {
+            line.push_str(&format!(" -W '{}'", words.replace('\'', "'\\''")));
+        }
+        if let Some(ref function) = self.function {
+            line.push_str(&format!(" -F {}", function));
+        }
+        line.push(' ');
+        line.push_str(name);
+        line
+    }
+}
+
+/// Per-command completion specs registered with `complete`.
+#[derive(Default)]
+pub struct CompletionState {
+    specs: HashMap<String, CompletionSpec>,
+}
+
+pub type SharedCompletions = Arc<Mutex<CompletionState>>;
+
+impl CompletionState {
+    pub fn get(&self, command: &str) -> Option<CompletionSpec> {
+        self.specs.get(command).cloned()
+    }
+
+    pub fn set(&mut self, command: &str, spec: CompletionSpec) {
+        self.specs.insert(command.to_string(), spec);
+    }
+
+    pub fn remove(&mut self, command: &str) -> bool {
+        self.specs.remove(command).is_some()
+    }
+
+    pub fn clear(&mut self) {
+        self.specs.clear();
+    }
+
+    /// All registered specs, sorted by command name.
+    pub fn specs(&self) -> Vec<(String, CompletionSpec)> {
+        let mut specs: Vec<_> = self
+            .specs
+            .iter()
+            .map(|(name, spec)| (name.clone(), spec.clone()))
+            .collect();
+        specs.sort_by(|a, b| a.0.cmp(&b.0));
+        specs
+    }
+}
+
+/// Lists the paths that start with `word`, relative to the working directory `cwd`.
+/// Hidden entries are only offered when the typed prefix starts with a dot.
+pub fn path_candidates(word: &str, directories_only: bool, cwd: &str) -> Vec<String> {
+    let (dir, prefix) = match word.rfind('/') {
+        Some(i) => (&word[..=i], &word[i + 1..]),
+        None => ("", word),
+    };
+    let search_dir = if dir.is_empty() { "." } else { dir };
+
+    let Ok(entries) = fs::read_dir(Path::new(cwd).join(search_dir)) else {
+        return Vec::new();
+    };
+
+    let mut candidates: Vec<String> = entries
+        .flatten()
+        .filter_map(|entry| {
+            let name = entry.file_name().to_string_lossy().to_string();
+            if !name.starts_with(prefix) || (name.starts_with('.') && !prefix.starts_with('.')) {
+                return None;
+            }
+            if directories_only && !entry.path().is_dir() {
+                return None;
+            }
+            Some(format!("{}{}", dir, name))
+        })
+        .collect();
+
+    candidates.sort();
+    candidates
+}
+
+/// Returns true when a completed word names a directory, so that completion
+/// appends `/` instead of a space.
+pub fn is_directory(candidate: &str, cwd: &str) -> bool {
+    Path::new(cwd).join(candidate).is_dir()
+}
@@ -0,0 +1,312 @@
+use crate::parsing::command_parser::{CommandParser, TokenKind};
+use std::path::Path;
+
+/// Words that are valid in command position without being commands.
+const KEYWORDS: [&str; 7] = ["function", "{", "}", "while", "until", "do", "done"];
+
+/// Keywords followed by another command, as in `while true` or `do echo`.
+const COMMAND_PREFIXES: [&str; 4] = ["{", "while", "until", "do"];
+
+/// SGR parameters for each kind of highlighted text, e.g. `"32"` or `"1;31"`.
+///
+/// The defaults can be overridden with `HIGHLIGHT_COLORS`, a colon-separated list of
+/// `name=sgr` pairs such as `command=1;32:comment=2`. An empty value turns a kind off.
+#[derive(Debug, Clone)]
+pub struct HighlightTheme {
+    pub command: String,
+    pub error: String,
+    pub string: String,
+    pub variable: String,
+    pub operator: String,
+    pub redirect: String,
+    pub comment: String,
+    pub path: String,
+}
+
+impl Default for HighlightTheme {
+    fn default() -> Self {
+        Self {
+            command: "32".to_string(),
+            error: "31".to_string(),
+            string: "33".to_string(),
+            variable: "36".to_string(),
+            operator: "35".to_string(),
+            redirect: "34".to_string(),
+            comment: "90".to_string(),
+            path: "4".to_string(),
+        }
+    }
+}
+
+impl HighlightTheme {
+    /// The default theme with the overrides in `spec` applied. Unknown names are ignored.
+    pub fn from_spec(spec: &str) -> Self {
+        let mut theme = Self::default();
+        for (name, sgr) in spec.split(':').filter_map(|pair| pair.split_once('=')) {
+            let slot = match name.trim() {
+                "command" => &mut theme.command,
+                "error" => &mut theme.error,
+                "string" => &mut theme.string,
+                "variable" => &mut theme.variable,
+                "operator" => &mut theme.operator,
+                "redirect" => &mut theme.redirect,
+                "comment" => &mut theme.comment,
+                "path" => &mut theme.path,
+                _ => continue,
+            };
+            *slot = sgr.trim().to_string();
+        }
+        theme
+    }
+}
+
+/// Colours a command line from the parser's tokens: commands by whether they can be
+/// run, plus strings, variables, operators, redirection targets and comments, with
+/// existing paths underlined.
+pub struct SyntaxHighlighter<'a> {
+    theme: &'a HighlightTheme,
+    is_command: &'a dyn Fn(&str) -> bool,
+    /// Relative paths are checked against this directory.
+    cwd: &'a str,
+}
+
+impl<'a> SyntaxHighlighter<'a> {
+    pub fn new(
+        theme: &'a HighlightTheme,
+        is_command: &'a dyn Fn(&str) -> bool,
+        cwd: &'a str,
+    ) -> Self {
+        Self {
+            theme,
+            is_command,
+            cwd,
+        }
+    }
+
+    pub fn highlight(&self, line: &str) -> String {
+        let mut output = String::with_capacity(line.len() * 2);
+        let mut last = 0;
+        let mut command_position = true;
+        let mut redirect_target = false;
+
+        for token in CommandParser::tokenize(line) {
+            output.push_str(&line[last..token.start]);
+            let text = &line[token.start..token.end];
+            last = token.end;
+
+            match token.kind {
+                TokenKind::Comment => paint(&mut output, &self.theme.comment, text),
+                TokenKind::Operator => {
+                    paint(&mut output, &self.theme.operator, text);
+                    command_position = true;
+                }
+                TokenKind::Redirection => {
+                    paint(&mut output, &self.theme.operator, text);
+                    redirect_target = true;
+                }
+                TokenKind::Word if redirect_target => {
+                    self.paint_word(&mut output, text, &self.theme.redirect);
+                    redirect_target = false;
+                }
+                TokenKind::Word if command_position => {
+                    if is_assignment(text) {
+                        self.paint_word(&mut output, text, "");
+                        continue;
+                    }
+                    let name = CommandParser::split_words(text).join(" ");
+                    let valid = KEYWORDS.contains(&name.as_str())
+                        || name.ends_with("()")
+                        || (self.is_command)(&name);
+                    let style = if valid {
+                        &self.theme.command
+                    } else {
+                        &self.theme.error
+                    };
+                    self.paint_word(&mut output, text, style);
+                    command_position = COMMAND_PREFIXES.contains(&name.as_str());
+                }
+                TokenKind::Word => self.paint_word(&mut output, text, ""),
+            }
+        }
+
+        output.push_str(&line[last..]);
+        output
+    }
+
+    /// Paints a word with `base`, giving quoted strings and variables their own colours
+    /// and underlining the whole word when it names an existing path.
+    fn paint_word(&self, output: &mut String, word: &str, base: &str) {
+        let value = CommandParser::split_words(word).join(" ");
+        let is_path = !value.is_empty()
+            && !value.starts_with('-')
+            && Path::new(self.cwd).join(&value).exists();
+        let extra = if is_path {
+            self.theme.path.as_str()
+        } else {
+            ""
+        };
+        let style = |style: &str| join_styles(style, extra);
+
+        let chars: Vec<(usize, char)> = word.char_indices().collect();
+        let offset = |i: usize| chars.get(i).map_or(word.len(), |&(o, _)| o);
+        let mut segment_start = 0;
+        let mut i = 0;
+
+        // Paints the plain characters `from..to` in the base style.
+        let flush = |output: &mut String, from: usize, to: usize| {
+            if from < to {
+                paint(output, &style(base), &word[offset(from)..offset(to)]);
+            }
+        };
+
+        while i < chars.len() {
+            let c = chars[i].1;
+            let end = match c {
+                '\\' => {
+                    i += 2;
+                    continue;
+                }
+                '\'' => chars[i + 1..]
+                    .iter()
+                    .position(|&(_, c)| c == '\'')
+                    .map_or(chars.len(), |p| i + p + 2),
+                '"' => {
+                    flush(output, segment_start, i);
+                    i = self.paint_double_quoted(output, &chars, word, i, &style);
+                    segment_start = i;
+                    continue;
+                }
+                '$' => variable_end(&chars, i),
+                _ => {
+                    i += 1;
+                    continue;
+                }
+            };
+            if end == i + 1 && c == '$' {
+                i += 1;
+                continue;
+            }
+
+            flush(output, segment_start, i);
+            let kind = if c == '$' {
+                &self.theme.variable
+            } else {
+                &self.theme.string
+            };
+            paint(output, &style(kind), &word[offset(i)..offset(end)]);
+            i = end;
+            segment_start = i;
+        }
+        flush(output, segment_start, chars.len());
+    }
+
+    /// Paints a double-quoted string starting at `start`, with the variables inside it
+    /// in their own colour. Returns the index just past the closing quote.
+    fn paint_double_quoted(
+        &self,
+        output: &mut String,
+        chars: &[(usize, char)],
+        word: &str,
+        start: usize,
+        style: &dyn Fn(&str) -> String,
+    ) -> usize {
+        let offset = |i: usize| chars.get(i).map_or(word.len(), |&(o, _)| o);
+        let string_style = style(&self.theme.string);
+        let mut segment_start = start;
+        let mut i = start + 1;
+
+        while i < chars.len() {
+            match chars[i].1 {
+                '\\' => i += 2,
+                '"' => {
+                    i += 1;
+                    break;
+                }
+                '$' => {
+                    let end = variable_end(chars, i);
+                    if end > i + 1 {
+                        paint(
+                            output,
+                            &string_style,
+                            &word[offset(segment_start)..offset(i)],
+                        );
+                        paint(
+                            output,
+                            &style(&self.theme.variable),
+                            &word[offset(i)..offset(end)],
+                        );
+                        segment_start = end;
+                    }
+                    i = end.max(i + 1);
+                }
+                _ => i += 1,
+            }
+        }
+
+        let end = i.min(chars.len());
+        paint(
+            output,
+            &string_style,
+            &word[offset(segment_start)..offset(end)],
+        );
+        end
+    }
+}
+
+/// Returns the index just past a `$name`, `${...}`, `$(...)` or special parameter
+/// starting at `start`, or `start + 1` when the `$` is literal.
+fn variable_end(chars: &[(usize, char)], start: usize) -> usize {
+    let at = |i: usize| chars.get(i).map(|&(_, c)| c);
+    match at(start + 1) {
+        Some(open @ ('{' | '(')) => {
+            let close = if open == '{' { '}' } else { ')' };
+            let mut depth = 0;
+            for (i, &(_, c)) in chars.iter().enumerate().skip(start + 1) {
+                if c == open {
+                    depth += 1;
+                } else if c == close {
+                    depth -= 1;
+                    if depth == 0 {
+                        return i + 1;
+                    }
+                }
+            }
+            chars.len()
+        }
+        Some(c) if c.is_ascii_alphabetic() || c == '_' => {
+            let mut i = start + 1;
+            while at(i).is_some_and(|c| c.is_ascii_alphanumeric() || c == '_') {
+                i += 1;
+            }
+            i
+        }
+        Some(c) if c.is_ascii_digit() || matches!(c, '?' | '#' | '@' | '*' | '$' | '-') => {
+            start + 2
+        }
+        _ => start + 1,
+    }
+}
+
+fn is_assignment(word: &str) -> bool {
+    word.split_once('=').is_some_and(|(name, _)| {
+        !name.is_empty()
+            && !name.starts_with(|c: char| c.is_ascii_digit())
+            && name.chars().all(|c| c.is_ascii_alphanumeric() || c == '_')
+    })
+}
+
+fn join_styles(style: &str, extra: &str) -> String {
+    match (style.is_empty(), extra.is_empty()) {
+        (true, _) => extra.to_string(),
+        (_, true) => style.to_string(),
+        _ => format!("{};{}", style, extra),
+    }
+}
+
+fn paint(output: &mut String, style: &str, text: &str) {
+    if style.is_empty() || text.is_empty() {
+        output.push_str(text);
+    } else {
+        output.push_str(&format!("\x1b[{}m{}\x1b[0m", style, text));
+    }
+}
@@ -0,0 +1,353 @@
+use rustyline::{Cmd, ConditionalEventHandler, Event, EventContext, Movement, RepeatCount};
+use std::collections::HashMap;
+use std::io::{self, Write};
+
+use crate::commands::history_state::{HistoryEntry, SharedHistory};
+use crate::commands::shell_state::SharedState;
+
+/// How many matches the search shows at once.
+const MAX_RESULTS: usize = 8;
+
+/// How long to wait for the rest of an escape sequence before treating `Esc` as a key.
+const ESCAPE_TIMEOUT_MS: i32 = 30;
+
+/// Ranks history entries against a search query.
+///
+/// Each distinct command is scored by fuzzy subsequence matching, with bonuses for
+/// contiguous matches and matches at word starts, plus how recently and how often it
+/// was run. With `cwd` set, only commands entered in that directory are considered.
+pub fn rank(entries: &[HistoryEntry], query: &str, cwd: Option<&str>) -> Vec<String> {
+    struct Candidate {
+        last_index: usize,
+        count: usize,
+    }
+
+    let mut candidates: HashMap<&str, Candidate> = HashMap::new();
+    for (index, entry) in entries.iter().enumerate() {
+        if cwd.is_some() && entry.cwd.as_deref() != cwd {
+            continue;
+        }
+        let candidate = candidates.entry(&entry.command).or_insert(Candidate {
+            last_index: index,
+            count: 0,
+        });
+        candidate.last_index = index;
+        candidate.count += 1;
+    }
+
+    let total = entries.len().max(1) as f64;
+    let mut scored: Vec<(i64, usize, &str)> = candidates
+        .into_iter()
+        .filter_map(|(command, candidate)| {
+            let matched = fuzzy_score(query, command)?;
+            let recency = (40.0 * (candidate.last_index + 1) as f64 / total) as i64;
+            let frequency = (10.0 * (candidate.count as f64).ln()) as i64;
+            Some((matched + recency + frequency, candidate.last_index, command))
+        })
+        .collect();
+
+    scored.sort_by(|a, b| b.0.cmp(&a.0).then(b.1.cmp(&a.1)));
+    scored
+        .into_iter()
+        .map(|(_, _, command)| command.to_string())
+        .collect()
+}
+
+/// Scores how well `text` matches `query`, or `None` when the query's characters do
+/// not all appear in order. A query with capitals matches case-sensitively.
+fn fuzzy_score(query: &str, text: &str) -> Option<i64> {
+    if query.is_empty() {
+        return Some(0);
+    }
+
+    let case_sensitive = query.chars().any(char::is_uppercase);
+    let fold = |c: char| {
+        if case_sensitive {
+            c
+        } else {
+            c.to_ascii_lowercase()
+        }
+    };
+    let text: Vec<char> = text.chars().map(fold).collect();
+    let query: Vec<char> = query.chars().map(fold).collect();
+    let is_boundary = |i: usize| i == 0 || !text[i - 1].is_alphanumeric();
+
+    if let Some(pos) = text.windows(query.len()).position(|w| w == query) {
+        let mut score = 100 + 4 * query.len() as i64;
+        if pos == 0 {
+            score += 30;
+        } else if is_boundary(pos) {
+            score += 15;
+        }
+        return Some(score);
+    }
+
+    let mut score = 0;
+    let mut matched = 0;
+    let mut previous: Option<usize> = None;
+    for (i, &c) in text.iter().enumerate() {
+        if matched == query.len() || c != query[matched] {
+            continue;
+        }
+        score += 2;
+        match previous {
+            Some(p) if p + 1 == i => score += 4,
+            Some(p) => score -= (i - p - 1).min(5) as i64,
+            None => {}
+        }
+        if is_boundary(i) {
+            score += 3;
+        }
+        previous = Some(i);
+        matched += 1;
+    }
+    (matched == query.len()).then_some(score)
+}
+
+/// Binds to Ctrl-R: an interactive, ranked search over the shared history that
+/// puts the chosen command on the line for editing.
+pub struct HistorySearchHandler {
+    history: SharedHistory,
+    /// Supplies the working directory the search can be limited to.
+    state: SharedState,
+}
+
+impl HistorySearchHandler {
+    pub fn new(history: SharedHistory, state: SharedState) -> Self {
+        Self { history, state }
+    }
+}
+
+impl ConditionalEventHandler for HistorySearchHandler {
+    fn handle(
+        &self,
+        _evt: &Event,
+        _n: RepeatCount,
+        _positive: bool,
+        ctx: &EventContext,
+    ) -> Option<Cmd> {
+        let entries = self.history.lock().unwrap().entries.clone();
+        let cwd = self.state.lock().unwrap().cwd().to_string();
+        let mut search = SearchSession::new(entries, ctx.line(), cwd);
+        match search.run() {
+            Ok(Some(command)) => Some(Cmd::Replace(Movement::WholeBuffer, Some(command))),
+            _ => Some(Cmd::Repaint),
+        }
+    }
+}
+
+enum Key {
+    Char(char),
+    Backspace,
+    Up,
+    Down,
+    Accept,
+    Cancel,
+    ToggleDirectory,
+    Ignore,
+}
+
+/// The state of one Ctrl-R search. It draws its results below the prompt line while
+/// the line editor has the terminal in raw mode, and reads keys straight from stdin.
+struct SearchSession {
+    entries: Vec<HistoryEntry>,
+    query: String,
+    cwd: String,
+    cwd_only: bool,
+    selected: usize,
+    results: Vec<String>,
+}
+
+impl SearchSession {
+    fn new(entries: Vec<HistoryEntry>, query: &str, cwd: String) -> Self {
+        let mut session = Self {
+            entries,
+            query: query.to_string(),
+            cwd,
+            cwd_only: false,
+            selected: 0,
+            results: Vec::new(),
+        };
+        session.update();
+        session
+    }
+
+    fn update(&mut self) {
+        let filter = self.cwd_only.then_some(self.cwd.as_str());
+        self.results = rank(&self.entries, &self.query, filter);
+        self.selected = 0;
+    }
+
+    /// Runs the search until a command is chosen (`Some`) or it is cancelled (`None`).
+    fn run(&mut self) -> io::Result<Option<String>> {
+        let mut out = io::stdout();
+        // Reserve the rows below the prompt first, so the screen scrolls now rather
+        // than while drawing and the prompt row stays where it is.
+        let rows = MAX_RESULTS + 1;
+        write!(out, "{}\x1b[{}A\r", "\r\n".repeat(rows), rows)?;
+
+        let result = loop {
+            self.render(&mut out)?;
+            match read_key()? {
+                Key::Char(c) => {
+                    self.query.push(c);
+                    self.update();
+                }
+                Key::Backspace => {
+                    self.query.pop();
+                    self.update();
+                }
+                Key::Up => self.selected = self.selected.saturating_sub(1),
+                Key::Down => {
+                    let visible = self.results.len().min(MAX_RESULTS);
+                    if self.selected + 1 < visible {
+                        self.selected += 1;
+                    }
+                }
+                Key::ToggleDirectory => {
+                    self.cwd_only = !self.cwd_only;
+                    self.update();
+                }
+                Key::Accept => break self.results.get(self.selected).cloned(),
+                Key::Cancel => break None,
+                Key::Ignore => {}
+            }
+        };
+
+        write!(out, "\r\x1b[1B\x1b[J\x1b[1A")?;
+        out.flush()?;
+        Ok(result)
+    }
+
+    fn render(&self, out: &mut impl Write) -> io::Result<()> {
+        let width = terminal_width();
+        let scope = if self.cwd_only {
+            " [this directory]"
+        } else {
+            ""
+        };
+        let header = format!("history search{}: {}", scope, self.query);
+
+        write!(out, "\r\x1b[1B\x1b[J{}", truncate(&header, width))?;
+        let shown = self.results.len().min(MAX_RESULTS);
+        if shown == 0 {
+            write!(out, "\r\n  (no matches)")?;
+        }
+        for (i, command) in self.results.iter().take(MAX_RESULTS).enumerate() {
+            // Multi-line commands are shown on one row.
+            let line = format!("  {}", command.replace('\n', " "));
+            let line = truncate(&line, width);
+            if i == self.selected {
+                write!(out, "\r\n\x1b[7m{}\x1b[0m", line)?;
+            } else {
+                write!(out, "\r\n{}", line)?;
+            }
+        }
+
+        write!(out, "\x1b[{}A\r", shown.max(1) + 1)?;
+        out.flush()
+    }
+}
+
+fn truncate(text: &str, width: usize) -> String {
+    text.chars().take(width.saturating_sub(1)).collect()
+}
+
+fn terminal_width() -> usize {
+    // SAFETY: `winsize` is plain old data that the ioctl fills in.
+    unsafe {
+        let mut size: libc::winsize = std::mem::zeroed();
+        if libc::ioctl(libc::STDOUT_FILENO, libc::TIOCGWINSZ, &mut size) == 0 && size.ws_col > 0 {
+            return size.ws_col as usize;
+        }
+    }
+    80
+}
+
+/// Reads one byte from stdin, waiting at most `timeout_ms` (or forever when negative).
+fn read_byte(timeout_ms: i32) -> io::Result<Option<u8>> {
+    let mut poll = libc::pollfd {
+        fd: libc::STDIN_FILENO,
+        events: libc::POLLIN,
+        revents: 0,
+    };
+    // SAFETY: `poll` and `byte` are valid for the duration of the calls.
+    unsafe {
+        match libc::poll(&mut poll, 1, timeout_ms) {
+            0 => return Ok(None),
+            n if n < 0 => return Err(io::Error::last_os_error()),
+            _ => {}
+        }
+        let mut byte = 0u8;
+        match libc::read(libc::STDIN_FILENO, (&mut byte as *mut u8).cast(), 1) {
+            1 => Ok(Some(byte)),
+            0 => Err(io::ErrorKind::UnexpectedEof.into()),
+            _ => Err(io::Error::last_os_error()),
+        }
+    }
+}
+
+fn read_key() -> io::Result<Key> {
+    let Some(byte) = read_byte(-1)? else {
+        return Ok(Key::Ignore);
+    };
+
+    Ok(match byte {
+        b'\r' | b'\n' | b'\t' => Key::Accept,
+        0x7f | 0x08 => Key::Backspace,
+        // Ctrl-R and Ctrl-N move to the next match, Ctrl-P to the previous one.
+        0x12 | 0x0e => Key::Down,
+        0x10 => Key::Up,
+        // Ctrl-T toggles between all history and this directory's.
+        0x14 => Key::ToggleDirectory,
+        // Ctrl-C and Ctrl-G cancel.
+        0x03 | 0x07 => Key::Cancel,
+        0x1b => read_escape_sequence()?,
+        b if b >= 0x20 => read_char(b)?,
+        _ => Key::Ignore,
+    })
+}
+
+fn read_escape_sequence() -> io::Result<Key> {
+    match read_byte(ESCAPE_TIMEOUT_MS)? {
+        None => Ok(Key::Cancel),
+        Some(b'[') | Some(b'O') => {
+            let mut byte = read_byte(ESCAPE_TIMEOUT_MS)?;
+            let key = match byte {
+                Some(b'A') => Key::Up,
+                Some(b'B') => Key::Down,
+                Some(b'C') => Key::Accept,
+                _ => Key::Ignore,
+            };
+            // Skip the parameters of any longer sequence up to its final byte.
+            while let Some(b) = byte
+                && !(0x40..=0x7e).contains(&b)
+            {
+                byte = read_byte(ESCAPE_TIMEOUT_MS)?;
+            }
+            Ok(key)
+        }
+        Some(_) => Ok(Key::Ignore),
+    }
+}
+
+/// Decodes a UTF-8 character whose first byte has already been read.
+fn read_char(first: u8) -> io::Result<Key> {
+    let len = match first {
+        0xc0..=0xdf => 2,
+        0xe0..=0xef => 3,
+        0xf0..=0xf7 => 4,
+        _ => 1,
+    };
+    let mut bytes = vec![first];
+    for _ in 1..len {
+        match read_byte(ESCAPE_TIMEOUT_MS)? {
+            Some(b) => bytes.push(b),
+            None => break,
+        }
+    }
+    Ok(std::str::from_utf8(&bytes)
+        .ok()
+        .and_then(|s| s.chars().next())
+        .map_or(Key::Ignore, Key::Char))
+}
@@ -1 +1,4 @@
 pub mod auto_complete_helper;
+pub mod completion_spec;
+pub mod highlighter;
+pub mod history_search;
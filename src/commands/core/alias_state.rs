@@ -0,0 +1,46 @@
+use std::collections::BTreeMap;
+use std::sync::Arc;
+use std::sync::Mutex;
+
+/// Aliases defined with `alias name=value`, kept sorted by name for listing.
+#[derive(Default)]
+pub struct AliasState {
+    aliases: BTreeMap<String, String>,
+}
+
+pub type SharedAliases = Arc<Mutex<AliasState>>;
+
+impl AliasState {
+    pub fn set(&mut self, name: &str, value: &str) {
+        self.aliases.insert(name.to_string(), value.to_string());
+    }
+
+    pub fn get(&self, name: &str) -> Option<String> {
+        self.aliases.get(name).cloned()
+    }
+
+    pub fn contains(&self, name: &str) -> bool {
+        self.aliases.contains_key(name)
+    }
+
+    /// Removes an alias, returning false when it was not defined.
+    pub fn remove(&mut self, name: &str) -> bool {
+        self.aliases.remove(name).is_some()
+    }
+
+    pub fn clear(&mut self) {
+        self.aliases.clear();
+    }
+
+    pub fn names(&self) -> Vec<String> {
+        self.aliases.keys().cloned().collect()
+    }
+
+    /// Every alias as `(name, value)`, sorted by name.
+    pub fn entries(&self) -> Vec<(String, String)> {
+        self.aliases
+            .iter()
+            .map(|(name, value)| (name.clone(), value.clone()))
+            .collect()
+    }
+}
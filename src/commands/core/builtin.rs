@@ -0,0 +1,87 @@
+use crate::commands::command_failure::CommandFailure;
+use std::collections::BTreeMap;
+use std::sync::{Arc, Mutex};
+
+/// Describes a builtin command. Each handler declares the builtins it implements,
+/// and `CommandRegistry` collects them into the `BuiltinTable` that parsing, `type`,
+/// `help` and completion consult, so a builtin is defined in one place.
+#[derive(Debug, Clone, Copy)]
+pub struct Builtin {
+    pub name: &'static str,
+    /// The synopsis, such as `cd [-L|-P] [dir]`.
+    pub usage: &'static str,
+    /// A one-line summary, optionally followed by a longer description.
+    pub help: &'static str,
+    /// The single-letter options the builtin accepts, offered by completion.
+    pub flags: &'static str,
+    /// POSIX special builtins, such as `set` and `exit`, cannot be overridden by
+    /// functions.
+    pub special: bool,
+}
+
+impl Builtin {
+    pub fn summary(&self) -> &'static str {
+        self.help.lines().next().unwrap_or_default()
+    }
+
+    /// The usage line printed after errors, e.g. `cd: usage: cd [-L|-P] [dir]`.
+    pub fn usage_line(&self) -> String {
+        format!("{}: usage: {}", self.name, self.usage)
+    }
+
+    /// A usage error for an unknown option, with status 2.
+    pub fn invalid_option(&self, option: &str) -> anyhow::Error {
+        CommandFailure::new(
+            2,
+            format!(
+                "{}: {}: invalid option\n{}",
+                self.name,
+                option,
+                self.usage_line()
+            ),
+        )
+        .into()
+    }
+}
+
+/// The builtins known to the shell, by name.
+#[derive(Debug, Default)]
+pub struct BuiltinTable {
+    builtins: BTreeMap<&'static str, Builtin>,
+}
+
+impl BuiltinTable {
+    /// Registers a builtin, replacing any earlier one with the same name.
+    pub fn register(&mut self, builtin: Builtin) {
+        self.builtins.insert(builtin.name, builtin);
+    }
+
+    pub fn remove(&mut self, name: &str) {
+        self.builtins.remove(name);
+    }
+
+    pub fn get(&self, name: &str) -> Option<Builtin> {
+        self.builtins.get(name).copied()
+    }
+
+    pub fn contains(&self, name: &str) -> bool {
+        self.builtins.contains_key(name)
+    }
+
+    pub fn is_special(&self, name: &str) -> bool {
+        self.get(name).is_some_and(|builtin| builtin.special)
+    }
+
+    /// Builtin names in sorted order.
+    pub fn names(&self) -> Vec<String> {
+        self.builtins.keys().map(|name| name.to_string()).collect()
+    }
+
+    /// Builtins in name order.
+    pub fn iter(&self) -> impl Iterator<Item = &Builtin> {
+        self.builtins.values()
+    }
+}
+
+/// A thread-safe, shared builtin table.
+pub type SharedBuiltins = Arc<Mutex<BuiltinTable>>;
@@ -0,0 +1,83 @@
+use std::fmt;
+use std::io;
+use std::os::unix::process::ExitStatusExt;
+use std::process::ExitStatus;
+
+/// An error that carries the exit status the failing command should report.
+///
+/// Handlers return it through `anyhow` when a failure needs a status other than
+/// the default `1`, such as `2` for usage errors or `127` for unknown commands.
+/// A failure can also carry standard output produced before it, such as the names
+/// `type` did find when some were missing.
+#[derive(Debug)]
+pub struct CommandFailure {
+    pub status: i32,
+    pub message: String,
+    pub output: String,
+}
+
+impl CommandFailure {
+    pub fn new(status: i32, message: impl Into<String>) -> Self {
+        Self {
+            status,
+            message: message.into(),
+            output: String::new(),
+        }
+    }
+
+    pub fn with_output(mut self, output: impl Into<String>) -> Self {
+        self.output = output.into();
+        self
+    }
+
+    /// The standard output carried by an error, empty unless it is a `CommandFailure`.
+    pub fn output_of(err: &anyhow::Error) -> &str {
+        err.downcast_ref::<Self>()
+            .map_or("", |failure| failure.output.as_str())
+    }
+
+    /// The exit status for a handler result: `0` on success, the carried status for
+    /// a `CommandFailure`, and `1` for any other error.
+    pub fn status_of<T>(result: &anyhow::Result<T>) -> i32 {
+        match result {
+            Ok(_) => 0,
+            Err(err) => err
+                .downcast_ref::<Self>()
+                .map_or(1, |failure| failure.status),
+        }
+    }
+
+    /// The status a finished process reports for `$?`. A process killed by a signal
+    /// reports 128 plus the signal number, as in bash.
+    pub fn exit_code(status: ExitStatus) -> i32 {
+        status
+            .code()
+            .or_else(|| status.signal().map(|signal| 128 + signal))
+            .unwrap_or(1)
+    }
+
+    /// A handler result for a command that finished with `status`: its output when
+    /// the status is `0`, and otherwise a failure with no message carrying it.
+    pub fn result_of(status: i32, output: String) -> anyhow::Result<String> {
+        if status == 0 {
+            return Ok(output);
+        }
+        Err(Self::new(status, "").with_output(output).into())
+    }
+
+    /// A failure to open `path`, worded as the shell reports it, such as
+    /// `file: No such file or directory`.
+    pub fn open_failed(path: &str, err: &io::Error) -> anyhow::Error {
+        let message = err.to_string();
+        let reason = message.split(" (os error").next().unwrap_or(&message);
+        Self::new(1, format!("{}: {}", path, reason)).into()
+    }
+}
+
+impl fmt::Display for CommandFailure {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.message)
+    }
+}
+
+impl std::error::Error for CommandFailure {}
@@ -1,12 +1,35 @@
+use crate::commands::builtin::Builtin;
+use crate::commands::shell_state::ShellState;
 use crate::commands::supported_command::SupportedCommand;
 use anyhow::Result;
 /// Trait defining the behavior of a shell command handler.
 pub trait CommandHandler: Send + Sync {
-    /// Returns true if this handler can process the given command.
-    fn can_handle(&self, cmd: &SupportedCommand) -> bool;
+    /// The builtins this handler implements. Registering the handler with the
+    /// `CommandRegistry` registers them by name.
+    fn builtins(&self) -> &'static [Builtin] {
+        &[]
+    }
 
-    /// Executes the command and returns the output as a string.
-    fn handle(&self, cmd: &SupportedCommand) -> Result<String>;
+    /// Returns true if this handler can process the given command. By default, that
+    /// is any of the builtins it declares.
+    fn can_handle(&self, cmd: &SupportedCommand) -> bool {
+        match cmd {
+            SupportedCommand::Builtin { name, .. } => {
+                self.builtins().iter().any(|builtin| builtin.name == name)
+            }
+            _ => false,
+        }
+    }
+
+    /// Executes the command in the session's `state` and returns the output as a
+    /// string.
+    fn handle(&self, cmd: &SupportedCommand, state: &mut ShellState) -> Result<String>;
+
+    /// Whether the output is written exactly as returned, as `printf` needs. Other
+    /// output is written as lines, ending in a single newline.
+    fn raw_output(&self) -> bool {
+        false
+    }
 
     /// Allows downcasting for specific handler implementations when needed.
     fn as_any(&self) -> &dyn std::any::Any;
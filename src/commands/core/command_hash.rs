@@ -0,0 +1,155 @@
+use crate::commands::path_helper::{PathHelper, SearchPath};
+use std::collections::HashMap;
+use std::fs;
+use std::os::unix::fs::PermissionsExt;
+use std::path::Path;
+use std::sync::{Arc, Mutex};
+use std::time::SystemTime;
+
+/// A remembered command location, as listed by `hash`.
+#[derive(Debug, Clone)]
+pub struct HashEntry {
+    pub path: String,
+    pub hits: usize,
+}
+
+/// Caches command lookups and the executables found on `PATH`. Lookups take the
+/// directories of `PATH` to search, which belong to the shell state rather than the
+/// process.
+///
+/// The cache is keyed on those directories and the modification time of every one
+/// of them, so editing `PATH` or installing a binary invalidates it on the next
+/// lookup. Commands found through a relative entry are not remembered, since they
+/// change with the working directory.
+#[derive(Default)]
+pub struct CommandHashTable {
+    path: SearchPath,
+    dir_mtimes: Vec<Option<SystemTime>>,
+    executables: Option<Vec<String>>,
+    hashed: HashMap<String, HashEntry>,
+}
+
+pub type SharedCommandHash = Arc<Mutex<CommandHashTable>>;
+
+impl CommandHashTable {
+    /// Resolves a command to the path of its executable, remembering the result.
+    /// Names containing a `/` are used as given.
+    pub fn find(&mut self, cmd: &str, path: &SearchPath) -> Option<String> {
+        if cmd.contains('/') {
+            return Self::is_executable(Path::new(cmd)).then(|| cmd.to_string());
+        }
+
+        self.validate(path);
+
+        if let Some(entry) = self.hashed.get_mut(cmd) {
+            if Self::is_executable(Path::new(&entry.path)) {
+                entry.hits += 1;
+                return Some(entry.path.clone());
+            }
+            self.hashed.remove(cmd);
+        }
+
+        let (found, relative) = PathHelper::locate_executable(cmd, path)?;
+        if !relative {
+            self.hashed.insert(
+                cmd.to_string(),
+                HashEntry {
+                    path: found.clone(),
+                    hits: 1,
+                },
+            );
+        }
+        Some(found)
+    }
+
+    /// Resolves a command without remembering it, preferring a remembered location.
+    pub fn lookup(&mut self, cmd: &str, path: &SearchPath) -> Option<String> {
+        if cmd.contains('/') {
+            return Self::is_executable(Path::new(cmd)).then(|| cmd.to_string());
+        }
+        match self.get(cmd, path) {
+            Some(entry) => Some(entry.path),
+            None => PathHelper::find_executable(cmd, path),
+        }
+    }
+
+    /// Looks a command up on `PATH` and remembers it without counting a hit.
+    pub fn remember(&mut self, cmd: &str, path: &SearchPath) -> Option<String> {
+        self.validate(path);
+        let (found, relative) = PathHelper::locate_executable(cmd, path)?;
+        if !relative {
+            self.set(cmd, &found);
+        }
+        Some(found)
+    }
+
+    /// Returns the remembered location of a command, if any.
+    pub fn get(&mut self, cmd: &str, path: &SearchPath) -> Option<HashEntry> {
+        self.validate(path);
+        self.hashed.get(cmd).cloned()
+    }
+
+    pub fn set(&mut self, cmd: &str, path: &str) {
+        self.hashed.insert(
+            cmd.to_string(),
+            HashEntry {
+                path: path.to_string(),
+                hits: 0,
+            },
+        );
+    }
+
+    pub fn remove(&mut self, cmd: &str) -> bool {
+        self.hashed.remove(cmd).is_some()
+    }
+
+    pub fn clear(&mut self) {
+        self.hashed.clear();
+        self.executables = None;
+    }
+
+    /// All remembered commands, sorted by name.
+    pub fn entries(&mut self, path: &SearchPath) -> Vec<(String, HashEntry)> {
+        self.validate(path);
+        let mut entries: Vec<_> = self
+            .hashed
+            .iter()
+            .map(|(name, entry)| (name.clone(), entry.clone()))
+            .collect();
+        entries.sort_by(|a, b| a.0.cmp(&b.0));
+        entries
+    }
+
+    /// Names of all executables on `PATH`, rescanned only when the cache is stale.
+    pub fn executables(&mut self, path: &SearchPath) -> Vec<String> {
+        self.validate(path);
+        self.executables
+            .get_or_insert_with(|| {
+                let mut names = PathHelper::get_all_executables(path).unwrap_or_default();
+                names.sort();
+                names.dedup();
+                names
+            })
+            .clone()
+    }
+
+    /// Drops everything cached when `PATH` or any directory on it has changed.
+    fn validate(&mut self, path: &SearchPath) {
+        let dir_mtimes: Vec<Option<SystemTime>> = path
+            .dirs()
+            .map(|dir| fs::metadata(dir).and_then(|m| m.modified()).ok())
+            .collect();
+
+        if *path != self.path || dir_mtimes != self.dir_mtimes {
+            self.path = path.clone();
+            self.dir_mtimes = dir_mtimes;
+            self.clear();
+        }
+    }
+
+    fn is_executable(path: &Path) -> bool {
+        fs::metadata(path)
+            .map(|m| m.is_file() && m.permissions().mode() & 0o111 != 0)
+            .unwrap_or(false)
+    }
+}
@@ -0,0 +1,144 @@
+use crate::commands::alias_state::SharedAliases;
+use crate::commands::builtin::SharedBuiltins;
+use crate::commands::command_hash::SharedCommandHash;
+use crate::commands::function_state::SharedFunctions;
+use crate::commands::path_helper::PathHelper;
+use crate::commands::shell_state::ShellState;
+
+/// Reserved words that are recognised in command position: those the script parser
+/// understands, so `type` does not report keywords the shell cannot run.
+pub const KEYWORDS: [&str; 8] = ["while", "until", "do", "done", "{", "}", "!", "function"];
+
+/// One way a command name can be run.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum CommandKind {
+    /// An alias and the text it expands to.
+    Alias(String),
+    Keyword,
+    /// A shell function and its body.
+    Function(String),
+    Builtin,
+    /// An executable file; `hashed` when its location came from the hash table.
+    File {
+        path: String,
+        hashed: bool,
+    },
+}
+
+impl CommandKind {
+    /// The single word `type -t` prints for this kind.
+    pub fn type_name(&self) -> &'static str {
+        match self {
+            CommandKind::Alias(_) => "alias",
+            CommandKind::Keyword => "keyword",
+            CommandKind::Function(_) => "function",
+            CommandKind::Builtin => "builtin",
+            CommandKind::File { .. } => "file",
+        }
+    }
+}
+
+/// Works out what a command name refers to, in the order the shell looks: aliases,
+/// keywords, special builtins, functions, other builtins, then executables in
+/// `PATH`. Shared by `type`, `which` and `command -v`.
+#[derive(Clone)]
+pub struct CommandResolver {
+    aliases: SharedAliases,
+    builtins: SharedBuiltins,
+    functions: SharedFunctions,
+    hash: SharedCommandHash,
+}
+
+impl CommandResolver {
+    pub fn new(
+        aliases: SharedAliases,
+        builtins: SharedBuiltins,
+        functions: SharedFunctions,
+        hash: SharedCommandHash,
+    ) -> Self {
+        Self {
+            aliases,
+            builtins,
+            functions,
+            hash,
+        }
+    }
+
+    /// Every meaning of `name` in lookup order when `all` is set, otherwise just
+    /// the one that would run. Functions are left out with `skip_functions`.
+    pub fn resolve(
+        &self,
+        state: &ShellState,
+        name: &str,
+        all: bool,
+        skip_functions: bool,
+    ) -> Vec<CommandKind> {
+        let mut kinds = Vec::new();
+
+        if let Some(value) = self.aliases.lock().unwrap().get(name) {
+            kinds.push(CommandKind::Alias(value));
+        }
+        if KEYWORDS.contains(&name) {
+            kinds.push(CommandKind::Keyword);
+        }
+        let (builtin, special) = {
+            let builtins = self.builtins.lock().unwrap();
+            (builtins.contains(name), builtins.is_special(name))
+        };
+        if special {
+            kinds.push(CommandKind::Builtin);
+        }
+        if !skip_functions && let Some(body) = self.functions.lock().unwrap().get(name) {
+            kinds.push(CommandKind::Function(body));
+        }
+        if builtin && !special {
+            kinds.push(CommandKind::Builtin);
+        }
+
+        if !all && !kinds.is_empty() {
+            kinds.truncate(1);
+            return kinds;
+        }
+        kinds.extend(self.find_files(state, name, all));
+        kinds
+    }
+
+    /// Executables named `name`: the one that would run, or with `all` every match
+    /// in `PATH`. A remembered location is preferred unless `all` is set.
+    pub fn find_files(&self, state: &ShellState, name: &str, all: bool) -> Vec<CommandKind> {
+        let search_path = state.search_path();
+        if name.contains('/') {
+            let located = state.resolve(name).display().to_string();
+            let mut hash = self.hash.lock().unwrap();
+            return hash
+                .lookup(&located, &search_path)
+                .map(|_| CommandKind::File {
+                    path: name.to_string(),
+                    hashed: false,
+                })
+                .into_iter()
+                .collect();
+        }
+
+        if !all && let Some(entry) = self.hash.lock().unwrap().get(name, &search_path) {
+            return vec![CommandKind::File {
+                path: entry.path,
+                hashed: true,
+            }];
+        }
+
+        let paths = PathHelper::find_all_executables(name, &search_path);
+        let paths = if all {
+            &paths[..]
+        } else {
+            &paths[..paths.len().min(1)]
+        };
+        paths
+            .iter()
+            .map(|path| CommandKind::File {
+                path: path.clone(),
+                hashed: false,
+            })
+            .collect()
+    }
+}
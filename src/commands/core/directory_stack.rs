@@ -0,0 +1,56 @@
+/// The directory stack used by `pushd`, `popd` and `dirs`.
+///
+/// As in bash, the top of the stack is always the current directory, so only the
+/// entries below it are stored; `full` puts the current directory back on top.
+#[derive(Debug, Default, Clone)]
+pub struct DirectoryStack {
+    entries: Vec<String>,
+}
+
+impl DirectoryStack {
+    /// The whole stack, top first, with `cwd` as the top entry.
+    pub fn full(&self, cwd: &str) -> Vec<String> {
+        let mut full = vec![cwd.to_string()];
+        full.extend(self.entries.iter().cloned());
+        full
+    }
+
+    /// Replaces the entries below the top with everything in `full` after its first element.
+    pub fn set_full(&mut self, full: Vec<String>) {
+        self.entries = full.into_iter().skip(1).collect();
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    pub fn clear(&mut self) {
+        self.entries.clear();
+    }
+
+    /// The entry `spec` names, with `cwd` as the top: `N` or `+N` counts from the top
+    /// starting at zero, `-N` from the bottom. `None` when it is out of range or
+    /// not a number.
+    pub fn get(&self, cwd: &str, spec: &str) -> Option<String> {
+        let full = self.full(cwd);
+        let index = Self::index(spec, full.len())?;
+        full.get(index).cloned()
+    }
+
+    /// Turns a `+N`/`-N` specifier into an index into a stack of `len` entries.
+    pub fn index(spec: &str, len: usize) -> Option<usize> {
+        let (from_bottom, digits) = match spec.as_bytes().first()? {
+            b'+' => (false, &spec[1..]),
+            b'-' => (true, &spec[1..]),
+            _ => (false, spec),
+        };
+        if digits.is_empty() || !digits.bytes().all(|b| b.is_ascii_digit()) {
+            return None;
+        }
+        let n: usize = digits.parse().ok()?;
+        if n >= len {
+            return None;
+        }
+        Some(if from_bottom { len - 1 - n } else { n })
+    }
+}
@@ -0,0 +1,231 @@
+use crate::commands::variable_state::VariableState;
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, Write};
+use std::os::fd::AsRawFd;
+use std::os::unix::fs::MetadataExt;
+use std::path::Path;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// Once the ranks add up to more than this, they are all aged so old directories fade.
+const MAX_TOTAL_RANK: f64 = 9000.0;
+
+/// Numbers the temporary files `save` writes, so that sessions in one process never
+/// share one.
+static SAVES: AtomicUsize = AtomicUsize::new(0);
+
+/// How directories are ordered when jumping or listing.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RankMode {
+    /// Visit count weighted by how recently the directory was visited.
+    Frecency,
+    /// Visit count only.
+    Rank,
+    /// Most recently visited first.
+    Recent,
+}
+
+#[derive(Debug, Clone)]
+pub struct DirectoryVisit {
+    pub path: String,
+    pub rank: f64,
+    /// Seconds since the Unix epoch of the last visit.
+    pub last_visit: i64,
+}
+
+impl DirectoryVisit {
+    pub fn score(&self, mode: RankMode, now: i64) -> f64 {
+        match mode {
+            RankMode::Rank => self.rank,
+            RankMode::Recent => -((now - self.last_visit) as f64),
+            RankMode::Frecency => {
+                let age = now - self.last_visit;
+                if age < 3600 {
+                    self.rank * 4.0
+                } else if age < 86400 {
+                    self.rank * 2.0
+                } else if age < 604800 {
+                    self.rank / 2.0
+                } else {
+                    self.rank / 4.0
+                }
+            }
+        }
+    }
+}
+
+/// Visited directories ranked by frecency, stored in the same `path|rank|time`
+/// format as `z`, so an existing `~/.z` database carries over.
+#[derive(Debug, Default)]
+pub struct FrecencyDatabase {
+    pub visits: Vec<DirectoryVisit>,
+}
+
+impl FrecencyDatabase {
+    /// The database file: `_Z_DATA`, or `~/.z` by default.
+    pub fn data_path(variables: &VariableState) -> Option<String> {
+        if let Some(path) = variables.get("_Z_DATA").filter(|p| !p.is_empty()) {
+            return Some(path);
+        }
+        let home = variables.get("HOME").filter(|h| !h.is_empty())?;
+        Some(format!("{}/.z", home.trim_end_matches('/')))
+    }
+
+    /// Loads the database, treating a missing file as empty and skipping malformed lines.
+    pub fn load(path: &str) -> Self {
+        let content = fs::read_to_string(path).unwrap_or_default();
+        let visits = content
+            .lines()
+            .filter_map(|line| {
+                let mut fields = line.rsplitn(3, '|');
+                let last_visit = fields.next()?.parse().ok()?;
+                let rank = fields.next()?.parse().ok()?;
+                let path = fields.next()?.to_string();
+                Some(DirectoryVisit {
+                    path,
+                    rank,
+                    last_visit,
+                })
+            })
+            .collect();
+        Self { visits }
+    }
+
+    /// Loads the database, lets `f` change it and saves it, all under an exclusive
+    /// `flock` on the file so concurrent shells never lose each other's changes.
+    pub fn update(path: &str, f: impl FnOnce(&mut Self)) -> io::Result<()> {
+        let _lock = lock(path)?;
+        let mut database = Self::load(path);
+        f(&mut database);
+        database.save(path)
+    }
+
+    /// Writes the database to a temporary file first and renames it into place, so a
+    /// concurrent reader never sees it half-written.
+    fn save(&self, path: &str) -> io::Result<()> {
+        let temp = format!(
+            "{}.{}.{}",
+            path,
+            std::process::id(),
+            SAVES.fetch_add(1, Ordering::Relaxed)
+        );
+        let mut file = fs::File::create(&temp)?;
+        for visit in &self.visits {
+            writeln!(file, "{}|{}|{}", visit.path, visit.rank, visit.last_visit)?;
+        }
+        drop(file);
+        fs::rename(&temp, path)
+    }
+
+    /// Records a visit to `dir`, ageing every rank once their total grows too large.
+    pub fn record(&mut self, dir: &str, now: i64) {
+        match self.visits.iter_mut().find(|visit| visit.path == dir) {
+            Some(visit) => {
+                visit.rank += 1.0;
+                visit.last_visit = now;
+            }
+            None => self.visits.push(DirectoryVisit {
+                path: dir.to_string(),
+                rank: 1.0,
+                last_visit: now,
+            }),
+        }
+
+        let total: f64 = self.visits.iter().map(|visit| visit.rank).sum();
+        if total > MAX_TOTAL_RANK {
+            for visit in &mut self.visits {
+                visit.rank *= 0.99;
+            }
+            self.visits.retain(|visit| visit.rank >= 1.0);
+        }
+    }
+
+    pub fn remove(&mut self, dir: &str) {
+        self.visits.retain(|visit| visit.path != dir);
+    }
+
+    /// The existing directories matching every fragment in order, best first.
+    /// Matching is case-sensitive unless that finds nothing.
+    pub fn rank(&self, fragments: &[String], mode: RankMode, now: i64) -> Vec<(f64, String)> {
+        let ranked = |case_sensitive: bool| {
+            let mut matches: Vec<(f64, String)> = self
+                .visits
+                .iter()
+                .filter(|visit| matches_fragments(&visit.path, fragments, case_sensitive))
+                .filter(|visit| Path::new(&visit.path).is_dir())
+                .map(|visit| (visit.score(mode, now), visit.path.clone()))
+                .collect();
+            matches.sort_by(|a, b| b.0.total_cmp(&a.0));
+            matches
+        };
+
+        let matches = ranked(true);
+        if matches.is_empty() {
+            ranked(false)
+        } else {
+            matches
+        }
+    }
+}
+
+/// Whether the fragments appear in `path` in order, without overlapping.
+fn matches_fragments(path: &str, fragments: &[String], case_sensitive: bool) -> bool {
+    let fold = |s: &str| {
+        if case_sensitive {
+            s.to_string()
+        } else {
+            s.to_lowercase()
+        }
+    };
+    let path = fold(path);
+    let mut rest = path.as_str();
+    for fragment in fragments {
+        let fragment = fold(fragment);
+        match rest.find(&fragment) {
+            Some(i) => rest = &rest[i + fragment.len()..],
+            None => return false,
+        }
+    }
+    true
+}
+
+/// Opens the database file, creating it if need be, and takes an exclusive `flock`
+/// on it, held until the file is dropped. `save` replaces the file, so when that
+/// happened while waiting, the lock is taken on the new one instead.
+fn lock(path: &str) -> io::Result<File> {
+    loop {
+        let file = OpenOptions::new().append(true).create(true).open(path)?;
+        // SAFETY: the descriptor stays valid for as long as `file` is alive.
+        if unsafe { libc::flock(file.as_raw_fd(), libc::LOCK_EX) } != 0 {
+            return Err(io::Error::last_os_error());
+        }
+        let locked = file.metadata()?;
+        match fs::metadata(path) {
+            Ok(current) if current.dev() == locked.dev() && current.ino() == locked.ino() => {
+                return Ok(file);
+            }
+            Ok(_) => continue,
+            Err(err) if err.kind() == io::ErrorKind::NotFound => continue,
+            Err(err) => return Err(err),
+        }
+    }
+}
+
+/// Records a visit to `dir` in the database named by the shell variables. Visits to
+/// `HOME` are not recorded, and database errors never get in the way of a `cd`.
+pub fn record_visit(variables: &VariableState, dir: &str) {
+    let Some(path) = FrecencyDatabase::data_path(variables) else {
+        return;
+    };
+    if variables.get("HOME").as_deref() == Some(dir) {
+        return;
+    }
+
+    let _ = FrecencyDatabase::update(&path, |database| database.record(dir, now()));
+}
+
+pub fn now() -> i64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |d| d.as_secs() as i64)
+}
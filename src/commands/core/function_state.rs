@@ -0,0 +1,30 @@
+use std::collections::HashMap;
+use std::sync::Arc;
+use std::sync::Mutex;
+
+/// Shell functions defined with `name() { ... }` or `function name { ... }`.
+/// Bodies are kept as source text and parsed each time the function is called.
+#[derive(Default)]
+pub struct FunctionState {
+    functions: HashMap<String, String>,
+}
+
+pub type SharedFunctions = Arc<Mutex<FunctionState>>;
+
+impl FunctionState {
+    pub fn define(&mut self, name: &str, body: &str) {
+        self.functions.insert(name.to_string(), body.to_string());
+    }
+
+    pub fn get(&self, name: &str) -> Option<String> {
+        self.functions.get(name).cloned()
+    }
+
+    pub fn contains(&self, name: &str) -> bool {
+        self.functions.contains_key(name)
+    }
+
+    pub fn names(&self) -> Vec<String> {
+        self.functions.keys().cloned().collect()
+    }
+}
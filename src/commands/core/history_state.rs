@@ -1,10 +1,338 @@
+use crate::commands::variable_state::VariableState;
+use crate::core::pattern;
+use std::fs::{File, OpenOptions};
+use std::io::{self, Read, Seek, Write};
+use std::os::fd::AsRawFd;
 use std::sync::Arc;
 use std::sync::Mutex;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// Number of entries kept when `HISTSIZE` is unset, as in bash.
+const DEFAULT_HISTSIZE: usize = 500;
+
+/// A command in the history list and when it was run.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct HistoryEntry {
+    pub command: String,
+    /// Seconds since the Unix epoch; `None` for entries read from a file without
+    /// timestamps.
+    pub timestamp: Option<i64>,
+    /// Working directory the command was entered in. Only known for entries
+    /// recorded by this session; the history file does not store it.
+    pub cwd: Option<String>,
+}
+
+impl HistoryEntry {
+    /// An entry stamped with the current time and the working directory `cwd`.
+    pub fn new(command: impl Into<String>, cwd: &str) -> Self {
+        Self {
+            command: command.into(),
+            timestamp: Some(now()),
+            cwd: Some(cwd.to_string()),
+        }
+    }
+
+    /// An entry read from a history file.
+    fn from_file(command: &str, timestamp: Option<i64>) -> Self {
+        Self {
+            command: command.to_string(),
+            timestamp,
+            cwd: None,
+        }
+    }
+}
+
+fn now() -> i64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |d| d.as_secs() as i64)
+}
 
 #[derive(Default)]
 pub struct HistoryState {
-    pub commands: Vec<String>,
+    pub entries: Vec<HistoryEntry>,
     pub last_appended_index: usize,
+    /// Entries dropped from the front to honour `HISTSIZE`; the first entry is
+    /// numbered `base + 1`.
+    pub base: usize,
+    /// Lines of the history file already read or written, so `history -n` only
+    /// picks up what other sessions appended since.
+    pub lines_in_file: usize,
 }
 
 pub type SharedHistory = Arc<Mutex<HistoryState>>;
+
+/// History settings read from `HISTSIZE`, `HISTFILESIZE`, `HISTCONTROL` and `HISTIGNORE`.
+#[derive(Debug, Clone)]
+pub struct HistoryControl {
+    /// Maximum entries kept in memory; `None` means unlimited.
+    pub size: Option<usize>,
+    /// Maximum entries kept in the history file; `None` disables truncation.
+    pub file_size: Option<usize>,
+    pub ignore_space: bool,
+    pub ignore_dups: bool,
+    pub erase_dups: bool,
+    pub ignore_patterns: Vec<String>,
+}
+
+impl HistoryControl {
+    pub fn from_variables(variables: &VariableState) -> Self {
+        let size = match variables.get("HISTSIZE") {
+            Some(value) => Self::parse_limit(&value),
+            None => Some(DEFAULT_HISTSIZE),
+        };
+        let file_size = match variables.get("HISTFILESIZE") {
+            Some(value) => Self::parse_limit(&value),
+            None => size,
+        };
+
+        let control = variables.get("HISTCONTROL").unwrap_or_default();
+        let control: Vec<&str> = control.split(':').collect();
+        let ignore_both = control.contains(&"ignoreboth");
+
+        Self {
+            size,
+            file_size,
+            ignore_space: ignore_both || control.contains(&"ignorespace"),
+            ignore_dups: ignore_both || control.contains(&"ignoredups"),
+            erase_dups: control.contains(&"erasedups"),
+            ignore_patterns: Self::split_patterns(&variables.get("HISTIGNORE").unwrap_or_default()),
+        }
+    }
+
+    /// Negative or non-numeric limits mean "no limit".
+    fn parse_limit(value: &str) -> Option<usize> {
+        value.trim().parse::<usize>().ok()
+    }
+
+    /// Splits `HISTIGNORE` on colons that are not escaped with a backslash.
+    fn split_patterns(value: &str) -> Vec<String> {
+        let mut patterns = Vec::new();
+        let mut current = String::new();
+        let mut chars = value.chars();
+        while let Some(c) = chars.next() {
+            match c {
+                '\\' if chars.clone().next() == Some(':') => current.push(chars.next().unwrap()),
+                ':' => patterns.push(std::mem::take(&mut current)),
+                _ => current.push(c),
+            }
+        }
+        patterns.push(current);
+        patterns.retain(|p| !p.is_empty());
+        patterns
+    }
+}
+
+impl HistoryState {
+    /// Records a line as it was typed in `cwd`, honouring the history controls.
+    /// Returns false when the line was not saved.
+    pub fn record(&mut self, line: &str, cwd: &str, control: &HistoryControl) -> bool {
+        if control.ignore_space && line.starts_with([' ', '\t']) {
+            return false;
+        }
+
+        let line = line.trim();
+        let previous = self.entries.last().map(|entry| entry.command.as_str());
+
+        if control.ignore_dups && previous == Some(line) {
+            return false;
+        }
+
+        let ignored = control.ignore_patterns.iter().any(|p| {
+            let p = p.replace('&', previous.unwrap_or_default());
+            pattern::matches(&p, line)
+        });
+        if ignored {
+            return false;
+        }
+
+        if control.erase_dups {
+            let mut i = 0;
+            while i < self.entries.len() {
+                if self.entries[i].command == line {
+                    self.remove(i);
+                } else {
+                    i += 1;
+                }
+            }
+        }
+
+        self.entries.push(HistoryEntry::new(line, cwd));
+        self.truncate(control.size);
+        true
+    }
+
+    /// Appends the entries not yet written to the history file at `path`. With
+    /// `merge`, entries other sessions appended since the file was last read are
+    /// added to the list too. Both happen under one exclusive lock, so the line
+    /// count the next merge starts from stays exact.
+    pub fn sync_file(&mut self, path: &str, merge: bool) -> io::Result<()> {
+        let mut options = OpenOptions::new();
+        options.read(true).append(true).create(true);
+        let mut file = open_locked(path, &options, true)?;
+        let mut content = String::new();
+        file.read_to_string(&mut content)?;
+        let line_count = content.lines().count();
+
+        let pending: String = self
+            .entries
+            .iter()
+            .skip(self.last_appended_index)
+            .map(format_entry)
+            .collect();
+        file.write_all(pending.as_bytes())?;
+
+        if merge {
+            let foreign = parse_history(content.lines().skip(self.lines_in_file));
+            self.entries.extend(foreign);
+            self.lines_in_file = line_count + pending.lines().count();
+        } else {
+            self.lines_in_file += pending.lines().count();
+        }
+        self.last_appended_index = self.entries.len();
+        Ok(())
+    }
+
+    /// The commands in the history list, oldest first.
+    pub fn commands(&self) -> Vec<String> {
+        self.entries
+            .iter()
+            .map(|entry| entry.command.clone())
+            .collect()
+    }
+
+    /// Removes the entry at `index`, keeping `last_appended_index` pointing at the
+    /// first entry not yet written to the history file.
+    pub fn remove(&mut self, index: usize) {
+        self.entries.remove(index);
+        if index < self.last_appended_index {
+            self.last_appended_index -= 1;
+        }
+    }
+
+    pub fn clear(&mut self) {
+        self.entries.clear();
+        self.last_appended_index = 0;
+        self.base = 0;
+    }
+
+    /// Drops the oldest entries so that at most `size` remain.
+    pub fn truncate(&mut self, size: Option<usize>) {
+        let Some(size) = size else {
+            return;
+        };
+        let excess = self.entries.len().saturating_sub(size);
+        if excess > 0 {
+            self.entries.drain(..excess);
+            self.base += excess;
+            self.last_appended_index = self.last_appended_index.saturating_sub(excess);
+        }
+    }
+}
+
+/// Parses history file lines. A `#<epoch>` line gives the time of the entry that
+/// follows, and every line up to the next timestamp belongs to that entry, so
+/// multi-line commands survive a round trip. Lines before any timestamp, as in
+/// files written without them, are one entry each.
+pub fn parse_history<'a>(lines: impl IntoIterator<Item = &'a str>) -> Vec<HistoryEntry> {
+    let mut entries: Vec<HistoryEntry> = Vec::new();
+    // Whether the last entry began with a timestamp and may take continuation lines.
+    let mut stamped = false;
+    let mut pending_timestamp = None;
+
+    for line in lines {
+        if let Some(timestamp) = parse_timestamp(line) {
+            pending_timestamp = Some(timestamp);
+            continue;
+        }
+
+        if let Some(timestamp) = pending_timestamp.take() {
+            entries.push(HistoryEntry::from_file(line, Some(timestamp)));
+            stamped = true;
+        } else if stamped && let Some(entry) = entries.last_mut() {
+            entry.command.push('\n');
+            entry.command.push_str(line);
+        } else if !line.trim().is_empty() {
+            entries.push(HistoryEntry::from_file(line, None));
+        }
+    }
+
+    for entry in &mut entries {
+        entry.command.truncate(entry.command.trim_end().len());
+    }
+    entries.retain(|entry| !entry.command.trim().is_empty());
+    entries
+}
+
+fn parse_timestamp(line: &str) -> Option<i64> {
+    let digits = line.strip_prefix('#')?;
+    if digits.is_empty() || !digits.bytes().all(|b| b.is_ascii_digit()) {
+        return None;
+    }
+    digits.parse().ok()
+}
+
+/// Formats an entry for the history file: a timestamp line, then the command.
+/// Entries without a known time are stamped with the current one, since the
+/// timestamp is what marks where a multi-line entry starts.
+pub fn format_entry(entry: &HistoryEntry) -> String {
+    let timestamp = entry.timestamp.unwrap_or_else(now);
+    format!("#{}\n{}\n", timestamp, entry.command)
+}
+
+/// Opens a history file and takes an advisory `flock` on it, held until the file
+/// is dropped, so concurrent sessions never interleave or clobber each other's writes.
+fn open_locked(path: &str, options: &OpenOptions, exclusive: bool) -> io::Result<File> {
+    let file = options.open(path)?;
+    let operation = if exclusive {
+        libc::LOCK_EX
+    } else {
+        libc::LOCK_SH
+    };
+    // SAFETY: the descriptor stays valid for as long as `file` is alive.
+    if unsafe { libc::flock(file.as_raw_fd(), operation) } != 0 {
+        return Err(io::Error::last_os_error());
+    }
+    Ok(file)
+}
+
+/// Reads a history file, skipping the first `skip` lines, and returns its entries
+/// along with the total number of lines in the file.
+pub fn read_history_file(path: &str, skip: usize) -> io::Result<(Vec<HistoryEntry>, usize)> {
+    let mut file = open_locked(path, OpenOptions::new().read(true), false)?;
+    let mut content = String::new();
+    file.read_to_string(&mut content)?;
+    let line_count = content.lines().count();
+    Ok((parse_history(content.lines().skip(skip)), line_count))
+}
+
+/// Replaces the contents of a history file with `entries`.
+pub fn write_history_file(path: &str, entries: &[HistoryEntry]) -> io::Result<()> {
+    let mut file = open_locked(path, OpenOptions::new().write(true).create(true), true)?;
+    // Truncate only once the lock is held, so a concurrent reader never sees the
+    // file half-written.
+    file.set_len(0)?;
+    let content: String = entries.iter().map(format_entry).collect();
+    file.write_all(content.as_bytes())
+}
+
+/// Keeps only the last `max_entries` entries of a history file and returns how
+/// many lines it holds afterwards.
+pub fn truncate_history_file(path: &str, max_entries: Option<usize>) -> io::Result<usize> {
+    let mut file = open_locked(path, OpenOptions::new().read(true).write(true), true)?;
+    let mut content = String::new();
+    file.read_to_string(&mut content)?;
+    let entries = parse_history(content.lines());
+    let Some(max_entries) = max_entries.filter(|max| entries.len() > *max) else {
+        return Ok(content.lines().count());
+    };
+
+    let kept: String = entries[entries.len() - max_entries..]
+        .iter()
+        .map(format_entry)
+        .collect();
+    file.set_len(0)?;
+    file.rewind()?;
+    file.write_all(kept.as_bytes())?;
+    Ok(kept.lines().count())
+}
@@ -1,4 +1,18 @@
+pub mod alias_state;
+pub mod builtin;
+pub mod command_failure;
 pub mod command_handler;
+pub mod command_hash;
+pub mod command_resolver;
+pub mod directory_stack;
+pub mod frecency;
+pub mod function_state;
 pub mod history_state;
+pub mod option_state;
+pub mod plugin_process;
+pub mod plugin_state;
 pub mod registry;
+pub mod shell_state;
 pub mod supported_command;
+pub mod trap_state;
+pub mod variable_state;
@@ -0,0 +1,198 @@
+use crate::core::glob::GlobOptions;
+
+/// Declares `OptionState` with a public field for each option, its defaults, the
+/// `OPTIONS` table and the `get` and `set` lookups by name, all from one list.
+/// Each entry is `name: kind, flag => default`, and the list is sorted by name.
+macro_rules! options {
+    ($($(#[$doc:meta])* $name:ident: $kind:ident, $flag:expr => $default:expr;)*) => {
+        /// Shell options toggled with `set` and `shopt`.
+        pub struct OptionState {
+            $($(#[$doc])* pub $name: bool,)*
+            /// Whether the shell reads commands from a user, shown as `i` in `$-`. It
+            /// cannot be changed with `set`.
+            pub interactive: bool,
+        }
+
+        impl Default for OptionState {
+            fn default() -> Self {
+                Self {
+                    $($name: $default,)*
+                    interactive: false,
+                }
+            }
+        }
+
+        /// Every option `set` and `shopt` know, sorted by name. `interactive` is not
+        /// one of them since it cannot be changed.
+        const OPTIONS: &[OptionSpec] = &[
+            $(OptionSpec::new(stringify!($name), OptionKind::$kind, $flag),)*
+        ];
+
+        impl OptionState {
+            pub fn get(&self, name: &str) -> Option<bool> {
+                match name {
+                    $(stringify!($name) => Some(self.$name),)*
+                    _ => None,
+                }
+            }
+
+            /// Sets an option by name, returning false when the name is unknown.
+            pub fn set(&mut self, name: &str, value: bool) -> bool {
+                match name {
+                    $(stringify!($name) => self.$name = value,)*
+                    _ => return false,
+                }
+                true
+            }
+        }
+    };
+}
+
+options! {
+    /// `shopt -s autocd`: a directory name typed as a command changes to it.
+    autocd: Shopt, None => false;
+    /// `shopt -s cdspell`: `cd` corrects small typos in directory names.
+    cdspell: Shopt, None => false;
+    /// `set -e` / `set -o errexit`: exit when a command fails, except where its status
+    /// is being tested, such as before `&&` or `||` or after `!`.
+    errexit: Set, Some('e') => false;
+    /// `shopt -s expand_aliases`: aliases are expanded in command position. On by
+    /// default in an interactive shell only.
+    expand_aliases: Shopt, None => false;
+    /// `shopt -s extglob`: patterns may use `?(...)`, `*(...)`, `+(...)`, `@(...)`
+    /// and `!(...)` with `|`-separated alternatives.
+    extglob: Shopt, None => false;
+    /// `shopt -s globstar`: `**` as a whole path component matches any number of
+    /// directories.
+    globstar: Shopt, None => false;
+    /// `shopt -s histappend`: append each command to `HISTFILE` as soon as it is
+    /// entered rather than when the shell exits.
+    histappend: Shopt, None => false;
+    /// `set -H` / `set -o histexpand`: expand `!` history references.
+    histexpand: Set, Some('H') => true;
+    /// `shopt -s histshare`: like `histappend`, and also merge commands other sessions
+    /// appended to `HISTFILE` before each prompt. An extension; bash has no such
+    /// option.
+    histshare: Shopt, None => false;
+    /// `shopt -s nocaseglob`: pathname expansion ignores case.
+    nocaseglob: Shopt, None => false;
+    /// `set -n` / `set -o noexec`: read commands without running them. Ignored by an
+    /// interactive shell.
+    noexec: Set, Some('n') => false;
+    /// `set -f` / `set -o noglob`: turn off pathname expansion.
+    noglob: Set, Some('f') => false;
+    /// `set -u` / `set -o nounset`: expanding an unset variable is an error.
+    nounset: Set, Some('u') => false;
+    /// `shopt -s nullglob`: a pattern matching no files expands to nothing rather
+    /// than to itself.
+    nullglob: Shopt, None => false;
+    /// `set -o pipefail`: a pipeline's status is that of its last failing command
+    /// rather than of its last command.
+    pipefail: Set, None => false;
+    /// `set -v` / `set -o verbose`: print input as it is read.
+    verbose: Set, Some('v') => false;
+    /// `set -x` / `set -o xtrace`: print each command, after expansion and prefixed
+    /// with `PS4`, before running it.
+    xtrace: Set, Some('x') => false;
+}
+
+/// Which builtin sets an option.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum OptionKind {
+    /// `set -o name`, or `shopt -o name`.
+    Set,
+    /// `shopt -s name`.
+    Shopt,
+}
+
+/// What the shell knows about an option besides its value.
+pub struct OptionSpec {
+    pub name: &'static str,
+    pub kind: OptionKind,
+    /// The single-letter `set` flag, which `$-` lists when the option is on.
+    pub flag: Option<char>,
+}
+
+impl OptionSpec {
+    const fn new(name: &'static str, kind: OptionKind, flag: Option<char>) -> Self {
+        Self { name, kind, flag }
+    }
+
+    /// Whether `set -o` accepts the option.
+    pub fn is_set_option(&self) -> bool {
+        self.kind == OptionKind::Set
+    }
+
+    /// Whether `shopt` without `-o` accepts the option.
+    pub fn is_shopt_option(&self) -> bool {
+        self.kind == OptionKind::Shopt
+    }
+}
+
+impl OptionState {
+    /// Every option, sorted by name.
+    pub fn specs() -> &'static [OptionSpec] {
+        OPTIONS
+    }
+
+    /// The option called `name`.
+    pub fn spec(name: &str) -> Option<&'static OptionSpec> {
+        OPTIONS.iter().find(|spec| spec.name == name)
+    }
+
+    /// Names accepted by `set -o`, in the order `set -o` lists them.
+    pub fn names() -> Vec<&'static str> {
+        OPTIONS
+            .iter()
+            .filter(|spec| spec.is_set_option())
+            .map(|spec| spec.name)
+            .collect()
+    }
+
+    /// Names accepted by `shopt`, in the order `shopt` lists them.
+    pub fn shopt_names() -> Vec<&'static str> {
+        OPTIONS
+            .iter()
+            .filter(|spec| spec.is_shopt_option())
+            .map(|spec| spec.name)
+            .collect()
+    }
+
+    /// Marks the shell as interactive, which also turns on `expand_aliases`.
+    pub fn set_interactive(&mut self) {
+        self.interactive = true;
+        self.expand_aliases = true;
+    }
+
+    /// Maps a single-letter `set` flag to its option name.
+    pub fn flag_name(flag: char) -> Option<&'static str> {
+        OPTIONS
+            .iter()
+            .find(|spec| spec.flag == Some(flag))
+            .map(|spec| spec.name)
+    }
+
+    /// The flags that are on, as `$-` expands them: lowercase letters first, each
+    /// group in alphabetical order.
+    pub fn flags(&self) -> String {
+        let mut flags: Vec<char> = OPTIONS
+            .iter()
+            .filter(|spec| self.get(spec.name) == Some(true))
+            .filter_map(|spec| spec.flag)
+            .collect();
+        if self.interactive {
+            flags.push('i');
+        }
+        flags.sort_by_key(|c| (c.is_ascii_uppercase(), *c));
+        flags.into_iter().collect()
+    }
+
+    /// The options that change how patterns match files.
+    pub fn glob_options(&self) -> GlobOptions {
+        GlobOptions {
+            extglob: self.extglob,
+            globstar: self.globstar,
+            nocaseglob: self.nocaseglob,
+        }
+    }
+}
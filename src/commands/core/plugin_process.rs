@@ -0,0 +1,195 @@
+use serde_json::{Value, json};
+use std::fmt;
+use std::io::{BufRead, BufReader, Read, Write};
+use std::os::fd::AsRawFd;
+use std::process::{Child, ChildStderr, ChildStdin, Command, Stdio};
+use std::sync::mpsc::{self, Receiver, RecvTimeoutError};
+use std::thread;
+use std::time::{Duration, Instant};
+
+/// How often the plugin's stderr is drained while waiting for a response, so a
+/// plugin that writes a lot to it does not block on a full pipe.
+const DRAIN_INTERVAL: Duration = Duration::from_millis(10);
+
+/// The error for a request the plugin did not answer in time.
+#[derive(Debug)]
+pub struct TimedOut {
+    method: String,
+}
+
+impl fmt::Display for TimedOut {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "no response to `{}`", self.method)
+    }
+}
+
+impl std::error::Error for TimedOut {}
+
+/// A plugin running as a child process, spoken to with JSON-RPC 2.0 messages, one
+/// JSON object per line on its stdin and stdout. The plugin's stderr is collected
+/// and handed to the command that ran it, so redirections and captures see it.
+///
+/// Running plugins out of process keeps them from crashing the shell: a plugin that
+/// exits or writes garbage only fails the request that was waiting on it.
+pub struct PluginProcess {
+    child: Child,
+    stdin: ChildStdin,
+    /// Lines read from the plugin's stdout by a background thread, so a request can
+    /// wait for its response with a timeout.
+    responses: Receiver<String>,
+    stderr: ChildStderr,
+    /// What the plugin has written to stderr and nobody has taken yet.
+    errors: Vec<u8>,
+    next_id: u64,
+    /// Set once the plugin's stdin or stdout has closed.
+    closed: bool,
+}
+
+impl PluginProcess {
+    /// Starts the plugin with `command`, which runs the executable at `path`.
+    pub fn spawn(mut command: Command, path: &str) -> anyhow::Result<Self> {
+        let mut child = command
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .spawn()
+            .map_err(|e| anyhow::anyhow!("{}: {}", path, e))?;
+
+        let stdin = child.stdin.take().expect("plugin stdin is piped");
+        let stdout = child.stdout.take().expect("plugin stdout is piped");
+        let stderr = child.stderr.take().expect("plugin stderr is piped");
+        // Draining stderr must not wait when the plugin has written nothing.
+        // SAFETY: fcntl only changes the flags of a descriptor `stderr` owns.
+        unsafe {
+            let flags = libc::fcntl(stderr.as_raw_fd(), libc::F_GETFL);
+            libc::fcntl(stderr.as_raw_fd(), libc::F_SETFL, flags | libc::O_NONBLOCK);
+        }
+        let (sender, responses) = mpsc::channel();
+        thread::spawn(move || {
+            for line in BufReader::new(stdout).lines() {
+                let Ok(line) = line else { break };
+                if sender.send(line).is_err() {
+                    break;
+                }
+            }
+        });
+
+        Ok(Self {
+            child,
+            stdin,
+            responses,
+            stderr,
+            errors: Vec::new(),
+            next_id: 1,
+            closed: false,
+        })
+    }
+
+    /// Sends a request and waits for its result, or for `timeout` when one is given,
+    /// failing with [`TimedOut`] after that. Lines that are not the matching
+    /// response are skipped.
+    pub fn request(
+        &mut self,
+        method: &str,
+        params: Value,
+        timeout: Option<Duration>,
+    ) -> anyhow::Result<Value> {
+        let id = self.next_id;
+        self.next_id += 1;
+        self.send(&json!({ "jsonrpc": "2.0", "id": id, "method": method, "params": params }))?;
+
+        let deadline = timeout.map(|timeout| Instant::now() + timeout);
+        loop {
+            self.drain_stderr();
+            let wait = deadline.map_or(DRAIN_INTERVAL, |deadline| {
+                let remaining = deadline.saturating_duration_since(Instant::now());
+                remaining.min(DRAIN_INTERVAL)
+            });
+            let line = match self.responses.recv_timeout(wait) {
+                Ok(line) => line,
+                Err(RecvTimeoutError::Timeout) => {
+                    if deadline.is_some_and(|deadline| Instant::now() >= deadline) {
+                        return Err(TimedOut {
+                            method: method.to_string(),
+                        }
+                        .into());
+                    }
+                    continue;
+                }
+                Err(RecvTimeoutError::Disconnected) => return Err(self.closed()),
+            };
+
+            let Ok(message) = serde_json::from_str::<Value>(&line) else {
+                continue;
+            };
+            if message.get("id").and_then(Value::as_u64) != Some(id) {
+                continue;
+            }
+            if let Some(error) = message.get("error") {
+                let text = error
+                    .get("message")
+                    .and_then(Value::as_str)
+                    .unwrap_or("unknown error");
+                anyhow::bail!("{}", text);
+            }
+            return Ok(message.get("result").cloned().unwrap_or(Value::Null));
+        }
+    }
+
+    /// Sends a notification, which gets no response.
+    pub fn notify(&mut self, method: &str, params: Value) -> anyhow::Result<()> {
+        self.send(&json!({ "jsonrpc": "2.0", "method": method, "params": params }))
+    }
+
+    /// What the plugin has written to stderr since this was last called. A plugin
+    /// writes its stderr before its response, so after a request this includes
+    /// everything written while handling it.
+    pub fn take_stderr(&mut self) -> String {
+        self.drain_stderr();
+        String::from_utf8_lossy(&std::mem::take(&mut self.errors)).into_owned()
+    }
+
+    /// Moves what is waiting in the plugin's stderr pipe into `errors`.
+    fn drain_stderr(&mut self) {
+        let mut buffer = [0; 4096];
+        while let Ok(read @ 1..) = self.stderr.read(&mut buffer) {
+            self.errors.extend_from_slice(&buffer[..read]);
+        }
+    }
+
+    /// Whether the process is still running and talking to the shell.
+    pub fn is_running(&mut self) -> bool {
+        !self.closed && matches!(self.child.try_wait(), Ok(None))
+    }
+
+    fn send(&mut self, message: &Value) -> anyhow::Result<()> {
+        if writeln!(self.stdin, "{}", message)
+            .and_then(|_| self.stdin.flush())
+            .is_err()
+        {
+            return Err(self.closed());
+        }
+        Ok(())
+    }
+
+    fn closed(&mut self) -> anyhow::Error {
+        self.closed = true;
+        anyhow::anyhow!("exited unexpectedly")
+    }
+}
+
+impl Drop for PluginProcess {
+    /// Asks the plugin to shut down, giving it a moment before it is killed.
+    fn drop(&mut self) {
+        let _ = self.notify("shutdown", Value::Null);
+        let deadline = Instant::now() + Duration::from_millis(200);
+        while Instant::now() < deadline {
+            if let Ok(Some(_)) = self.child.try_wait() {
+                return;
+            }
+            thread::sleep(Duration::from_millis(10));
+        }
+        let _ = self.child.kill();
+        let _ = self.child.wait();
+    }
+}
@@ -0,0 +1,249 @@
+use crate::auto_complete::completion_spec::CompletionSpec;
+use crate::commands::builtin::Builtin;
+use crate::commands::plugin_process::PluginProcess;
+use crate::commands::shell_state::ShellState;
+use serde_json::{Value, json};
+use std::io::{self, Write};
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+/// The version of the plugin protocol this shell speaks. A plugin reports the
+/// version it speaks when initialized, and is refused if they differ.
+pub const PROTOCOL_VERSION: u64 = 1;
+
+/// How long a plugin may take to initialize, to run a builtin or to produce its
+/// prompt segment.
+const INITIALIZE_TIMEOUT: Duration = Duration::from_secs(5);
+pub const RUN_TIMEOUT: Duration = Duration::from_secs(30);
+const PROMPT_TIMEOUT: Duration = Duration::from_millis(500);
+
+/// What running a plugin builtin produced.
+#[derive(Debug, Default)]
+pub struct PluginOutput {
+    pub status: i32,
+    pub stdout: String,
+    pub stderr: String,
+}
+
+/// A loaded plugin and what it registered.
+pub struct Plugin {
+    pub name: String,
+    pub path: String,
+    pub builtins: Vec<Builtin>,
+    /// Completion specs by command name.
+    pub completions: Vec<(String, CompletionSpec)>,
+    /// Whether the plugin contributes a segment to the prompt.
+    pub prompt: bool,
+    process: PluginProcess,
+}
+
+impl Plugin {
+    /// Starts the plugin at `path` in the session's working directory and
+    /// environment, and reads what it registers from its response to `initialize`.
+    pub fn load(path: &str, args: &[String], state: &ShellState) -> anyhow::Result<Self> {
+        let program = if path.contains('/') {
+            state.resolve(path)
+        } else {
+            PathBuf::from(path)
+        };
+        let mut command = state.command(program);
+        command.args(args);
+        let mut process = PluginProcess::spawn(command, path)?;
+        let manifest = process
+            .request(
+                "initialize",
+                json!({
+                    "protocol": PROTOCOL_VERSION,
+                    "shell": env!("CARGO_PKG_NAME"),
+                    "version": env!("CARGO_PKG_VERSION"),
+                }),
+                Some(INITIALIZE_TIMEOUT),
+            )
+            .map_err(|e| anyhow::anyhow!("{}: {}", path, e))?;
+
+        let protocol = manifest.get("protocol").and_then(Value::as_u64);
+        if protocol != Some(PROTOCOL_VERSION) {
+            anyhow::bail!(
+                "{}: unsupported protocol version {}, expected {}",
+                path,
+                protocol.map_or("(none)".to_string(), |v| v.to_string()),
+                PROTOCOL_VERSION
+            );
+        }
+        let Some(name) = manifest.get("name").and_then(Value::as_str) else {
+            anyhow::bail!("{}: the plugin did not report a name", path);
+        };
+
+        let builtins = manifest
+            .get("builtins")
+            .and_then(Value::as_array)
+            .map(|builtins| builtins.iter().filter_map(parse_builtin).collect())
+            .unwrap_or_default();
+        let completions = manifest
+            .get("completions")
+            .and_then(Value::as_array)
+            .map(|specs| specs.iter().filter_map(parse_completion).collect())
+            .unwrap_or_default();
+        let prompt = manifest
+            .get("prompt")
+            .and_then(Value::as_bool)
+            .unwrap_or(false);
+
+        Ok(Self {
+            name: name.to_string(),
+            path: path.to_string(),
+            builtins,
+            completions,
+            prompt,
+            process,
+        })
+    }
+
+    pub fn is_running(&mut self) -> bool {
+        self.process.is_running()
+    }
+
+    /// What the plugin has written to its stderr since this was last called.
+    pub fn take_stderr(&mut self) -> String {
+        self.process.take_stderr()
+    }
+
+    /// Runs one of the plugin's builtins in `cwd`, with `$?` as `status`. The
+    /// output's stderr is what the plugin wrote to its stderr, then what it answered.
+    pub fn run(
+        &mut self,
+        name: &str,
+        args: &[String],
+        cwd: &str,
+        status: i32,
+    ) -> anyhow::Result<PluginOutput> {
+        let result = self.process.request(
+            "run",
+            json!({ "name": name, "args": args, "cwd": cwd, "status": status }),
+            Some(RUN_TIMEOUT),
+        )?;
+        let text = |key: &str| {
+            result
+                .get(key)
+                .and_then(Value::as_str)
+                .unwrap_or_default()
+                .to_string()
+        };
+        Ok(PluginOutput {
+            status: result.get("status").and_then(Value::as_i64).unwrap_or(0) as i32,
+            stdout: text("stdout"),
+            stderr: self.process.take_stderr() + &text("stderr"),
+        })
+    }
+
+    /// The plugin's prompt segment, or nothing if it does not answer in time. What
+    /// it writes to stderr meanwhile goes to the terminal the prompt is drawn on.
+    fn prompt_segment(&mut self, cwd: &str, status: i32) -> String {
+        let segment = self
+            .process
+            .request(
+                "prompt",
+                json!({ "cwd": cwd, "status": status }),
+                Some(PROMPT_TIMEOUT),
+            )
+            .ok()
+            .and_then(|result| {
+                result
+                    .get("segment")
+                    .and_then(Value::as_str)
+                    .map(str::to_string)
+            })
+            .unwrap_or_default();
+        let mut stderr = io::stderr();
+        let _ = stderr.write_all(self.process.take_stderr().as_bytes());
+        let _ = stderr.flush();
+        segment
+    }
+}
+
+/// Builtin metadata from a plugin. The strings live for the rest of the session,
+/// as the `BuiltinTable` holds static metadata; plugins cannot add special builtins.
+fn parse_builtin(value: &Value) -> Option<Builtin> {
+    let leak = |key: &str| -> &'static str {
+        let text = value.get(key).and_then(Value::as_str).unwrap_or_default();
+        Box::leak(text.to_string().into_boxed_str())
+    };
+    value.get("name").and_then(Value::as_str)?;
+    Some(Builtin {
+        name: leak("name"),
+        usage: leak("usage"),
+        help: leak("help"),
+        flags: leak("flags"),
+        special: false,
+    })
+}
+
+/// A completion spec from a plugin: `command` with `words` (a string or a list),
+/// `files`, `directories` and `commands`, like the options of `complete`.
+fn parse_completion(value: &Value) -> Option<(String, CompletionSpec)> {
+    let command = value.get("command").and_then(Value::as_str)?;
+    let flag = |key: &str| value.get(key).and_then(Value::as_bool).unwrap_or(false);
+    let word_list = match value.get("words") {
+        Some(Value::String(words)) => Some(words.clone()),
+        Some(Value::Array(words)) => Some(
+            words
+                .iter()
+                .filter_map(Value::as_str)
+                .collect::<Vec<_>>()
+                .join(" "),
+        ),
+        _ => None,
+    };
+    let spec = CompletionSpec {
+        word_list,
+        function: None,
+        directories: flag("directories"),
+        files: flag("files"),
+        commands: flag("commands"),
+    };
+    Some((command.to_string(), spec))
+}
+
+/// The plugins loaded with `plugin`, in load order.
+#[derive(Default)]
+pub struct PluginState {
+    plugins: Vec<Plugin>,
+}
+
+pub type SharedPlugins = Arc<Mutex<PluginState>>;
+
+impl PluginState {
+    pub fn add(&mut self, plugin: Plugin) {
+        self.plugins.push(plugin);
+    }
+
+    pub fn remove(&mut self, name: &str) -> Option<Plugin> {
+        let index = self.plugins.iter().position(|p| p.name == name)?;
+        Some(self.plugins.remove(index))
+    }
+
+    pub fn get(&self, name: &str) -> Option<&Plugin> {
+        self.plugins.iter().find(|p| p.name == name)
+    }
+
+    pub fn plugins(&self) -> &[Plugin] {
+        &self.plugins
+    }
+
+    /// The plugin that registered the builtin `name`.
+    pub fn owner_of(&mut self, name: &str) -> Option<&mut Plugin> {
+        self.plugins
+            .iter_mut()
+            .find(|p| p.builtins.iter().any(|builtin| builtin.name == name))
+    }
+
+    /// The prompt segments of every plugin that contributes one, in load order.
+    pub fn prompt(&mut self, cwd: &str, status: i32) -> String {
+        self.plugins
+            .iter_mut()
+            .filter(|p| p.prompt)
+            .map(|p| p.prompt_segment(cwd, status))
+            .collect()
+    }
+}
@@ -1,34 +1,98 @@
+use crate::auto_complete::completion_spec::SharedCompletions;
 use crate::commands::{
+    alias_command_handler::AliasCommandHandler, alias_state::SharedAliases,
+    assignment_command_handler::AssignmentCommandHandler, builtin::SharedBuiltins,
     cd_command_handler::ChangeDirCommandHandler, command_handler::CommandHandler,
-    echo_command_handler::EchoCommandHandler, history_command_handler::HistoryCommandHandler,
-    history_state::HistoryState, locate_command_handler::LocateCommandHandler,
-    pwd_command_handler::PwdCommandHandler, type_command_handler::TypeCommandHandler,
-    unspecified_command_handler::UnspecifiedCommandHandler,
+    command_hash::SharedCommandHash, command_resolver::CommandResolver,
+    compgen_command_handler::CompGenCommandHandler,
+    complete_command_handler::CompleteCommandHandler, dirs_command_handler::DirsCommandHandler,
+    echo_command_handler::EchoCommandHandler, exit_command_handler::ExitCommandHandler,
+    export_command_handler::ExportCommandHandler, function_state::SharedFunctions,
+    hash_command_handler::HashCommandHandler, help_command_handler::HelpCommandHandler,
+    history_command_handler::HistoryCommandHandler, history_state::SharedHistory,
+    plugin_command_handler::PluginCommandHandler, plugin_state::SharedPlugins,
+    printf_command_handler::PrintfCommandHandler, pwd_command_handler::PwdCommandHandler,
+    read_command_handler::ReadCommandHandler, return_command_handler::ReturnCommandHandler,
+    set_command_handler::SetCommandHandler, shopt_command_handler::ShoptCommandHandler,
+    trap_command_handler::TrapCommandHandler, type_command_handler::TypeCommandHandler,
+    unspecified_command_handler::UnspecifiedCommandHandler, z_command_handler::ZCommandHandler,
 };
-use std::sync::{Arc, Mutex};
+use std::sync::Arc;
 
 pub type Handler = Arc<dyn CommandHandler>;
 
+/// The command handlers, and the builtins they declare registered by name.
 pub struct CommandRegistry {
     handlers: Vec<Handler>,
+    builtins: SharedBuiltins,
 }
 
 impl CommandRegistry {
-    pub fn new(state: Arc<Mutex<HistoryState>>) -> Self {
+    /// Builds the handlers. Those that need the working directory, variables or
+    /// options get them from the `ShellState` passed to `handle`.
+    pub fn new(
+        history: SharedHistory,
+        functions: SharedFunctions,
+        completions: SharedCompletions,
+        hash: SharedCommandHash,
+        aliases: SharedAliases,
+        plugins: SharedPlugins,
+    ) -> Self {
+        let builtins = SharedBuiltins::default();
         let handlers: Vec<Handler> = vec![
             Arc::new(EchoCommandHandler),
-            Arc::new(TypeCommandHandler),
-            Arc::new(LocateCommandHandler),
-            Arc::new(UnspecifiedCommandHandler),
+            Arc::new(PrintfCommandHandler),
+            Arc::new(ExitCommandHandler),
+            Arc::new(ReturnCommandHandler),
+            Arc::new(HelpCommandHandler::new(builtins.clone())),
+            Arc::new(TypeCommandHandler::new(CommandResolver::new(
+                aliases.clone(),
+                builtins.clone(),
+                functions.clone(),
+                hash.clone(),
+            ))),
+            Arc::new(UnspecifiedCommandHandler::new(hash.clone())),
             Arc::new(PwdCommandHandler),
             Arc::new(ChangeDirCommandHandler),
-            Arc::new(HistoryCommandHandler::new(state)),
+            Arc::new(DirsCommandHandler),
+            Arc::new(ZCommandHandler),
+            Arc::new(HistoryCommandHandler::new(history)),
+            Arc::new(AssignmentCommandHandler),
+            Arc::new(CompleteCommandHandler::new(completions.clone())),
+            Arc::new(CompGenCommandHandler::new(
+                builtins.clone(),
+                functions,
+                hash.clone(),
+            )),
+            Arc::new(HashCommandHandler::new(hash)),
+            Arc::new(SetCommandHandler),
+            Arc::new(ExportCommandHandler),
+            Arc::new(ShoptCommandHandler),
+            Arc::new(TrapCommandHandler),
+            Arc::new(ReadCommandHandler),
+            Arc::new(AliasCommandHandler::new(aliases)),
+            Arc::new(PluginCommandHandler::new(
+                plugins,
+                builtins.clone(),
+                completions,
+            )),
         ];
 
-        Self { handlers }
+        {
+            let mut table = builtins.lock().unwrap();
+            for builtin in handlers.iter().flat_map(|handler| handler.builtins()) {
+                table.register(*builtin);
+            }
+        }
+
+        Self { handlers, builtins }
     }
 
     pub fn handlers(&self) -> Vec<Handler> {
         self.handlers.clone()
     }
+
+    pub fn builtins(&self) -> SharedBuiltins {
+        self.builtins.clone()
+    }
 }
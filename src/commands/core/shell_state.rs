@@ -0,0 +1,238 @@
+use crate::commands::directory_stack::DirectoryStack;
+use crate::commands::option_state::OptionState;
+use crate::commands::path_helper::SearchPath;
+use crate::commands::trap_state::TrapState;
+use crate::commands::variable_state::VariableState;
+use crate::core::utils::PathHelper;
+use std::io::Write;
+use std::os::fd::{AsRawFd, OwnedFd, RawFd};
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
+use std::sync::{Arc, Mutex};
+use std::{env, fs, io};
+
+/// What a shell session's commands run in: the working directory and directory
+/// stack, the variables and environment, the options, the traps, the positional
+/// parameters, and standard input and standard error.
+///
+/// The shell reads the process's working directory and environment once, when it
+/// starts, and never changes them. Commands get the state's instead, through
+/// `command`, and relative paths are resolved against it, so several sessions can
+/// run side by side in one process.
+pub struct ShellState {
+    /// The logical working directory, which `PWD` shows. It keeps the symlinks
+    /// `cd` followed.
+    cwd: String,
+    /// Directories saved by `pushd`, also reachable as `~N`.
+    directory_stack: DirectoryStack,
+    /// Shell variables and the environment.
+    pub variables: VariableState,
+    pub options: OptionState,
+    pub traps: TrapState,
+    /// Positional parameters (`$1`, `$2`, ...) of each running function call.
+    positional: Vec<Vec<String>>,
+    /// Where standard input is redirected from, such as a file given with `<` or
+    /// the pipe from an earlier pipeline stage. `None` is the shell's own.
+    stdin: Option<OwnedFd>,
+    /// Where builtins write to standard error as they run, such as the prompt of
+    /// `read -p`: a file given with `2>`, or the pipe capturing it for
+    /// `ShellSession::eval`. `None` is the shell's own.
+    stderr: Option<OwnedFd>,
+}
+
+pub type SharedState = Arc<Mutex<ShellState>>;
+
+impl ShellState {
+    pub fn new(
+        cwd: impl Into<String>,
+        environment: impl IntoIterator<Item = (String, String)>,
+    ) -> Self {
+        let cwd = cwd.into();
+        let mut variables = VariableState::with_environment(environment);
+        variables.set_scalar("PWD", cwd.as_str());
+        Self {
+            cwd,
+            directory_stack: DirectoryStack::default(),
+            variables,
+            options: OptionState::default(),
+            traps: TrapState::default(),
+            positional: Vec::new(),
+            stdin: None,
+            stderr: None,
+        }
+    }
+
+    /// A state starting from the process's environment and working directory. An
+    /// inherited `PWD` is kept when it names that directory.
+    pub fn from_process() -> Self {
+        let environment: Vec<(String, String)> = env::vars_os()
+            .filter_map(|(name, value)| Some((name.into_string().ok()?, value.into_string().ok()?)))
+            .collect();
+        let physical = PathHelper::get_current_dir().unwrap_or_else(|| "/".to_string());
+        let pwd = environment
+            .iter()
+            .find(|(name, _)| name == "PWD")
+            .map(|(_, value)| value.as_str());
+        let cwd = PathHelper::logical_dir(pwd, &physical).unwrap_or(physical);
+        Self::new(cwd, environment)
+    }
+
+    /// The logical working directory.
+    pub fn cwd(&self) -> &str {
+        &self.cwd
+    }
+
+    pub fn directory_stack(&self) -> &DirectoryStack {
+        &self.directory_stack
+    }
+
+    pub fn directory_stack_mut(&mut self) -> &mut DirectoryStack {
+        &mut self.directory_stack
+    }
+
+    /// The working directory with symlinks resolved, as `pwd -P` shows it.
+    pub fn physical_cwd(&self) -> Option<String> {
+        fs::canonicalize(&self.cwd)
+            .ok()
+            .map(|dir| dir.display().to_string())
+    }
+
+    /// `path` relative to the working directory, or as given when it is absolute.
+    pub fn resolve(&self, path: impl AsRef<Path>) -> PathBuf {
+        Path::new(&self.cwd).join(path)
+    }
+
+    /// The history file named by `HISTFILE`, relative to the working directory, or
+    /// `None` when it is unset or empty.
+    pub fn history_file(&self) -> Option<String> {
+        let path = self
+            .variables
+            .get("HISTFILE")
+            .filter(|path| !path.is_empty())?;
+        Some(self.resolve(path).display().to_string())
+    }
+
+    /// Changes the working directory to `dir`, relative to the current one. With
+    /// `physical` set, symlinks in the new directory are resolved; otherwise `.`
+    /// and `..` are resolved textually.
+    pub fn change_dir(&mut self, dir: &str, physical: bool) -> io::Result<()> {
+        let target = self.resolve(dir);
+        if !fs::metadata(&target)?.is_dir() {
+            return Err(io::ErrorKind::NotADirectory.into());
+        }
+        if !PathHelper::is_searchable(&target) {
+            return Err(io::ErrorKind::PermissionDenied.into());
+        }
+
+        self.cwd = if physical {
+            target.canonicalize()?.display().to_string()
+        } else {
+            PathHelper::normalize_logical(&target.display().to_string())
+        };
+        Ok(())
+    }
+
+    /// The directories of `PATH`, where commands are looked up, with relative
+    /// entries taken relative to the working directory.
+    pub fn search_path(&self) -> SearchPath {
+        SearchPath::new(&self.variables.get("PATH").unwrap_or_default(), &self.cwd)
+    }
+
+    /// A command for `program` that runs in the working directory with the
+    /// state's environment.
+    pub fn command(&self, program: impl AsRef<Path>) -> Command {
+        let mut command = Command::new(program.as_ref());
+        command
+            .current_dir(&self.cwd)
+            .env_clear()
+            .envs(self.variables.environment());
+        command
+    }
+
+    /// The descriptor of standard input, which builtins such as `read` read from.
+    pub fn stdin_fd(&self) -> RawFd {
+        match &self.stdin {
+            Some(fd) => fd.as_raw_fd(),
+            None => libc::STDIN_FILENO,
+        }
+    }
+
+    /// Standard input for a child process.
+    pub fn child_stdin(&self) -> Stdio {
+        match self.stdin.as_ref().map(OwnedFd::try_clone) {
+            Some(Ok(fd)) => Stdio::from(fd),
+            _ => Stdio::inherit(),
+        }
+    }
+
+    /// Redirects standard input, returning what it was so it can be put back.
+    pub fn replace_stdin(&mut self, stdin: Option<OwnedFd>) -> Option<OwnedFd> {
+        std::mem::replace(&mut self.stdin, stdin)
+    }
+
+    /// Redirects standard error, returning what it was so it can be put back.
+    pub fn replace_stderr(&mut self, stderr: Option<OwnedFd>) -> Option<OwnedFd> {
+        std::mem::replace(&mut self.stderr, stderr)
+    }
+
+    /// Writes `text` to standard error, for builtins that write to it as they run
+    /// rather than through their result.
+    pub fn write_stderr(&self, text: &str) -> io::Result<()> {
+        match &self.stderr {
+            Some(fd) => fs::File::from(fd.try_clone()?).write_all(text.as_bytes()),
+            None => {
+                let mut stderr = io::stderr();
+                stderr.write_all(text.as_bytes())?;
+                stderr.flush()
+            }
+        }
+    }
+
+    /// Runs `f` with `assignments` made and exported, then puts the variables back.
+    pub fn with_assignments<T>(
+        &mut self,
+        assignments: &[(String, String)],
+        f: impl FnOnce(&mut Self) -> T,
+    ) -> T {
+        let saved = self.variables.assign_temporarily(assignments);
+        let result = f(self);
+        self.variables.restore(saved);
+        result
+    }
+
+    /// Positional parameters of the innermost function call.
+    pub fn positional(&self) -> &[String] {
+        self.positional
+            .last()
+            .map(Vec::as_slice)
+            .unwrap_or_default()
+    }
+
+    /// Replaces the positional parameters of the innermost function call, or of the
+    /// shell outside one, as `set --` does.
+    pub fn set_positional(&mut self, args: Vec<String>) {
+        match self.positional.last_mut() {
+            Some(positional) => *positional = args,
+            None => self.positional.push(args),
+        }
+    }
+
+    pub fn push_positional(&mut self, args: Vec<String>) {
+        self.positional.push(args);
+    }
+
+    pub fn pop_positional(&mut self) {
+        self.positional.pop();
+    }
+}
+
+/// A pipe that reads back `content`, such as the output of a builtin for the next
+/// stage of a pipeline. It is written from a separate thread, so content larger
+/// than the pipe buffer does not block.
+pub fn pipe_input(content: String) -> io::Result<OwnedFd> {
+    let (reader, mut writer) = io::pipe()?;
+    std::thread::spawn(move || {
+        let _ = writer.write_all(content.as_bytes());
+    });
+    Ok(reader.into())
+}
@@ -1,27 +1,18 @@
+use crate::commands::variable_state::VariableValue;
+use crate::parsing::command_parser::CommandParser;
+
 #[derive(Debug, Clone)]
 pub enum SupportedCommand {
-    Echo {
+    /// A builtin registered by name in the `BuiltinTable`.
+    Builtin {
+        name: String,
         args: Vec<String>,
     },
-    Exit,
-    TypeCheck {
-        cmd: String,
-    },
-    LocateExecutable {
-        cmd: String,
-    },
     Unspecified {
         cmd: String,
         args: Vec<String>,
     },
     NoArgument,
-    Pwd,
-    ChangeDir {
-        path: String,
-    },
-    History {
-        action: HistoryAction,
-    },
     Redirection {
         kind: RedirectionKind,
         inner_cmd: Box<SupportedCommand>,
@@ -30,29 +21,161 @@ pub enum SupportedCommand {
     Pipeline {
         commands: Box<Vec<SupportedCommand>>,
     },
+    Assignment {
+        name: String,
+        value: VariableValue,
+    },
+    /// A command run with variables assigned and exported only while it runs, as
+    /// in `LANG=C sort`. With no command, as in `a=1 b=2`, the assignments stay.
+    Prefixed {
+        assignments: Vec<(String, String)>,
+        inner_cmd: Box<SupportedCommand>,
+    },
 }
 
 #[derive(Debug, Clone)]
 pub enum HistoryAction {
-    Display { limit: Option<usize> },
-    Read { path: String },
-    Write { path: String },
-    Append { path: String },
+    Display {
+        limit: Option<usize>,
+    },
+    Read {
+        path: String,
+    },
+    Write {
+        path: String,
+    },
+    Append {
+        path: String,
+    },
+    /// `-n`: read the lines added to the file since it was last read or written.
+    ReadNew {
+        path: String,
+    },
+    Clear,
+    /// `-d offset` or `-d start-end`; negative offsets count back from the end.
+    Delete {
+        offset: String,
+    },
+    /// `-s`: replace the `history -s` entry with the arguments.
+    Store {
+        args: Vec<String>,
+    },
+    /// `-p`: history-expand the arguments and print them without storing them.
+    Print {
+        args: Vec<String>,
+    },
+    /// An unknown option, or a count that is not a number.
+    Invalid {
+        arg: String,
+    },
+    MissingArgument {
+        option: String,
+    },
+}
+
+impl HistoryAction {
+    pub fn parse(args: &[String]) -> Self {
+        let path = args.get(1).cloned().unwrap_or_default();
+        match args.first().map(String::as_str) {
+            None => HistoryAction::Display { limit: None },
+            Some("-c") => HistoryAction::Clear,
+            Some("-r") => HistoryAction::Read { path },
+            Some("-w") => HistoryAction::Write { path },
+            Some("-a") => HistoryAction::Append { path },
+            Some("-n") => HistoryAction::ReadNew { path },
+            Some("-d") => match args.get(1) {
+                Some(offset) => HistoryAction::Delete {
+                    offset: offset.clone(),
+                },
+                None => HistoryAction::MissingArgument {
+                    option: "-d".to_string(),
+                },
+            },
+            Some("-s") => HistoryAction::Store {
+                args: args[1..].to_vec(),
+            },
+            Some("-p") => HistoryAction::Print {
+                args: args[1..].to_vec(),
+            },
+            Some(arg) => match arg.parse::<usize>() {
+                Ok(limit) => HistoryAction::Display { limit: Some(limit) },
+                Err(_) => HistoryAction::Invalid {
+                    arg: arg.to_string(),
+                },
+            },
+        }
+    }
 }
 
 impl SupportedCommand {
-    pub fn commands() -> Vec<String> {
-        vec![
-            "echo".to_string(),
-            "exit".to_string(),
-            "type".to_string(),
-            "pwd".to_string(),
-            "history".to_string(),
-        ]
+    /// The expanded command as `xtrace` prints it: a line for each command of a
+    /// pipeline, with its words quoted and without its redirections.
+    pub fn trace_lines(&self) -> Vec<String> {
+        let quote_all = |words: Vec<&String>| {
+            let words: Vec<String> = words.into_iter().map(|w| CommandParser::quote(w)).collect();
+            words.join(" ")
+        };
+        match self {
+            SupportedCommand::Builtin { name, args }
+            | SupportedCommand::Unspecified { cmd: name, args } => {
+                vec![quote_all(std::iter::once(name).chain(args).collect())]
+            }
+            SupportedCommand::Redirection { inner_cmd, .. } => inner_cmd.trace_lines(),
+            SupportedCommand::Pipeline { commands } => {
+                commands.iter().flat_map(Self::trace_lines).collect()
+            }
+            SupportedCommand::Assignment {
+                name,
+                value: VariableValue::Scalar(value),
+            } => vec![format!("{}={}", name, CommandParser::quote(value))],
+            SupportedCommand::Assignment {
+                name,
+                value: VariableValue::Array(values),
+            } => vec![format!("{}=({})", name, quote_all(values.iter().collect()))],
+            SupportedCommand::Prefixed {
+                assignments,
+                inner_cmd,
+            } => {
+                let prefix: Vec<String> = assignments
+                    .iter()
+                    .map(|(name, value)| format!("{}={}", name, CommandParser::quote(value)))
+                    .collect();
+                let mut lines = inner_cmd.trace_lines();
+                match lines.first_mut() {
+                    Some(line) => *line = format!("{} {}", prefix.join(" "), line),
+                    None => lines = prefix,
+                }
+                lines
+            }
+            SupportedCommand::NoArgument => Vec::new(),
+        }
+    }
+
+    /// The temporary assignments of a `Prefixed` command and the command they apply
+    /// to, or none and the command itself.
+    pub fn split_assignments(&self) -> (&[(String, String)], &SupportedCommand) {
+        match self {
+            SupportedCommand::Prefixed {
+                assignments,
+                inner_cmd,
+            } => (assignments, inner_cmd),
+            command => (&[], command),
+        }
     }
 
-    pub fn supported(cmd: &String) -> bool {
-        SupportedCommand::commands().contains(cmd)
+    /// The command that `command [-p] [--] name args` runs, or `None` for the `-v` and
+    /// `-V` lookup forms and invalid options, which `TypeCommandHandler` handles.
+    pub fn command_operands(args: &[String]) -> Option<&[String]> {
+        let mut rest = args;
+        while let Some(arg) = rest.first() {
+            match arg.as_str() {
+                "--" => return Some(&rest[1..]),
+                "-p" => rest = &rest[1..],
+                flag if flag.starts_with('-') && flag.len() > 1 => return None,
+                _ => break,
+            }
+        }
+        Some(rest)
     }
 }
 
@@ -62,4 +185,6 @@ pub enum RedirectionKind {
     OverwriteOnlyError,
     AppendOnlySuccess,
     AppendOnlyError,
+    /// `<`: standard input is read from the file.
+    Input,
 }
@@ -0,0 +1,130 @@
+use crate::core::signals::{self, Disposition};
+use std::collections::{BTreeMap, BTreeSet};
+use std::io;
+
+/// What a trap is set on: a signal, or one of the shell's own events.
+///
+/// The order is the one `trap -p` lists traps in.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum TrapEvent {
+    /// The shell is about to exit.
+    Exit,
+    Signal(i32),
+    /// A simple command is about to run.
+    Debug,
+    /// A command failed, outside the places `errexit` ignores failures.
+    Err,
+    /// A function returned.
+    Return,
+}
+
+impl TrapEvent {
+    /// Parses a `trap` signal specification: a signal name or number, `EXIT` (or
+    /// `0`), `ERR`, `DEBUG` or `RETURN`, in any case.
+    pub fn parse(spec: &str) -> Option<Self> {
+        match spec.to_ascii_uppercase().as_str() {
+            "0" | "EXIT" => Some(TrapEvent::Exit),
+            "DEBUG" => Some(TrapEvent::Debug),
+            "ERR" => Some(TrapEvent::Err),
+            "RETURN" => Some(TrapEvent::Return),
+            _ => signals::number(spec).map(TrapEvent::Signal),
+        }
+    }
+
+    /// The name `trap -p` shows, such as `EXIT` or `SIGINT`.
+    pub fn name(&self) -> String {
+        match self {
+            TrapEvent::Exit => "EXIT".to_string(),
+            TrapEvent::Signal(signal) => match signals::name(*signal) {
+                Some(name) => format!("SIG{}", name),
+                None => signal.to_string(),
+            },
+            TrapEvent::Debug => "DEBUG".to_string(),
+            TrapEvent::Err => "ERR".to_string(),
+            TrapEvent::Return => "RETURN".to_string(),
+        }
+    }
+}
+
+/// The actions set with `trap`, and the signals waiting for theirs to run. An empty
+/// action ignores a signal.
+#[derive(Default)]
+pub struct TrapState {
+    actions: BTreeMap<TrapEvent, String>,
+    /// Signals raised for this shell alone, such as with `ShellSession::raise`.
+    pending: BTreeSet<i32>,
+    /// Whether traps on signals change how the process handles them.
+    handles_signals: bool,
+}
+
+impl TrapState {
+    /// Makes traps on signals set the process's own dispositions, with the signals
+    /// it catches pending here. Dispositions are shared by the whole process, so
+    /// only the interactive shell does this.
+    pub fn handle_signals(&mut self) {
+        self.handles_signals = true;
+    }
+
+    /// Sets the action for `event`. A signal is caught, or ignored when `action` is
+    /// empty; `KILL` and `STOP` can be neither, but the trap is kept as in bash.
+    pub fn set(&mut self, event: TrapEvent, action: &str) -> io::Result<()> {
+        if let TrapEvent::Signal(signal) = event
+            && self.handles_signals
+        {
+            let disposition = match action.is_empty() {
+                true => Disposition::Ignore,
+                false => Disposition::Catch,
+            };
+            Self::install(signal, disposition)?;
+        }
+        self.actions.insert(event, action.to_string());
+        Ok(())
+    }
+
+    /// Removes the trap on `event`, restoring a signal's default disposition.
+    pub fn reset(&mut self, event: TrapEvent) -> io::Result<()> {
+        if let TrapEvent::Signal(signal) = event
+            && self.handles_signals
+        {
+            Self::install(signal, Disposition::Default)?;
+        }
+        self.actions.remove(&event);
+        Ok(())
+    }
+
+    /// The action to run for `event`, if one is set and is not empty.
+    pub fn action(&self, event: TrapEvent) -> Option<String> {
+        self.actions
+            .get(&event)
+            .filter(|action| !action.is_empty())
+            .cloned()
+    }
+
+    /// Every trap, in the order `trap -p` lists them.
+    pub fn entries(&self) -> impl Iterator<Item = (TrapEvent, &str)> {
+        self.actions
+            .iter()
+            .map(|(event, action)| (*event, action.as_str()))
+    }
+
+    /// Marks `signal` as received, for its trap to run at the next check.
+    pub fn raise(&mut self, signal: i32) {
+        self.pending.insert(signal);
+    }
+
+    /// The signals raised or, when this shell handles the process's signals,
+    /// caught since the last call, in increasing order.
+    pub fn take_pending(&mut self) -> Vec<i32> {
+        if self.handles_signals {
+            self.pending.extend(signals::take_pending());
+        }
+        std::mem::take(&mut self.pending).into_iter().collect()
+    }
+
+    fn install(signal: i32, disposition: Disposition) -> io::Result<()> {
+        match signals::set_disposition(signal, disposition) {
+            Err(err) if err.raw_os_error() == Some(libc::EINVAL) => Ok(()),
+            result => result,
+        }
+    }
+}
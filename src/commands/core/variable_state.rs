@@ -0,0 +1,173 @@
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+
+/// The value held by a shell variable.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum VariableValue {
+    Scalar(String),
+    Array(Vec<String>),
+}
+
+/// A variable as it was before a temporary assignment, such as `LANG=C` in
+/// `LANG=C sort`, so it can be put back.
+pub struct SavedVariable {
+    name: String,
+    value: Option<VariableValue>,
+    exported: bool,
+}
+
+/// Shell variables, and which of them are exported to the commands the shell starts.
+///
+/// The environment is imported into exported variables when the shell starts and
+/// kept here rather than in the process, so several shells can run in one process.
+/// Commands get the scalar values of the exported variables, so `export` and
+/// assigning to an inherited variable change what they see.
+#[derive(Default)]
+pub struct VariableState {
+    variables: HashMap<String, VariableValue>,
+    /// Names marked for export. A name can be exported before it has a value.
+    exported: BTreeSet<String>,
+    /// Exit status of the last command, expanded by `$?`.
+    last_status: i32,
+}
+
+impl VariableState {
+    pub fn with_environment(environment: impl IntoIterator<Item = (String, String)>) -> Self {
+        let mut state = Self::default();
+        for (name, value) in environment {
+            state.set_scalar(&name, value);
+            state.export(&name);
+        }
+        state
+    }
+
+    /// Returns the scalar value of a variable, or the first element of an array.
+    pub fn get(&self, name: &str) -> Option<String> {
+        match self.variables.get(name) {
+            Some(VariableValue::Scalar(value)) => Some(value.clone()),
+            Some(VariableValue::Array(values)) => values.first().cloned(),
+            None => None,
+        }
+    }
+
+    /// Returns every element of a variable; a scalar is treated as a one-element array.
+    pub fn get_array(&self, name: &str) -> Vec<String> {
+        match self.variables.get(name) {
+            Some(VariableValue::Array(values)) => values.clone(),
+            _ => self.get(name).into_iter().collect(),
+        }
+    }
+
+    /// Returns a single element of an array variable.
+    pub fn get_element(&self, name: &str, index: usize) -> Option<String> {
+        match self.variables.get(name) {
+            Some(VariableValue::Array(values)) => values.get(index).cloned(),
+            _ if index == 0 => self.get(name),
+            _ => None,
+        }
+    }
+
+    /// Whether `name` can name a variable: a letter or underscore, then letters,
+    /// digits and underscores.
+    pub fn is_valid_name(name: &str) -> bool {
+        let mut chars = name.chars();
+        chars
+            .next()
+            .is_some_and(|c| c.is_ascii_alphabetic() || c == '_')
+            && chars.all(|c| c.is_ascii_alphanumeric() || c == '_')
+    }
+
+    pub fn is_set(&self, name: &str) -> bool {
+        self.variables.contains_key(name)
+    }
+
+    pub fn set(&mut self, name: &str, value: VariableValue) {
+        self.variables.insert(name.to_string(), value);
+    }
+
+    pub fn set_scalar(&mut self, name: &str, value: impl Into<String>) {
+        self.set(name, VariableValue::Scalar(value.into()));
+    }
+
+    pub fn set_array(&mut self, name: &str, values: Vec<String>) {
+        self.set(name, VariableValue::Array(values));
+    }
+
+    pub fn unset(&mut self, name: &str) {
+        self.variables.remove(name);
+        self.exported.remove(name);
+    }
+
+    /// Marks `name` for export, so commands the shell starts get its value.
+    pub fn export(&mut self, name: &str) {
+        self.exported.insert(name.to_string());
+    }
+
+    /// Removes the export mark from `name`, keeping its value, as `export -n` does.
+    pub fn unexport(&mut self, name: &str) {
+        self.exported.remove(name);
+    }
+
+    pub fn is_exported(&self, name: &str) -> bool {
+        self.exported.contains(name)
+    }
+
+    /// The exported names in order, with their values if they are set.
+    pub fn exported(&self) -> impl Iterator<Item = (&str, Option<String>)> {
+        self.exported
+            .iter()
+            .map(|name| (name.as_str(), self.get(name)))
+    }
+
+    /// Assigns and exports each variable for the duration of one command, returning
+    /// what they were so [`restore`](Self::restore) can put them back.
+    pub fn assign_temporarily(&mut self, assignments: &[(String, String)]) -> Vec<SavedVariable> {
+        assignments
+            .iter()
+            .map(|(name, value)| {
+                let saved = SavedVariable {
+                    name: name.clone(),
+                    value: self.variables.get(name).cloned(),
+                    exported: self.is_exported(name),
+                };
+                self.set_scalar(name, value.as_str());
+                self.export(name);
+                saved
+            })
+            .collect()
+    }
+
+    /// Puts back variables saved by [`assign_temporarily`](Self::assign_temporarily).
+    pub fn restore(&mut self, saved: Vec<SavedVariable>) {
+        for saved in saved.into_iter().rev() {
+            match saved.value {
+                Some(value) => self.set(&saved.name, value),
+                None => {
+                    self.variables.remove(&saved.name);
+                }
+            }
+            if !saved.exported {
+                self.unexport(&saved.name);
+            }
+        }
+    }
+
+    pub fn last_status(&self) -> i32 {
+        self.last_status
+    }
+
+    pub fn set_last_status(&mut self, status: i32) {
+        self.last_status = status;
+    }
+
+    /// The environment passed to commands: the exported variables that have a scalar
+    /// value. Arrays are not exported, as in bash.
+    pub fn environment(&self) -> BTreeMap<String, String> {
+        self.exported
+            .iter()
+            .filter_map(|name| match self.variables.get(name)? {
+                VariableValue::Scalar(value) => Some((name.clone(), value.clone())),
+                VariableValue::Array(_) => None,
+            })
+            .collect()
+    }
+}
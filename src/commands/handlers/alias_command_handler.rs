@@ -0,0 +1,123 @@
+use crate::commands::alias_state::SharedAliases;
+use crate::commands::builtin::Builtin;
+use crate::commands::command_failure::CommandFailure;
+use crate::commands::command_handler::CommandHandler;
+use crate::commands::shell_state::ShellState;
+use crate::commands::supported_command::SupportedCommand;
+
+const ALIAS: Builtin = Builtin {
+    name: "alias",
+    usage: "alias [-p] [name[=value] ... ]",
+    help: "Define or display aliases.\n\
+           With no arguments, or with -p, print every alias in a reusable form.",
+    flags: "p",
+    special: false,
+};
+
+const UNALIAS: Builtin = Builtin {
+    name: "unalias",
+    usage: "unalias [-a] name [name ...]",
+    help: "Remove each name from the list of defined aliases.\n\
+           -a removes every alias.",
+    flags: "a",
+    special: false,
+};
+
+/// Handles `alias`, which defines and prints aliases, and `unalias`, which removes them.
+pub struct AliasCommandHandler {
+    aliases: SharedAliases,
+}
+
+impl AliasCommandHandler {
+    pub fn new(aliases: SharedAliases) -> Self {
+        Self { aliases }
+    }
+
+    /// Formats an alias so the output can be read back in as input.
+    pub fn format_alias(name: &str, value: &str) -> String {
+        format!("alias {}='{}'\n", name, value.replace('\'', "'\\''"))
+    }
+
+    fn alias(&self, args: &[String]) -> anyhow::Result<String> {
+        let mut aliases = self.aliases.lock().unwrap();
+        let args: Vec<&String> = match args.first().map(String::as_str) {
+            Some("-p") => args[1..].iter().collect(),
+            Some("--") => args[1..].iter().collect(),
+            Some(flag) if flag.starts_with('-') && flag.len() > 1 => {
+                return Err(ALIAS.invalid_option(flag));
+            }
+            _ => args.iter().collect(),
+        };
+
+        if args.is_empty() {
+            return Ok(aliases
+                .entries()
+                .iter()
+                .map(|(name, value)| Self::format_alias(name, value))
+                .collect());
+        }
+
+        let mut output = String::new();
+        let mut missing = Vec::new();
+        for arg in args {
+            match arg.split_once('=') {
+                Some((name, value)) if !name.is_empty() => aliases.set(name, value),
+                _ => match aliases.get(arg) {
+                    Some(value) => output.push_str(&Self::format_alias(arg, &value)),
+                    None => missing.push(format!("alias: {}: not found", arg)),
+                },
+            }
+        }
+
+        if missing.is_empty() {
+            Ok(output)
+        } else {
+            Err(CommandFailure::new(1, missing.join("\n"))
+                .with_output(output)
+                .into())
+        }
+    }
+
+    fn unalias(&self, args: &[String]) -> anyhow::Result<String> {
+        let mut aliases = self.aliases.lock().unwrap();
+        match args.first().map(String::as_str) {
+            None => anyhow::bail!("{}", UNALIAS.usage_line()),
+            Some("-a") => {
+                aliases.clear();
+                return Ok(String::new());
+            }
+            _ => {}
+        }
+
+        let missing: Vec<String> = args
+            .iter()
+            .filter(|name| !aliases.remove(name))
+            .map(|name| format!("unalias: {}: not found", name))
+            .collect();
+        if missing.is_empty() {
+            Ok(String::new())
+        } else {
+            anyhow::bail!("{}", missing.join("\n"))
+        }
+    }
+}
+
+impl CommandHandler for AliasCommandHandler {
+    fn builtins(&self) -> &'static [Builtin] {
+        &[ALIAS, UNALIAS]
+    }
+
+    fn handle(&self, cmd: &SupportedCommand, _state: &mut ShellState) -> anyhow::Result<String> {
+        let SupportedCommand::Builtin { name, args } = cmd else {
+            anyhow::bail!("Unsupported command passed to AliasCommandHandler");
+        };
+        match name.as_str() {
+            "alias" => self.alias(args),
+            _ => self.unalias(args),
+        }
+    }
+
+    fn as_any(&self) -> &dyn std::any::Any {
+        self
+    }
+}
@@ -0,0 +1,24 @@
+use crate::commands::command_handler::CommandHandler;
+use crate::commands::shell_state::ShellState;
+use crate::commands::supported_command::SupportedCommand;
+
+pub struct AssignmentCommandHandler;
+
+impl CommandHandler for AssignmentCommandHandler {
+    fn can_handle(&self, cmd: &SupportedCommand) -> bool {
+        matches!(cmd, SupportedCommand::Assignment { .. })
+    }
+
+    fn handle(&self, cmd: &SupportedCommand, state: &mut ShellState) -> anyhow::Result<String> {
+        let SupportedCommand::Assignment { name, value } = cmd else {
+            anyhow::bail!("Unsupported command passed to AssignmentCommandHandler");
+        };
+
+        state.variables.set(name, value.clone());
+        Ok(String::new())
+    }
+
+    fn as_any(&self) -> &dyn std::any::Any {
+        self
+    }
+}
@@ -1,43 +1,125 @@
+use crate::commands::builtin::Builtin;
+use crate::commands::frecency;
+use crate::commands::shell_state::ShellState;
 use crate::commands::{command_handler::CommandHandler, supported_command::SupportedCommand};
 use crate::core::utils::PathHelper;
-use std::{env, io};
+use std::io;
+use std::path::Path;
 
+const BUILTIN: Builtin = Builtin {
+    name: "cd",
+    usage: "cd [-L|-P] [dir]",
+    help: "Change the shell working directory.\n\
+           With no dir, change to HOME; `cd -` changes to OLDPWD. Relative names are\n\
+           searched for in CDPATH. -P resolves symlinks, -L (the default) keeps them.",
+    flags: "LP",
+    special: false,
+};
+
+/// Handles `cd`, which changes the working directory and keeps `PWD` and `OLDPWD`
+/// up to date.
+///
+/// By default (`-L`) the new directory is worked out from `PWD` textually, so
+/// `cd ..` after entering a symlink returns to where the link was. With `-P`
+/// symlinks are resolved and `PWD` holds the physical path.
 pub struct ChangeDirCommandHandler;
 
+impl ChangeDirCommandHandler {
+    /// Finds `dir` under the `CDPATH` entries. Returns the directory found and
+    /// whether it should be printed, which bash does when a non-empty entry matched.
+    fn search_cdpath(state: &ShellState, dir: &str) -> Option<(String, bool)> {
+        let is_relative = !dir.starts_with('/');
+        let first = dir.split('/').next().unwrap_or_default();
+        if !is_relative || first == "." || first == ".." {
+            return None;
+        }
+
+        let cdpath = state.variables.get("CDPATH")?;
+        cdpath.split(':').find_map(|entry| {
+            let base = if entry.is_empty() { "." } else { entry };
+            let candidate = Path::new(base).join(dir);
+            state
+                .resolve(&candidate)
+                .is_dir()
+                .then(|| (candidate.to_string_lossy().into_owned(), !entry.is_empty()))
+        })
+    }
+}
+
 impl CommandHandler for ChangeDirCommandHandler {
-    fn can_handle(&self, cmd: &SupportedCommand) -> bool {
-        matches!(cmd, SupportedCommand::ChangeDir { .. })
+    fn builtins(&self) -> &'static [Builtin] {
+        &[BUILTIN]
     }
 
-    fn handle(&self, cmd: &SupportedCommand) -> anyhow::Result<String> {
-        let SupportedCommand::ChangeDir { path } = cmd else {
+    fn handle(&self, cmd: &SupportedCommand, state: &mut ShellState) -> anyhow::Result<String> {
+        let SupportedCommand::Builtin { args, .. } = cmd else {
             anyhow::bail!("Unsupported command passed to ChangeDirCommandHandler");
         };
 
-        let mut path = path.clone();
+        let mut physical = false;
+        let mut operands = Vec::new();
+        let mut iter = args.iter();
+        while let Some(arg) = iter.next() {
+            match arg.as_str() {
+                "--" => {
+                    operands.extend(iter.by_ref().cloned());
+                    break;
+                }
+                "-" => operands.push(arg.clone()),
+                flags if flags.starts_with('-') => {
+                    for flag in flags[1..].chars() {
+                        match flag {
+                            'L' => physical = false,
+                            'P' => physical = true,
+                            _ => return Err(BUILTIN.invalid_option(&format!("-{}", flag))),
+                        }
+                    }
+                }
+                _ => operands.push(arg.clone()),
+            }
+        }
 
-        if path == "~" {
-            path = env::var("HOME").unwrap_or_else(|_| "/".to_string());
+        if operands.len() > 1 {
+            anyhow::bail!("cd: too many arguments");
         }
 
-        if !PathHelper::path_exists(&path) {
-            anyhow::bail!("cd: {}: No such file or directory", path);
+        let home = state.variables.get("HOME");
+        let old_dir = state.variables.get("OLDPWD");
+
+        let mut print = false;
+        let mut dir = match operands.first().map(String::as_str) {
+            None => home.ok_or_else(|| anyhow::anyhow!("cd: HOME not set"))?,
+            Some("-") => {
+                print = true;
+                old_dir.ok_or_else(|| anyhow::anyhow!("cd: OLDPWD not set"))?
+            }
+            Some(dir) => dir.to_string(),
+        };
+        if dir.is_empty() {
+            return Ok(String::new());
         }
 
-        match PathHelper::change_dir(&path) {
-            Ok(_) => Ok(String::new()),
+        let display = dir.clone();
+        if let Some((found, print_found)) = Self::search_cdpath(state, &dir) {
+            dir = found;
+            print |= print_found;
+        }
 
-            Err(e) => match e.kind() {
-                io::ErrorKind::NotFound => {
-                    anyhow::bail!("cd: {}: No such file or directory", path)
-                }
-                io::ErrorKind::PermissionDenied => {
-                    anyhow::bail!("cd: {}: Permission denied", path)
-                }
-                _ => {
-                    anyhow::bail!("cd: {}: {}", path, e)
-                }
-            },
+        // With `cdspell`, a directory that does not exist may be a typo of one that
+        // does; the corrected name is printed, as in bash.
+        if !state.resolve(&dir).exists()
+            && state.options.cdspell
+            && let Some(corrected) = PathHelper::correct_spelling(&dir, state.cwd())
+        {
+            change_directory(state, "cd", &corrected, &display, physical)?;
+            return Ok(format!("{}\n", corrected));
+        }
+
+        let new_pwd = change_directory(state, "cd", &dir, &display, physical)?;
+        if print {
+            Ok(format!("{}\n", new_pwd))
+        } else {
+            Ok(String::new())
         }
     }
 
@@ -45,3 +127,60 @@ impl CommandHandler for ChangeDirCommandHandler {
         self
     }
 }
+
+/// Changes to `dir`, updates `PWD` and `OLDPWD` and records the visit for `z`,
+/// returning the new `PWD`.
+///
+/// Unless `physical` is set, the target is worked out from `PWD` textually. Errors
+/// are reported as `<builtin>: <display>: <reason>`.
+pub fn change_directory(
+    state: &mut ShellState,
+    builtin: &str,
+    dir: &str,
+    display: &str,
+    physical: bool,
+) -> anyhow::Result<String> {
+    let old_pwd = state.cwd().to_string();
+
+    // The logical target: the path relative to `PWD` with `.` and `..` resolved
+    // textually. `-P` uses the path as given.
+    let target = match physical {
+        false if !dir.starts_with('/') => {
+            PathHelper::normalize_logical(&format!("{}/{}", old_pwd, dir))
+        }
+        false => PathHelper::normalize_logical(dir),
+        true => dir.to_string(),
+    };
+
+    // Like bash, fall back to the physical path when the logical one does not
+    // work, e.g. `..` out of a directory that has since been moved.
+    let mut result = state.change_dir(&target, physical);
+    if result.is_err() && !physical && target != dir {
+        result = state.change_dir(dir, true);
+    }
+
+    if let Err(e) = result {
+        match e.kind() {
+            io::ErrorKind::NotFound => {
+                anyhow::bail!("{}: {}: No such file or directory", builtin, display)
+            }
+            io::ErrorKind::PermissionDenied => {
+                anyhow::bail!("{}: {}: Permission denied", builtin, display)
+            }
+            io::ErrorKind::NotADirectory => {
+                anyhow::bail!("{}: {}: Not a directory", builtin, display)
+            }
+            _ => anyhow::bail!("{}: {}: {}", builtin, display, e),
+        }
+    }
+
+    let new_pwd = state.cwd().to_string();
+    state.variables.set_scalar("OLDPWD", old_pwd);
+    state.variables.set_scalar("PWD", new_pwd.clone());
+    // Only the interactive shell records visits, so embedded sessions and scripts
+    // leave the user's database alone.
+    if state.options.interactive {
+        frecency::record_visit(&state.variables, &new_pwd);
+    }
+    Ok(new_pwd)
+}
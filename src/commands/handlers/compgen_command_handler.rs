@@ -0,0 +1,81 @@
+use crate::auto_complete::completion_spec::CompletionOptions;
+use crate::commands::builtin::{Builtin, SharedBuiltins};
+use crate::commands::command_failure::CommandFailure;
+use crate::commands::command_handler::CommandHandler;
+use crate::commands::command_hash::SharedCommandHash;
+use crate::commands::function_state::SharedFunctions;
+use crate::commands::shell_state::ShellState;
+use crate::commands::supported_command::SupportedCommand;
+
+const BUILTIN: Builtin = Builtin {
+    name: "compgen",
+    usage: "compgen [-dfc] [-W wordlist] [word]",
+    help: "Display the possible completions of word for the given options.",
+    flags: "cdfW",
+    special: false,
+};
+
+/// Handles `compgen`, which prints the completions a spec would generate for a word.
+pub struct CompGenCommandHandler {
+    builtins: SharedBuiltins,
+    functions: SharedFunctions,
+    hash: SharedCommandHash,
+}
+
+impl CompGenCommandHandler {
+    pub fn new(
+        builtins: SharedBuiltins,
+        functions: SharedFunctions,
+        hash: SharedCommandHash,
+    ) -> Self {
+        Self {
+            builtins,
+            functions,
+            hash,
+        }
+    }
+}
+
+impl CommandHandler for CompGenCommandHandler {
+    fn builtins(&self) -> &'static [Builtin] {
+        &[BUILTIN]
+    }
+
+    fn handle(&self, cmd: &SupportedCommand, state: &mut ShellState) -> anyhow::Result<String> {
+        let SupportedCommand::Builtin { args, .. } = cmd else {
+            anyhow::bail!("Unsupported command passed to CompGenCommandHandler");
+        };
+
+        let options = CompletionOptions::parse("compgen", args)?;
+        if options.spec.function.is_some() {
+            anyhow::bail!("compgen: -F: only available to completions started by `complete`");
+        }
+
+        let word = options.operands.first().map(String::as_str).unwrap_or("");
+
+        let mut commands = Vec::new();
+        if options.spec.commands {
+            commands.extend(self.builtins.lock().unwrap().names());
+            commands.extend(self.functions.lock().unwrap().names());
+            commands.extend(self.hash.lock().unwrap().executables(&state.search_path()));
+            commands.sort();
+            commands.dedup();
+        }
+
+        let candidates = options.spec.generate(word, &commands, state.cwd());
+        // Like bash, finding nothing is a failure, which completion functions test.
+        if candidates.is_empty() {
+            return Err(CommandFailure::new(1, "").into());
+        }
+        let mut output = String::new();
+        for candidate in candidates {
+            output.push_str(&candidate);
+            output.push('\n');
+        }
+        Ok(output)
+    }
+
+    fn as_any(&self) -> &dyn std::any::Any {
+        self
+    }
+}
@@ -0,0 +1,83 @@
+use crate::auto_complete::completion_spec::{CompletionOptions, SharedCompletions};
+use crate::commands::builtin::Builtin;
+use crate::commands::command_handler::CommandHandler;
+use crate::commands::shell_state::ShellState;
+use crate::commands::supported_command::SupportedCommand;
+
+const BUILTIN: Builtin = Builtin {
+    name: "complete",
+    usage: "complete [-dfcpr] [-W wordlist] [-F function] [name ...]",
+    help: "Specify how arguments are to be completed.\n\
+           -p prints the specs and -r removes them.",
+    flags: "cdfprFW",
+    special: false,
+};
+
+/// Handles `complete`, which registers how the arguments of a command are completed.
+pub struct CompleteCommandHandler {
+    completions: SharedCompletions,
+}
+
+impl CompleteCommandHandler {
+    pub fn new(completions: SharedCompletions) -> Self {
+        Self { completions }
+    }
+}
+
+impl CommandHandler for CompleteCommandHandler {
+    fn builtins(&self) -> &'static [Builtin] {
+        &[BUILTIN]
+    }
+
+    fn handle(&self, cmd: &SupportedCommand, _state: &mut ShellState) -> anyhow::Result<String> {
+        let SupportedCommand::Builtin { args, .. } = cmd else {
+            anyhow::bail!("Unsupported command passed to CompleteCommandHandler");
+        };
+
+        let options = CompletionOptions::parse("complete", args)?;
+        let mut completions = self.completions.lock().unwrap();
+
+        if options.remove {
+            if options.operands.is_empty() {
+                completions.clear();
+            }
+            for name in &options.operands {
+                if !completions.remove(name) {
+                    anyhow::bail!("complete: {}: no completion specification", name);
+                }
+            }
+            return Ok(String::new());
+        }
+
+        if options.print || (options.spec.is_empty() && options.operands.is_empty()) {
+            let mut output = String::new();
+            if options.operands.is_empty() {
+                for (name, spec) in completions.specs() {
+                    output.push_str(&spec.to_command_line(&name));
+                    output.push('\n');
+                }
+            }
+            for name in &options.operands {
+                let Some(spec) = completions.get(name) else {
+                    anyhow::bail!("complete: {}: no completion specification", name);
+                };
+                output.push_str(&spec.to_command_line(name));
+                output.push('\n');
+            }
+            return Ok(output);
+        }
+
+        if options.operands.is_empty() {
+            anyhow::bail!("{}", BUILTIN.usage_line());
+        }
+
+        for name in &options.operands {
+            completions.set(name, options.spec.clone());
+        }
+        Ok(String::new())
+    }
+
+    fn as_any(&self) -> &dyn std::any::Any {
+        self
+    }
+}
@@ -0,0 +1,248 @@
+use crate::commands::builtin::Builtin;
+use crate::commands::cd_command_handler::change_directory;
+use crate::commands::command_failure::CommandFailure;
+use crate::commands::command_handler::CommandHandler;
+use crate::commands::directory_stack::DirectoryStack;
+use crate::commands::shell_state::ShellState;
+use crate::commands::supported_command::SupportedCommand;
+
+const PUSHD: Builtin = Builtin {
+    name: "pushd",
+    usage: "pushd [-n] [+N | -N | dir]",
+    help: "Add a directory to the top of the directory stack, or rotate the stack.\n\
+           With no arguments, swap the top two directories. -n does not change directory.",
+    flags: "n",
+    special: false,
+};
+
+const POPD: Builtin = Builtin {
+    name: "popd",
+    usage: "popd [-n] [+N | -N]",
+    help: "Remove a directory from the directory stack and change to the new top.\n\
+           -n removes it without changing directory.",
+    flags: "n",
+    special: false,
+};
+
+const DIRS: Builtin = Builtin {
+    name: "dirs",
+    usage: "dirs [-clpv] [+N] [-N]",
+    help: "Display the directory stack.\n\
+           -c clears it, -l shows full paths, -p one per line and -v numbered.",
+    flags: "clpv",
+    special: false,
+};
+
+/// Handles the directory stack builtins: `pushd`, `popd` and `dirs`.
+///
+/// The stack lives in the shell variables so that `~N` can expand to its entries.
+/// Directory changes go through `change_directory`, so they keep `PWD`/`OLDPWD`
+/// up to date and report errors the same way `cd` does.
+pub struct DirsCommandHandler;
+
+/// Options and operand shared by the three builtins.
+#[derive(Default)]
+struct StackArgs {
+    clear: bool,
+    long: bool,
+    per_line: bool,
+    verbose: bool,
+    no_cd: bool,
+    operand: Option<String>,
+}
+
+impl DirsCommandHandler {
+    fn full_stack(state: &ShellState) -> Vec<String> {
+        state.directory_stack().full(state.cwd())
+    }
+
+    fn set_full_stack(state: &mut ShellState, full: Vec<String>) {
+        state.directory_stack_mut().set_full(full);
+    }
+
+    /// Parses `args` for `builtin`, which accepts the option letters in its `flags`.
+    fn parse_args(builtin: &Builtin, args: &[String]) -> anyhow::Result<StackArgs> {
+        let flags = builtin.flags;
+        let mut parsed = StackArgs::default();
+        let mut iter = args.iter();
+
+        while let Some(arg) = iter.next() {
+            if arg == "--" {
+                parsed.operand = iter.next().cloned();
+                break;
+            }
+            if is_index(arg) || !arg.starts_with('-') || arg == "-" {
+                if parsed.operand.is_some() {
+                    anyhow::bail!("{}: too many arguments", builtin.name);
+                }
+                parsed.operand = Some(arg.clone());
+                continue;
+            }
+            for flag in arg[1..].chars() {
+                match flag {
+                    'c' if flags.contains('c') => parsed.clear = true,
+                    'l' if flags.contains('l') => parsed.long = true,
+                    'p' if flags.contains('p') => parsed.per_line = true,
+                    'v' if flags.contains('v') => parsed.verbose = true,
+                    'n' if flags.contains('n') => parsed.no_cd = true,
+                    _ => return Err(builtin.invalid_option(&format!("-{}", flag))),
+                }
+            }
+        }
+
+        if builtin.name == "pushd" {
+            return Ok(parsed);
+        }
+        if let Some(ref operand) = parsed.operand
+            && !(operand.starts_with('+') || operand.starts_with('-'))
+        {
+            return Err(CommandFailure::new(
+                2,
+                format!(
+                    "{}: {}: invalid argument\n{}",
+                    builtin.name,
+                    operand,
+                    builtin.usage_line()
+                ),
+            )
+            .into());
+        }
+        Ok(parsed)
+    }
+
+    /// Resolves a `+N`/`-N` operand to an index into a stack of `len` entries.
+    fn index(builtin: &str, spec: &str, len: usize) -> anyhow::Result<usize> {
+        DirectoryStack::index(spec, len).ok_or_else(|| {
+            anyhow::anyhow!("{}: {}: directory stack index out of range", builtin, spec)
+        })
+    }
+
+    /// Formats the stack the way `dirs` prints it.
+    fn format(state: &ShellState, full: &[String], args: &StackArgs) -> String {
+        let home = state.variables.get("HOME");
+        let show = |dir: &str| match home.as_deref() {
+            Some(home) if !args.long && !home.is_empty() && home != "/" => {
+                match dir.strip_prefix(home) {
+                    Some(rest) if rest.is_empty() || rest.starts_with('/') => {
+                        format!("~{}", rest)
+                    }
+                    _ => dir.to_string(),
+                }
+            }
+            _ => dir.to_string(),
+        };
+
+        if args.verbose {
+            full.iter()
+                .enumerate()
+                .map(|(i, dir)| format!("{:2}  {}\n", i, show(dir)))
+                .collect()
+        } else if args.per_line {
+            full.iter().map(|dir| format!("{}\n", show(dir))).collect()
+        } else {
+            let dirs: Vec<String> = full.iter().map(|dir| show(dir)).collect();
+            format!("{}\n", dirs.join(" "))
+        }
+    }
+
+    fn dirs(state: &mut ShellState, args: &[String]) -> anyhow::Result<String> {
+        let args = Self::parse_args(&DIRS, args)?;
+        if args.clear {
+            state.directory_stack_mut().clear();
+            return Ok(String::new());
+        }
+
+        let full = Self::full_stack(state);
+        match args.operand {
+            Some(ref spec) => {
+                let index = Self::index("dirs", spec, full.len())?;
+                Ok(Self::format(state, &full[index..=index], &args))
+            }
+            None => Ok(Self::format(state, &full, &args)),
+        }
+    }
+
+    fn pushd(state: &mut ShellState, args: &[String]) -> anyhow::Result<String> {
+        let args = Self::parse_args(&PUSHD, args)?;
+        let mut full = Self::full_stack(state);
+
+        match args.operand.as_deref() {
+            None => {
+                if full.len() < 2 {
+                    anyhow::bail!("pushd: no other directory");
+                }
+                full.swap(0, 1);
+                if !args.no_cd {
+                    full[0] = change_directory(state, "pushd", &full[0], &full[0], false)?;
+                }
+            }
+            Some(spec) if is_index(spec) => {
+                let index = Self::index("pushd", spec, full.len())?;
+                full.rotate_left(index);
+                if !args.no_cd {
+                    full[0] = change_directory(state, "pushd", &full[0], &full[0], false)?;
+                }
+            }
+            Some(dir) if args.no_cd => full.insert(1, dir.to_string()),
+            Some(dir) => {
+                let new_pwd = change_directory(state, "pushd", dir, dir, false)?;
+                full.insert(0, new_pwd);
+            }
+        }
+
+        let output = Self::format(state, &full, &StackArgs::default());
+        Self::set_full_stack(state, full);
+        Ok(output)
+    }
+
+    fn popd(state: &mut ShellState, args: &[String]) -> anyhow::Result<String> {
+        let args = Self::parse_args(&POPD, args)?;
+        let mut full = Self::full_stack(state);
+        if full.len() < 2 {
+            anyhow::bail!("popd: directory stack empty");
+        }
+
+        let index = match args.operand.as_deref() {
+            Some(spec) => Self::index("popd", spec, full.len())?,
+            None if args.no_cd => 1,
+            None => 0,
+        };
+
+        full.remove(index);
+        if index == 0 {
+            full[0] = change_directory(state, "popd", &full[0], &full[0], false)?;
+        }
+
+        let output = Self::format(state, &full, &StackArgs::default());
+        Self::set_full_stack(state, full);
+        Ok(output)
+    }
+}
+
+impl CommandHandler for DirsCommandHandler {
+    fn builtins(&self) -> &'static [Builtin] {
+        &[PUSHD, POPD, DIRS]
+    }
+
+    fn handle(&self, cmd: &SupportedCommand, state: &mut ShellState) -> anyhow::Result<String> {
+        let SupportedCommand::Builtin { name, args } = cmd else {
+            anyhow::bail!("Unsupported command passed to DirsCommandHandler");
+        };
+        match name.as_str() {
+            "pushd" => Self::pushd(state, args),
+            "popd" => Self::popd(state, args),
+            _ => Self::dirs(state, args),
+        }
+    }
+
+    fn as_any(&self) -> &dyn std::any::Any {
+        self
+    }
+}
+
+/// Whether `arg` is a `+N` or `-N` stack index rather than an option or directory.
+fn is_index(arg: &str) -> bool {
+    arg.len() > 1
+        && (arg.starts_with('+') || arg.starts_with('-'))
+        && arg[1..].bytes().all(|b| b.is_ascii_digit())
+}
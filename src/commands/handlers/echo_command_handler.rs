@@ -1,14 +1,24 @@
+use crate::commands::builtin::Builtin;
+use crate::commands::shell_state::ShellState;
 use crate::commands::{command_handler::CommandHandler, supported_command::SupportedCommand};
 
+const BUILTIN: Builtin = Builtin {
+    name: "echo",
+    usage: "echo [arg ...]",
+    help: "Write the arguments, separated by spaces, to the standard output.",
+    flags: "",
+    special: false,
+};
+
 pub struct EchoCommandHandler;
 
 impl CommandHandler for EchoCommandHandler {
-    fn can_handle(&self, cmd: &SupportedCommand) -> bool {
-        matches!(cmd, SupportedCommand::Echo { .. })
+    fn builtins(&self) -> &'static [Builtin] {
+        &[BUILTIN]
     }
 
-    fn handle(&self, cmd: &SupportedCommand) -> anyhow::Result<String> {
-        let SupportedCommand::Echo { args } = cmd else {
+    fn handle(&self, cmd: &SupportedCommand, _state: &mut ShellState) -> anyhow::Result<String> {
+        let SupportedCommand::Builtin { args, .. } = cmd else {
             anyhow::bail!("Unsupported command passed to EchoCommandHandler");
         };
 
@@ -0,0 +1,30 @@
+use crate::commands::builtin::Builtin;
+use crate::commands::shell_state::ShellState;
+use crate::commands::{command_handler::CommandHandler, supported_command::SupportedCommand};
+
+const BUILTIN: Builtin = Builtin {
+    name: "exit",
+    usage: "exit [n]",
+    help: "Exit the shell with a status of n, or that of the last command, saving\n\
+           the history. An EXIT trap runs first.",
+    flags: "",
+    special: true,
+};
+
+/// Declares `exit`. Leaving the shell is up to the executor, which sets the status
+/// and stops reading input once `exit` has run.
+pub struct ExitCommandHandler;
+
+impl CommandHandler for ExitCommandHandler {
+    fn builtins(&self) -> &'static [Builtin] {
+        &[BUILTIN]
+    }
+
+    fn handle(&self, _cmd: &SupportedCommand, _state: &mut ShellState) -> anyhow::Result<String> {
+        Ok(String::new())
+    }
+
+    fn as_any(&self) -> &dyn std::any::Any {
+        self
+    }
+}
@@ -0,0 +1,110 @@
+use crate::commands::builtin::Builtin;
+use crate::commands::command_failure::CommandFailure;
+use crate::commands::command_handler::CommandHandler;
+use crate::commands::shell_state::ShellState;
+use crate::commands::supported_command::SupportedCommand;
+use crate::commands::variable_state::VariableState;
+
+const BUILTIN: Builtin = Builtin {
+    name: "export",
+    usage: "export [-n] [-p] [name[=value] ...]",
+    help: "Mark variables for export to the commands the shell starts.\n\
+           Each name is exported, after being assigned when a value is given. -n\n\
+           removes the export mark instead, and -p or no names lists the exported\n\
+           variables as commands that can be read back in.",
+    flags: "np",
+    special: true,
+};
+
+/// Handles `export`, which marks variables so the commands the shell starts get
+/// them in their environment.
+pub struct ExportCommandHandler;
+
+impl ExportCommandHandler {
+    /// The exported variables as `declare -x` commands, as bash prints them.
+    fn list(state: &ShellState) -> String {
+        state
+            .variables
+            .exported()
+            .map(|(name, value)| match value {
+                Some(value) => {
+                    let mut escaped = String::new();
+                    for c in value.chars() {
+                        if matches!(c, '"' | '\\' | '$' | '`') {
+                            escaped.push('\\');
+                        }
+                        escaped.push(c);
+                    }
+                    format!("declare -x {}=\"{}\"\n", name, escaped)
+                }
+                None => format!("declare -x {}\n", name),
+            })
+            .collect()
+    }
+}
+
+impl CommandHandler for ExportCommandHandler {
+    fn builtins(&self) -> &'static [Builtin] {
+        &[BUILTIN]
+    }
+
+    fn handle(&self, cmd: &SupportedCommand, state: &mut ShellState) -> anyhow::Result<String> {
+        let SupportedCommand::Builtin { args, .. } = cmd else {
+            anyhow::bail!("Unsupported command passed to ExportCommandHandler");
+        };
+
+        let mut unexport = false;
+        let mut operands = args.as_slice();
+        while let Some((arg, rest)) = operands.split_first() {
+            if arg == "--" {
+                operands = rest;
+                break;
+            }
+            let Some(flags) = arg.strip_prefix('-').filter(|f| !f.is_empty()) else {
+                break;
+            };
+            for flag in flags.chars() {
+                match flag {
+                    'n' => unexport = true,
+                    'p' => {}
+                    _ => return Err(BUILTIN.invalid_option(&format!("-{}", flag))),
+                }
+            }
+            operands = rest;
+        }
+
+        if operands.is_empty() {
+            return Ok(Self::list(state));
+        }
+
+        let mut errors = Vec::new();
+        for operand in operands {
+            let (name, value) = match operand.split_once('=') {
+                Some((name, value)) => (name, Some(value)),
+                None => (operand.as_str(), None),
+            };
+            if !VariableState::is_valid_name(name) {
+                errors.push(format!("export: `{}': not a valid identifier", operand));
+                continue;
+            }
+            if let Some(value) = value {
+                state.variables.set_scalar(name, value);
+            }
+            if unexport {
+                state.variables.unexport(name);
+            } else {
+                state.variables.export(name);
+            }
+        }
+
+        if errors.is_empty() {
+            Ok(String::new())
+        } else {
+            Err(CommandFailure::new(1, errors.join("\n")).into())
+        }
+    }
+
+    fn as_any(&self) -> &dyn std::any::Any {
+        self
+    }
+}
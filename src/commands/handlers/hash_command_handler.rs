@@ -0,0 +1,141 @@
+use crate::commands::builtin::Builtin;
+use crate::commands::command_handler::CommandHandler;
+use crate::commands::command_hash::SharedCommandHash;
+use crate::commands::shell_state::ShellState;
+use crate::commands::supported_command::SupportedCommand;
+
+const BUILTIN: Builtin = Builtin {
+    name: "hash",
+    usage: "hash [-lr] [-p pathname] [-dt] [name ...]",
+    help: "Remember or display the full pathnames of commands.\n\
+           With no arguments, list the remembered commands and their hit counts.",
+    flags: "dlprt",
+    special: false,
+};
+
+/// Handles `hash`, which lists and edits the remembered command locations.
+pub struct HashCommandHandler {
+    hash: SharedCommandHash,
+}
+
+impl HashCommandHandler {
+    pub fn new(hash: SharedCommandHash) -> Self {
+        Self { hash }
+    }
+}
+
+impl CommandHandler for HashCommandHandler {
+    fn builtins(&self) -> &'static [Builtin] {
+        &[BUILTIN]
+    }
+
+    fn handle(&self, cmd: &SupportedCommand, state: &mut ShellState) -> anyhow::Result<String> {
+        let SupportedCommand::Builtin { args, .. } = cmd else {
+            anyhow::bail!("Unsupported command passed to HashCommandHandler");
+        };
+
+        let mut reset = false;
+        let mut list = false;
+        let mut delete = false;
+        let mut print_paths = false;
+        let mut pathname: Option<String> = None;
+        let mut names = Vec::new();
+
+        let mut iter = args.iter();
+        while let Some(arg) = iter.next() {
+            let Some(flags) = arg.strip_prefix('-').filter(|f| !f.is_empty()) else {
+                names.push(arg.clone());
+                names.extend(iter.by_ref().cloned());
+                break;
+            };
+            for flag in flags.chars() {
+                match flag {
+                    'r' => reset = true,
+                    'l' => list = true,
+                    'd' => delete = true,
+                    't' => print_paths = true,
+                    'p' => match iter.next() {
+                        Some(path) => pathname = Some(path.clone()),
+                        None => anyhow::bail!("hash: -p: option requires an argument"),
+                    },
+                    _ => return Err(BUILTIN.invalid_option(&format!("-{}", flag))),
+                }
+            }
+        }
+
+        let search_path = state.search_path();
+        let mut hash = self.hash.lock().unwrap();
+
+        if reset {
+            hash.clear();
+        }
+
+        if let Some(path) = pathname {
+            if names.is_empty() {
+                anyhow::bail!("hash: -p: a name is required");
+            }
+            for name in &names {
+                hash.set(name, &path);
+            }
+            return Ok(String::new());
+        }
+
+        if names.is_empty() {
+            if reset {
+                return Ok(String::new());
+            }
+            let entries = hash.entries(&search_path);
+            if entries.is_empty() {
+                return Ok("hash: hash table empty\n".to_string());
+            }
+            let mut output = String::new();
+            if list {
+                for (name, entry) in entries {
+                    output.push_str(&format!("builtin hash -p {} {}\n", entry.path, name));
+                }
+            } else {
+                output.push_str("hits\tcommand\n");
+                for (_, entry) in entries {
+                    output.push_str(&format!("{:4}\t{}\n", entry.hits, entry.path));
+                }
+            }
+            return Ok(output);
+        }
+
+        let mut output = String::new();
+        let mut missing = Vec::new();
+        for name in &names {
+            if delete {
+                if !hash.remove(name) {
+                    missing.push(name);
+                }
+            } else if print_paths || list {
+                match hash.get(name, &search_path) {
+                    Some(entry) if list => {
+                        output.push_str(&format!("builtin hash -p {} {}\n", entry.path, name))
+                    }
+                    Some(entry) if names.len() > 1 => {
+                        output.push_str(&format!("{}\t{}\n", name, entry.path))
+                    }
+                    Some(entry) => output.push_str(&format!("{}\n", entry.path)),
+                    None => missing.push(name),
+                }
+            } else if hash.remember(name, &search_path).is_none() {
+                missing.push(name);
+            }
+        }
+
+        if !missing.is_empty() {
+            let errors: Vec<String> = missing
+                .iter()
+                .map(|name| format!("hash: {}: not found", name))
+                .collect();
+            anyhow::bail!("{}", errors.join("\n"));
+        }
+        Ok(output)
+    }
+
+    fn as_any(&self) -> &dyn std::any::Any {
+        self
+    }
+}
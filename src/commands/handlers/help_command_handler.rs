@@ -0,0 +1,117 @@
+use crate::commands::builtin::{Builtin, SharedBuiltins};
+use crate::commands::command_failure::CommandFailure;
+use crate::commands::command_handler::CommandHandler;
+use crate::commands::shell_state::ShellState;
+use crate::commands::supported_command::SupportedCommand;
+use crate::core::pattern;
+
+const BUILTIN: Builtin = Builtin {
+    name: "help",
+    usage: "help [-ds] [pattern ...]",
+    help: "Display information about builtin commands.\n\
+           With patterns, describe each builtin whose name starts with one; -d prints\n\
+           only the summary and -s only the usage synopsis.",
+    flags: "ds",
+    special: false,
+};
+
+/// Handles `help`, which describes the builtins from their registered metadata.
+pub struct HelpCommandHandler {
+    builtins: SharedBuiltins,
+}
+
+impl HelpCommandHandler {
+    pub fn new(builtins: SharedBuiltins) -> Self {
+        Self { builtins }
+    }
+
+    fn describe(builtin: &Builtin, summary_only: bool, usage_only: bool) -> String {
+        if summary_only {
+            return format!("{} - {}\n", builtin.name, builtin.summary());
+        }
+        if usage_only {
+            return format!("{}: {}\n", builtin.name, builtin.usage);
+        }
+        let help: String = builtin
+            .help
+            .lines()
+            .map(|line| format!("    {}\n", line))
+            .collect();
+        format!("{}: {}\n{}", builtin.name, builtin.usage, help)
+    }
+}
+
+impl CommandHandler for HelpCommandHandler {
+    fn builtins(&self) -> &'static [Builtin] {
+        &[BUILTIN]
+    }
+
+    fn handle(&self, cmd: &SupportedCommand, _state: &mut ShellState) -> anyhow::Result<String> {
+        let SupportedCommand::Builtin { args, .. } = cmd else {
+            anyhow::bail!("Unsupported command passed to HelpCommandHandler");
+        };
+
+        let mut summary_only = false;
+        let mut usage_only = false;
+        let mut patterns = Vec::new();
+        let mut iter = args.iter();
+        while let Some(arg) = iter.next() {
+            if arg == "--" {
+                patterns.extend(iter.by_ref().cloned());
+                break;
+            }
+            let Some(flags) = arg.strip_prefix('-').filter(|f| !f.is_empty()) else {
+                patterns.push(arg.clone());
+                patterns.extend(iter.by_ref().cloned());
+                break;
+            };
+            for flag in flags.chars() {
+                match flag {
+                    'd' => summary_only = true,
+                    's' => usage_only = true,
+                    _ => return Err(BUILTIN.invalid_option(&format!("-{}", flag))),
+                }
+            }
+        }
+
+        let builtins = self.builtins.lock().unwrap();
+        if patterns.is_empty() {
+            let mut output = String::from(
+                "These shell commands are defined internally. Type `help name' to find out\n\
+                 more about the builtin `name'.\n\n",
+            );
+            for builtin in builtins.iter() {
+                output.push_str(&format!(" {}\n", builtin.usage));
+            }
+            return Ok(output);
+        }
+
+        let mut output = String::new();
+        let mut missing = Vec::new();
+        for pattern in &patterns {
+            let prefix = format!("{}*", pattern);
+            let matched: Vec<&Builtin> = builtins
+                .iter()
+                .filter(|builtin| pattern::matches(&prefix, builtin.name))
+                .collect();
+            if matched.is_empty() {
+                missing.push(format!("help: no help topics match `{}'.", pattern));
+            }
+            for builtin in matched {
+                output.push_str(&Self::describe(builtin, summary_only, usage_only));
+            }
+        }
+
+        if missing.is_empty() {
+            Ok(output)
+        } else {
+            Err(CommandFailure::new(1, missing.join("\n"))
+                .with_output(output)
+                .into())
+        }
+    }
+
+    fn as_any(&self) -> &dyn std::any::Any {
+        self
+    }
+}
@@ -1,8 +1,23 @@
+use crate::commands::builtin::Builtin;
+use crate::commands::command_failure::CommandFailure;
 use crate::commands::command_handler::CommandHandler;
-use crate::commands::history_state::SharedHistory;
+use crate::commands::history_state::{
+    self, HistoryControl, HistoryEntry, HistoryState, SharedHistory,
+};
+use crate::commands::shell_state::ShellState;
 use crate::commands::supported_command::{HistoryAction, SupportedCommand};
-use std::fs::{File, OpenOptions};
-use std::io::{BufRead, BufReader, Write};
+use crate::core::time_format;
+use crate::parsing::history_expander::HistoryExpander;
+
+const BUILTIN: Builtin = Builtin {
+    name: "history",
+    usage: "history [-c] [-d offset] [n] or history -anrw [filename] or history -ps arg [arg...]",
+    help: "Display or manipulate the history list.\n\
+           -c clears the list, -d deletes entries, -anrw sync with the history file,\n\
+           -s stores the arguments and -p expands them without storing them.",
+    flags: "acdnprsw",
+    special: false,
+};
 
 pub struct HistoryCommandHandler {
     state: SharedHistory,
@@ -12,69 +27,171 @@ impl HistoryCommandHandler {
     pub fn new(state: SharedHistory) -> Self {
         Self { state }
     }
+
+    /// The file named on the command line, or `HISTFILE` when none was given,
+    /// relative to the working directory.
+    fn history_file(shell: &ShellState, path: &str) -> anyhow::Result<String> {
+        match shell.history_file() {
+            _ if !path.is_empty() => Ok(shell.resolve(path).display().to_string()),
+            Some(path) => Ok(path),
+            None => anyhow::bail!("history: HISTFILE not set"),
+        }
+    }
+
+    /// Resolves `-d offset` or `-d start-end` to an inclusive range of indices into
+    /// the history list. Positive offsets are history numbers; negative ones count
+    /// back from the end, `-1` being the last entry.
+    fn resolve_range(state: &HistoryState, offset: &str) -> Option<(usize, usize)> {
+        let resolve = |offset: &str| -> Option<usize> {
+            let index = match offset.strip_prefix('-') {
+                Some(back) => state.entries.len().checked_sub(back.parse().ok()?)?,
+                None => offset.parse::<usize>().ok()?.checked_sub(state.base + 1)?,
+            };
+            (index < state.entries.len()).then_some(index)
+        };
+
+        // The range separator is the first `-` after the start offset's own sign.
+        let separator = offset.char_indices().skip(1).find(|(_, c)| *c == '-');
+        let (first, last) = match separator {
+            Some((i, _)) => (resolve(&offset[..i])?, resolve(&offset[i + 1..])?),
+            None => {
+                let index = resolve(offset)?;
+                (index, index)
+            }
+        };
+        (first <= last).then_some((first, last))
+    }
+
+    fn usage_error(message: &str) -> anyhow::Error {
+        CommandFailure::new(2, format!("history: {}\n{}", message, BUILTIN.usage_line())).into()
+    }
 }
 
 impl CommandHandler for HistoryCommandHandler {
-    fn can_handle(&self, cmd: &SupportedCommand) -> bool {
-        matches!(cmd, SupportedCommand::History { .. })
+    fn builtins(&self) -> &'static [Builtin] {
+        &[BUILTIN]
     }
 
-    fn handle(&self, cmd: &SupportedCommand) -> anyhow::Result<String> {
-        let SupportedCommand::History { action } = cmd else {
+    fn handle(&self, cmd: &SupportedCommand, shell: &mut ShellState) -> anyhow::Result<String> {
+        let SupportedCommand::Builtin { args, .. } = cmd else {
             anyhow::bail!("Unsupported command passed to HistoryCommandHandler");
         };
 
-        match action {
+        let control = HistoryControl::from_variables(&shell.variables);
+        match &HistoryAction::parse(args) {
             HistoryAction::Display { limit } => {
+                let time_format = shell.variables.get("HISTTIMEFORMAT");
                 let state = self.state.lock().unwrap();
                 let mut result = String::new();
-                let total_count = state.commands.len();
-                let start_index = if let Some(n) = limit {
-                    if *n < total_count {
-                        total_count - *n
-                    } else {
-                        0
-                    }
-                } else {
-                    0
-                };
+                let total_count = state.entries.len();
+                let start_index = limit.map_or(0, |n| total_count.saturating_sub(n));
 
-                for (i, command) in state.commands.iter().enumerate().skip(start_index) {
-                    result.push_str(&format!("{:5}  {}\n", i + 1, command));
+                for (i, entry) in state.entries.iter().enumerate().skip(start_index) {
+                    let time = match (&time_format, entry.timestamp) {
+                        (Some(format), Some(timestamp)) => {
+                            time_format::format_local(format, timestamp)
+                        }
+                        (Some(_), None) => "??".to_string(),
+                        (None, _) => String::new(),
+                    };
+                    result.push_str(&format!(
+                        "{:5}  {}{}\n",
+                        state.base + i + 1,
+                        time,
+                        entry.command
+                    ));
                 }
                 Ok(result)
             }
             HistoryAction::Read { path } => {
-                let file = File::open(path)?;
-                let reader = BufReader::new(file);
+                let path = Self::history_file(shell, path)?;
+                let (entries, line_count) = history_state::read_history_file(&path, 0)?;
                 let mut state = self.state.lock().unwrap();
-                for line in reader.lines() {
-                    let line = line?;
-                    if !line.trim().is_empty() {
-                        state.commands.push(line);
-                    }
-                }
+                state.lines_in_file = line_count;
+                state.entries.extend(entries);
+                state.truncate(control.size);
+                Ok(String::new())
+            }
+            HistoryAction::ReadNew { path } => {
+                let path = Self::history_file(shell, path)?;
+                let mut state = self.state.lock().unwrap();
+                let (entries, line_count) =
+                    history_state::read_history_file(&path, state.lines_in_file)?;
+                state.lines_in_file = line_count;
+                state.entries.extend(entries);
+                state.truncate(control.size);
                 Ok(String::new())
             }
             HistoryAction::Write { path } => {
-                let mut file = File::create(path)?;
-                let state = self.state.lock().unwrap();
-                for command in &state.commands {
-                    writeln!(file, "{}", command)?;
-                }
+                let path = Self::history_file(shell, path)?;
+                let mut state = self.state.lock().unwrap();
+                history_state::write_history_file(&path, &state.entries)?;
+                state.last_appended_index = state.entries.len();
+                state.lines_in_file =
+                    history_state::truncate_history_file(&path, control.file_size)?;
                 Ok(String::new())
             }
             HistoryAction::Append { path } => {
-                let mut file = OpenOptions::new().create(true).append(true).open(path)?;
+                let path = Self::history_file(shell, path)?;
+                let mut state = self.state.lock().unwrap();
+                state.sync_file(&path, false)?;
+                state.lines_in_file =
+                    history_state::truncate_history_file(&path, control.file_size)?;
+                Ok(String::new())
+            }
+            HistoryAction::Clear => {
+                self.state.lock().unwrap().clear();
+                Ok(String::new())
+            }
+            HistoryAction::Delete { offset } => {
                 let mut state = self.state.lock().unwrap();
-                let start = state.last_appended_index;
-                let end = state.commands.len();
-                for i in start..end {
-                    writeln!(file, "{}", state.commands[i])?;
+                let Some((first, last)) = Self::resolve_range(&state, offset) else {
+                    anyhow::bail!("history: {}: history position out of range", offset);
+                };
+                for index in (first..=last).rev() {
+                    state.remove(index);
                 }
-                state.last_appended_index = end;
                 Ok(String::new())
             }
+            HistoryAction::Store { args } => {
+                let mut state = self.state.lock().unwrap();
+                // The `history -s` line itself is replaced by its arguments.
+                let last = state.entries.len().checked_sub(1);
+                if let Some(last) = last {
+                    state.remove(last);
+                }
+                if !args.is_empty() {
+                    state
+                        .entries
+                        .push(HistoryEntry::new(args.join(" "), shell.cwd()));
+                    state.truncate(control.size);
+                }
+                Ok(String::new())
+            }
+            HistoryAction::Print { args } => {
+                let state = self.state.lock().unwrap();
+                let commands = state.commands();
+                let mut expander = HistoryExpander::new(&commands, state.base);
+                let mut result = String::new();
+                for arg in args {
+                    let expansion = expander
+                        .expand(arg)
+                        .map_err(|err| anyhow::anyhow!("history: {}", err))?;
+                    result.push_str(&expansion.line);
+                    result.push('\n');
+                }
+                Ok(result)
+            }
+            HistoryAction::Invalid { arg } if arg.starts_with('-') => {
+                Err(Self::usage_error(&format!("{}: invalid option", arg)))
+            }
+            HistoryAction::Invalid { arg } => {
+                anyhow::bail!("history: {}: numeric argument required", arg)
+            }
+            HistoryAction::MissingArgument { option } => Err(Self::usage_error(&format!(
+                "{}: option requires an argument",
+                option
+            ))),
         }
     }
 
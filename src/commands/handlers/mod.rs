@@ -1,9 +1,25 @@
+pub mod alias_command_handler;
+pub mod assignment_command_handler;
 pub mod cd_command_handler;
+pub mod compgen_command_handler;
+pub mod complete_command_handler;
+pub mod dirs_command_handler;
 pub mod echo_command_handler;
+pub mod exit_command_handler;
+pub mod export_command_handler;
+pub mod hash_command_handler;
+pub mod help_command_handler;
 pub mod history_command_handler;
-pub mod locate_command_handler;
 pub mod pipeline_command_handler;
+pub mod plugin_command_handler;
+pub mod printf_command_handler;
 pub mod pwd_command_handler;
+pub mod read_command_handler;
 pub mod redirection_command_handler;
+pub mod return_command_handler;
+pub mod set_command_handler;
+pub mod shopt_command_handler;
+pub mod trap_command_handler;
 pub mod type_command_handler;
 pub mod unspecified_command_handler;
+pub mod z_command_handler;
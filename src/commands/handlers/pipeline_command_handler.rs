@@ -1,5 +1,7 @@
+use crate::commands::command_failure::CommandFailure;
+use crate::commands::shell_state::{ShellState, pipe_input};
 use crate::commands::{command_handler::CommandHandler, supported_command::SupportedCommand};
-use anyhow::{bail, Ok, Result};
+use anyhow::{Ok, Result, bail};
 use std::sync::Arc;
 
 pub struct PipelineCommandHandler {
@@ -10,21 +12,23 @@ impl PipelineCommandHandler {
     pub fn new(handlers: Vec<Arc<dyn CommandHandler>>) -> Self {
         Self { handlers }
     }
-}
-
-impl CommandHandler for PipelineCommandHandler {
-    fn can_handle(&self, cmd: &SupportedCommand) -> bool {
-        matches!(cmd, SupportedCommand::Pipeline { .. })
-    }
-
-    /// Executes a pipeline of commands, supporting both built-ins (echo, type) and
-    /// external binaries (ls, cat).
-    fn handle(&self, cmd: &SupportedCommand) -> Result<String> {
-        let SupportedCommand::Pipeline { commands } = cmd else {
-            bail!("Unsupported command passed to PipelineCommandHandler");
-        };
 
-        let mut prev_stdout = Some(std::process::Stdio::inherit());
+    /// Runs the pipeline with the last external stage writing to `stdout`, and each
+    /// external stage writing errors to a stream made by `stderr`. The output of a
+    /// trailing builtin is returned instead. The first stage reads the session's
+    /// standard input, and a builtin after it reads the previous stage's output.
+    ///
+    /// The pipeline fails with the status of its last stage, or with `pipefail` the
+    /// status of the last stage that failed.
+    pub fn run(
+        &self,
+        commands: &[SupportedCommand],
+        stdout: std::process::Stdio,
+        stderr: impl Fn() -> std::process::Stdio,
+        state: &mut ShellState,
+    ) -> Result<String> {
+        let mut last_stdout = Some(stdout);
+        let mut prev_stdout: Option<std::process::ChildStdout> = None;
         let mut prev_output_buffer: Option<String> = None;
         let mut child_processes = Vec::new();
 
@@ -37,41 +41,30 @@ impl CommandHandler for PipelineCommandHandler {
 
         for (i, command) in commands.iter().enumerate() {
             let is_last = i == num_commands - 1;
+            let (assignments, command) = command.split_assignments();
 
             match command {
                 SupportedCommand::Unspecified { cmd, args } => {
-                    let (stdin, buffer_to_write) = if let Some(buf) = prev_output_buffer.take() {
-                        (std::process::Stdio::piped(), Some(buf))
+                    let stdin = if let Some(buf) = prev_output_buffer.take() {
+                        std::process::Stdio::from(pipe_input(buf)?)
+                    } else if let Some(pipe) = prev_stdout.take() {
+                        std::process::Stdio::from(pipe)
                     } else {
-                        (
-                            prev_stdout.take().unwrap_or(std::process::Stdio::inherit()),
-                            None,
-                        )
+                        state.child_stdin()
                     };
 
                     let stdout = if is_last {
-                        std::process::Stdio::inherit()
+                        last_stdout.take().unwrap_or(std::process::Stdio::inherit())
                     } else {
                         std::process::Stdio::piped()
                     };
 
-                    let mut child = unspecified_handler.spawn_process(
-                        cmd,
-                        args,
-                        stdin,
-                        stdout,
-                        std::process::Stdio::inherit(),
-                    )?;
-
-                    if let Some(content) = buffer_to_write {
-                        if let Some(mut stdin_pipe) = child.stdin.take() {
-                            use std::io::Write;
-                            stdin_pipe.write_all(content.as_bytes())?;
-                        }
-                    }
+                    let mut child = state.with_assignments(assignments, |state| {
+                        unspecified_handler.spawn_process(cmd, args, stdin, stdout, stderr(), state)
+                    })?;
 
                     if !is_last {
-                        prev_stdout = Some(std::process::Stdio::from(child.stdout.take().unwrap()));
+                        prev_stdout = child.stdout.take();
                     }
                     child_processes.push(child);
                 }
@@ -82,28 +75,76 @@ impl CommandHandler for PipelineCommandHandler {
                         .find(|h| h.can_handle(command))
                         .ok_or_else(|| anyhow::anyhow!("No handler found for builtin"))?;
 
-                    let output = handler.handle(command)?;
+                    // A builtin after the first stage reads the previous stage's output.
+                    let stdin = match (prev_output_buffer.take(), prev_stdout.take()) {
+                        (Some(buf), _) => Some(pipe_input(buf)?),
+                        (None, Some(pipe)) => Some(pipe.into()),
+                        (None, None) => None,
+                    };
+                    let previous = stdin.map(|fd| state.replace_stdin(Some(fd)));
+                    let result =
+                        state.with_assignments(assignments, |state| handler.handle(command, state));
+                    if let Some(previous) = previous {
+                        state.replace_stdin(previous);
+                    }
 
                     if is_last {
-                        return Ok(output);
+                        let mut statuses = Self::wait_all(child_processes)?;
+                        statuses.push(CommandFailure::status_of(&result));
+                        return CommandFailure::result_of(Self::status(&statuses, state), result?);
                     } else {
-                        prev_output_buffer = Some(output);
-
-                        prev_stdout = None;
+                        prev_output_buffer = Some(result?);
                     }
                 }
             }
         }
 
-        if let Some(mut last_child) = child_processes.pop() {
-            last_child.wait()?;
+        let statuses = Self::wait_all(child_processes)?;
+        CommandFailure::result_of(Self::status(&statuses, state), String::new())
+    }
+
+    /// Waits for the external stages, returning their statuses in order.
+    fn wait_all(children: Vec<std::process::Child>) -> Result<Vec<i32>> {
+        let mut statuses = Vec::new();
+        for mut child in children {
+            statuses.push(CommandFailure::exit_code(child.wait()?));
         }
+        Ok(statuses)
+    }
 
-        for mut child in child_processes {
-            let _ = child.wait();
+    /// The pipeline's status from those of its stages.
+    pub fn status(statuses: &[i32], state: &ShellState) -> i32 {
+        if state.options.pipefail {
+            statuses
+                .iter()
+                .rev()
+                .find(|status| **status != 0)
+                .copied()
+                .unwrap_or(0)
+        } else {
+            statuses.last().copied().unwrap_or(0)
         }
+    }
+}
+
+impl CommandHandler for PipelineCommandHandler {
+    fn can_handle(&self, cmd: &SupportedCommand) -> bool {
+        matches!(cmd, SupportedCommand::Pipeline { .. })
+    }
+
+    /// Executes a pipeline of commands, supporting both built-ins (echo, type) and
+    /// external binaries (ls, cat).
+    fn handle(&self, cmd: &SupportedCommand, state: &mut ShellState) -> Result<String> {
+        let SupportedCommand::Pipeline { commands } = cmd else {
+            bail!("Unsupported command passed to PipelineCommandHandler");
+        };
 
-        Ok(String::new())
+        self.run(
+            commands,
+            std::process::Stdio::inherit(),
+            std::process::Stdio::inherit,
+            state,
+        )
     }
 
     fn as_any(&self) -> &dyn std::any::Any {
@@ -0,0 +1,192 @@
+use crate::auto_complete::completion_spec::SharedCompletions;
+use crate::commands::builtin::{Builtin, SharedBuiltins};
+use crate::commands::command_failure::CommandFailure;
+use crate::commands::command_handler::CommandHandler;
+use crate::commands::plugin_process::TimedOut;
+use crate::commands::plugin_state::{Plugin, RUN_TIMEOUT, SharedPlugins};
+use crate::commands::shell_state::ShellState;
+use crate::commands::supported_command::SupportedCommand;
+
+const BUILTIN: Builtin = Builtin {
+    name: "plugin",
+    usage: "plugin [-l] [-u name] [path [arg ...]]",
+    help: "Load, list and unload plugins.\n\
+           `plugin path` starts the plugin at path and registers its builtins,\n\
+           completions and prompt segment; -l lists the loaded plugins and -u unloads one.",
+    flags: "lu",
+    special: false,
+};
+
+/// Handles `plugin`, and runs the builtins that loaded plugins register.
+///
+/// A plugin is a separate process spoken to over JSON-RPC (see `PluginProcess`), so
+/// one that crashes is unloaded without taking the shell with it.
+pub struct PluginCommandHandler {
+    plugins: SharedPlugins,
+    builtins: SharedBuiltins,
+    completions: SharedCompletions,
+}
+
+impl PluginCommandHandler {
+    pub fn new(
+        plugins: SharedPlugins,
+        builtins: SharedBuiltins,
+        completions: SharedCompletions,
+    ) -> Self {
+        Self {
+            plugins,
+            builtins,
+            completions,
+        }
+    }
+
+    /// Loads a plugin, reporting what it wrote to stderr while starting up.
+    fn load(&self, path: &str, args: &[String], state: &ShellState) -> anyhow::Result<String> {
+        let mut plugin =
+            Plugin::load(path, args, state).map_err(|e| anyhow::anyhow!("plugin: {}", e))?;
+        let stderr = plugin.take_stderr();
+        let mut plugins = self.plugins.lock().unwrap();
+        if plugins.get(&plugin.name).is_some() {
+            anyhow::bail!("plugin: {}: already loaded", plugin.name);
+        }
+
+        let mut builtins = self.builtins.lock().unwrap();
+        if let Some(builtin) = plugin.builtins.iter().find(|b| builtins.contains(b.name)) {
+            anyhow::bail!(
+                "plugin: {}: builtin `{}' already exists",
+                plugin.name,
+                builtin.name
+            );
+        }
+        for builtin in &plugin.builtins {
+            builtins.register(*builtin);
+        }
+        let mut completions = self.completions.lock().unwrap();
+        for (command, spec) in &plugin.completions {
+            completions.set(command, spec.clone());
+        }
+
+        plugins.add(plugin);
+        if stderr.is_empty() {
+            return Ok(String::new());
+        }
+        Err(CommandFailure::new(0, stderr.trim_end_matches('\n')).into())
+    }
+
+    /// Unloads a plugin, removing what it registered.
+    fn unload(&self, name: &str) -> anyhow::Result<String> {
+        let Some(plugin) = self.plugins.lock().unwrap().remove(name) else {
+            anyhow::bail!("plugin: {}: not loaded", name);
+        };
+        let mut builtins = self.builtins.lock().unwrap();
+        for builtin in &plugin.builtins {
+            builtins.remove(builtin.name);
+        }
+        let mut completions = self.completions.lock().unwrap();
+        for (command, _) in &plugin.completions {
+            completions.remove(command);
+        }
+        Ok(String::new())
+    }
+
+    fn list(&self) -> String {
+        self.plugins
+            .lock()
+            .unwrap()
+            .plugins()
+            .iter()
+            .map(|plugin| {
+                let names: Vec<&str> = plugin.builtins.iter().map(|b| b.name).collect();
+                format!("{}\t{}\t{}\n", plugin.name, plugin.path, names.join(" "))
+            })
+            .collect()
+    }
+
+    /// Runs a plugin builtin, unloading the plugin if it has stopped or does not
+    /// answer within `RUN_TIMEOUT`. What the plugin writes to stderr is returned with
+    /// the result rather than printed, so captures and `2>` see it.
+    fn run(&self, name: &str, args: &[String], state: &ShellState) -> anyhow::Result<String> {
+        let status = state.variables.last_status();
+        let mut plugins = self.plugins.lock().unwrap();
+        let Some(plugin) = plugins.owner_of(name) else {
+            anyhow::bail!("{}: plugin not loaded", name);
+        };
+        let output = match plugin.run(name, args, state.cwd(), status) {
+            Ok(output) => output,
+            Err(err) => {
+                let plugin_name = plugin.name.clone();
+                let stopped = !plugin.is_running();
+                let timed_out = err.is::<TimedOut>();
+                let stderr = plugin.take_stderr();
+                drop(plugins);
+                let message = if stopped {
+                    self.unload(&plugin_name)?;
+                    format!(
+                        "{}: plugin {} exited unexpectedly and was unloaded",
+                        name, plugin_name
+                    )
+                } else if timed_out {
+                    self.unload(&plugin_name)?;
+                    format!(
+                        "{}: plugin {} did not answer within {} seconds and was unloaded",
+                        name,
+                        plugin_name,
+                        RUN_TIMEOUT.as_secs()
+                    )
+                } else {
+                    format!("{}: {}", name, err)
+                };
+                anyhow::bail!("{}{}", stderr, message);
+            }
+        };
+
+        if output.status == 0 && output.stderr.is_empty() {
+            return Ok(output.stdout);
+        }
+        Err(
+            CommandFailure::new(output.status, output.stderr.trim_end_matches('\n'))
+                .with_output(output.stdout)
+                .into(),
+        )
+    }
+}
+
+impl CommandHandler for PluginCommandHandler {
+    fn builtins(&self) -> &'static [Builtin] {
+        &[BUILTIN]
+    }
+
+    /// Handles `plugin` and any builtin a loaded plugin registered.
+    fn can_handle(&self, cmd: &SupportedCommand) -> bool {
+        let SupportedCommand::Builtin { name, .. } = cmd else {
+            return false;
+        };
+        name == BUILTIN.name || self.plugins.lock().unwrap().owner_of(name).is_some()
+    }
+
+    fn handle(&self, cmd: &SupportedCommand, state: &mut ShellState) -> anyhow::Result<String> {
+        let SupportedCommand::Builtin { name, args } = cmd else {
+            anyhow::bail!("Unsupported command passed to PluginCommandHandler");
+        };
+        if name != BUILTIN.name {
+            return self.run(name, args, state);
+        }
+
+        match args.first().map(String::as_str) {
+            None | Some("-l") => Ok(self.list()),
+            Some("-u") => match args.get(1) {
+                Some(plugin) => self.unload(plugin),
+                None => anyhow::bail!("plugin: -u: option requires an argument"),
+            },
+            Some("--") if args.len() > 1 => self.load(&args[1], &args[2..], state),
+            Some(flag) if flag.starts_with('-') && flag.len() > 1 => {
+                Err(BUILTIN.invalid_option(flag))
+            }
+            Some(path) => self.load(path, &args[1..], state),
+        }
+    }
+
+    fn as_any(&self) -> &dyn std::any::Any {
+        self
+    }
+}
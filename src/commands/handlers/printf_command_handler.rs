@@ -0,0 +1,109 @@
+use crate::commands::builtin::Builtin;
+use crate::commands::command_failure::CommandFailure;
+use crate::commands::command_handler::CommandHandler;
+use crate::commands::shell_state::ShellState;
+use crate::commands::supported_command::SupportedCommand;
+use crate::commands::variable_state::VariableState;
+use crate::core::printf_format;
+
+const BUILTIN: Builtin = Builtin {
+    name: "printf",
+    usage: "printf [-v var] format [arguments]",
+    help: "Format and print the arguments under the control of the format.\n\
+           The format is printed as it is, except for backslash escapes such as \\n\n\
+           and conversions such as %s, %d, %i, %u, %x, %o, %e, %f, %g, %c, %b (the\n\
+           argument with escapes expanded), %q (the argument quoted for reuse) and\n\
+           %%, with optional flags, width and precision. The format is reused until\n\
+           every argument is used. -v assigns the output to var instead.",
+    flags: "v",
+    special: false,
+};
+
+/// Handles `printf`, which formats its arguments with a `printf(1)` format and
+/// prints the result or assigns it to a variable.
+pub struct PrintfCommandHandler;
+
+impl CommandHandler for PrintfCommandHandler {
+    fn builtins(&self) -> &'static [Builtin] {
+        &[BUILTIN]
+    }
+
+    fn handle(&self, cmd: &SupportedCommand, state: &mut ShellState) -> anyhow::Result<String> {
+        let SupportedCommand::Builtin { args, .. } = cmd else {
+            anyhow::bail!("Unsupported command passed to PrintfCommandHandler");
+        };
+
+        let mut variable = None;
+        let mut operands = args.as_slice();
+        while let Some((arg, rest)) = operands.split_first() {
+            if arg == "--" {
+                operands = rest;
+                break;
+            }
+            if let Some(name) = arg.strip_prefix("-v") {
+                let (name, rest) = match name {
+                    "" => match rest.split_first() {
+                        Some((name, rest)) => (name.as_str(), rest),
+                        None => {
+                            return Err(CommandFailure::new(
+                                2,
+                                format!(
+                                    "printf: -v: option requires an argument\n{}",
+                                    BUILTIN.usage_line()
+                                ),
+                            )
+                            .into());
+                        }
+                    },
+                    name => (name, rest),
+                };
+                if !VariableState::is_valid_name(name) {
+                    return Err(CommandFailure::new(
+                        2,
+                        format!("printf: `{}': not a valid identifier", name),
+                    )
+                    .into());
+                }
+                variable = Some(name.to_string());
+                operands = rest;
+                continue;
+            }
+            if arg.len() > 1 && arg.starts_with('-') {
+                return Err(BUILTIN.invalid_option(arg));
+            }
+            break;
+        }
+
+        let Some((format, arguments)) = operands.split_first() else {
+            return Err(CommandFailure::new(2, BUILTIN.usage_line()).into());
+        };
+
+        let printed = printf_format::printf(format, arguments);
+        let mut output = String::from_utf8_lossy(&printed.output).into_owned();
+        if let Some(name) = variable {
+            state.variables.set_scalar(&name, output);
+            output = String::new();
+        }
+
+        if printed.errors.is_empty() {
+            return Ok(output);
+        }
+        let errors: Vec<String> = printed
+            .errors
+            .iter()
+            .map(|error| format!("printf: {}", error))
+            .collect();
+        let status = if printed.failed { 1 } else { 0 };
+        Err(CommandFailure::new(status, errors.join("\n"))
+            .with_output(output)
+            .into())
+    }
+
+    fn raw_output(&self) -> bool {
+        true
+    }
+
+    fn as_any(&self) -> &dyn std::any::Any {
+        self
+    }
+}
@@ -1,20 +1,53 @@
+use crate::commands::builtin::Builtin;
 use crate::commands::command_handler::CommandHandler;
-use crate::commands::path_helper::PathHelper;
+use crate::commands::shell_state::ShellState;
 use crate::commands::supported_command::SupportedCommand;
 
+const BUILTIN: Builtin = Builtin {
+    name: "pwd",
+    usage: "pwd [-LP]",
+    help: "Print the name of the current working directory.\n\
+           -L prints the logical directory from PWD (the default), -P the physical one\n\
+           with every symlink resolved.",
+    flags: "LP",
+    special: false,
+};
+
+/// Handles `pwd`. `-L` (the default) prints the logical directory from `PWD`,
+/// `-P` the physical one with every symlink resolved.
 pub struct PwdCommandHandler;
 
 impl CommandHandler for PwdCommandHandler {
-    fn can_handle(&self, cmd: &SupportedCommand) -> bool {
-        matches!(cmd, SupportedCommand::Pwd)
+    fn builtins(&self) -> &'static [Builtin] {
+        &[BUILTIN]
     }
 
-    fn handle(&self, cmd: &SupportedCommand) -> anyhow::Result<String> {
-        let SupportedCommand::Pwd = cmd else {
+    fn handle(&self, cmd: &SupportedCommand, state: &mut ShellState) -> anyhow::Result<String> {
+        let SupportedCommand::Builtin { args, .. } = cmd else {
             anyhow::bail!("Unsupported command passed to PwdCommandHandler");
         };
 
-        match PathHelper::get_current_dir() {
+        let mut physical = false;
+        for arg in args
+            .iter()
+            .take_while(|arg| arg.starts_with('-') && *arg != "-")
+        {
+            if arg == "--" {
+                break;
+            }
+            for flag in arg[1..].chars() {
+                match flag {
+                    'L' => physical = false,
+                    'P' => physical = true,
+                    _ => return Err(BUILTIN.invalid_option(&format!("-{}", flag))),
+                }
+            }
+        }
+
+        if !physical {
+            return Ok(format!("{}\n", state.cwd()));
+        }
+        match state.physical_cwd() {
             Some(dir) => Ok(format!("{}\n", dir)),
             None => anyhow::bail!("pwd: unable to determine current directory"),
         }
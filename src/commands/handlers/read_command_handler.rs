@@ -0,0 +1,355 @@
+use crate::commands::builtin::Builtin;
+use crate::commands::command_failure::CommandFailure;
+use crate::commands::command_handler::CommandHandler;
+use crate::commands::shell_state::ShellState;
+use crate::commands::supported_command::SupportedCommand;
+use crate::commands::variable_state::VariableState;
+use std::io;
+use std::os::fd::RawFd;
+use std::time::{Duration, Instant};
+
+const BUILTIN: Builtin = Builtin {
+    name: "read",
+    usage: "read [-rs] [-a array] [-d delim] [-n nchars] [-p prompt] [-t timeout] [name ...]",
+    help: "Read a line from standard input and split it into fields.\n\
+           The line is split on the characters of IFS, and each field is assigned to\n\
+           a name in turn, with the remainder going to the last one. With no names\n\
+           the line is assigned to REPLY. -a assigns the fields to an array, -d reads\n\
+           up to delim rather than a newline, -n stops after nchars characters, -p\n\
+           shows a prompt, -r keeps backslashes, -s turns off echo and -t gives up\n\
+           after timeout seconds. The status is non-zero at end of file.",
+    flags: "rsadnpt",
+    special: false,
+};
+
+/// The status of a `read` that timed out, as bash reports it.
+const TIMEOUT_STATUS: i32 = 142;
+
+/// The options of a `read` command.
+struct ReadOptions {
+    raw: bool,
+    silent: bool,
+    array: Option<String>,
+    delimiter: char,
+    nchars: Option<usize>,
+    prompt: Option<String>,
+    timeout: Option<Duration>,
+}
+
+/// How reading a line ended.
+enum Ending {
+    /// The delimiter was read, or `-n` characters were.
+    Complete,
+    EndOfFile,
+    TimedOut,
+}
+
+/// A character of the input line, and whether a backslash escaped it, which keeps
+/// it from splitting fields.
+type Escaped = (char, bool);
+
+/// Handles `read`, which reads a line from the shell's standard input, such as a
+/// pipe or a `<` redirection, and assigns its fields to variables.
+pub struct ReadCommandHandler;
+
+impl ReadCommandHandler {
+    fn parse_options(args: &[String]) -> anyhow::Result<(ReadOptions, &[String])> {
+        let mut options = ReadOptions {
+            raw: false,
+            silent: false,
+            array: None,
+            delimiter: '\n',
+            nchars: None,
+            prompt: None,
+            timeout: None,
+        };
+
+        let mut operands = args;
+        while let Some((arg, rest)) = operands.split_first() {
+            if arg == "--" {
+                operands = rest;
+                break;
+            }
+            let Some(flags) = arg.strip_prefix('-').filter(|f| !f.is_empty()) else {
+                break;
+            };
+            operands = rest;
+
+            for (i, flag) in flags.char_indices() {
+                match flag {
+                    'r' => options.raw = true,
+                    's' => options.silent = true,
+                    'a' | 'd' | 'n' | 'p' | 't' => {
+                        // The value is the rest of the word, or the next word.
+                        let rest = &flags[i + 1..];
+                        let value = if !rest.is_empty() {
+                            rest.to_string()
+                        } else if let Some((next, remaining)) = operands.split_first() {
+                            operands = remaining;
+                            next.clone()
+                        } else {
+                            return Err(CommandFailure::new(
+                                2,
+                                format!(
+                                    "read: -{}: option requires an argument\n{}",
+                                    flag,
+                                    BUILTIN.usage_line()
+                                ),
+                            )
+                            .into());
+                        };
+                        Self::set_value(&mut options, flag, value)?;
+                        break;
+                    }
+                    _ => return Err(BUILTIN.invalid_option(&format!("-{}", flag))),
+                }
+            }
+        }
+        Ok((options, operands))
+    }
+
+    fn set_value(options: &mut ReadOptions, flag: char, value: String) -> anyhow::Result<()> {
+        match flag {
+            'a' => options.array = Some(value),
+            // An empty delimiter reads up to a NUL byte.
+            'd' => options.delimiter = value.chars().next().unwrap_or('\0'),
+            'n' => match value.parse() {
+                Ok(count) => options.nchars = Some(count),
+                Err(_) => anyhow::bail!("read: {}: invalid number", value),
+            },
+            'p' => options.prompt = Some(value),
+            't' => match value.parse::<f64>() {
+                Ok(seconds) if seconds >= 0.0 && seconds.is_finite() => {
+                    options.timeout = Some(Duration::from_secs_f64(seconds))
+                }
+                _ => anyhow::bail!("read: {}: invalid timeout specification", value),
+            },
+            _ => unreachable!("only options that take a value are set here"),
+        }
+        Ok(())
+    }
+
+    /// Whether input is ready on `fd` within `timeout`, or at end of file.
+    fn wait_for_input(fd: RawFd, timeout: Duration) -> io::Result<bool> {
+        let mut poll = libc::pollfd {
+            fd,
+            events: libc::POLLIN,
+            revents: 0,
+        };
+        let timeout_ms = timeout.as_millis().min(i32::MAX as u128) as i32;
+        // SAFETY: `poll` is valid for the duration of the call.
+        match unsafe { libc::poll(&mut poll, 1, timeout_ms) } {
+            n if n < 0 => Err(io::Error::last_os_error()),
+            n => Ok(n > 0),
+        }
+    }
+
+    /// Reads one byte from `fd`, or `None` at end of file. Reading a byte at a time
+    /// leaves the rest of the input for the commands that run after `read`.
+    fn read_byte(fd: RawFd) -> io::Result<Option<u8>> {
+        let mut byte = 0u8;
+        loop {
+            // SAFETY: `byte` is valid for the duration of the call.
+            match unsafe { libc::read(fd, (&mut byte as *mut u8).cast(), 1) } {
+                1 => return Ok(Some(byte)),
+                0 => return Ok(None),
+                _ => {
+                    let err = io::Error::last_os_error();
+                    if err.kind() != io::ErrorKind::Interrupted {
+                        return Err(err);
+                    }
+                }
+            }
+        }
+    }
+
+    /// Reads characters from `fd` up to the delimiter, removing backslashes unless
+    /// `-r` was given. A backslash before a newline continues the line.
+    fn read_line(fd: RawFd, options: &ReadOptions) -> io::Result<(Vec<Escaped>, Ending)> {
+        let deadline = options.timeout.map(|timeout| Instant::now() + timeout);
+        let mut line = Vec::new();
+        let mut bytes = Vec::new();
+        let mut escaped = false;
+
+        loop {
+            if options.nchars.is_some_and(|n| line.len() >= n) {
+                return Ok((line, Ending::Complete));
+            }
+            if let Some(deadline) = deadline {
+                let remaining = deadline.saturating_duration_since(Instant::now());
+                if !Self::wait_for_input(fd, remaining)? {
+                    return Ok((line, Ending::TimedOut));
+                }
+            }
+            let Some(byte) = Self::read_byte(fd)? else {
+                return Ok((line, Ending::EndOfFile));
+            };
+
+            bytes.push(byte);
+            let c = match std::str::from_utf8(&bytes) {
+                Ok(text) => text.chars().next().unwrap(),
+                Err(err) if err.error_len().is_none() => continue,
+                Err(_) => char::REPLACEMENT_CHARACTER,
+            };
+            bytes.clear();
+
+            if escaped {
+                escaped = false;
+                if c != '\n' {
+                    line.push((c, true));
+                }
+            } else if c == options.delimiter {
+                return Ok((line, Ending::Complete));
+            } else if c == '\\' && !options.raw {
+                escaped = true;
+            } else {
+                line.push((c, false));
+            }
+        }
+    }
+
+    /// Reads a line from a terminal, with echo turned off for `-s`. For `-n`, or a
+    /// delimiter other than newline, canonical mode is turned off too, so each
+    /// character is read as it is typed rather than once a whole line has been.
+    fn read_terminal(fd: RawFd, options: &ReadOptions) -> io::Result<(Vec<Escaped>, Ending)> {
+        // SAFETY: `termios` is plain old data that tcgetattr fills in.
+        let mut termios: libc::termios = unsafe { std::mem::zeroed() };
+        if unsafe { libc::tcgetattr(fd, &mut termios) } != 0 {
+            return Self::read_line(fd, options);
+        }
+        let mut reading = termios;
+        if options.silent {
+            reading.c_lflag &= !libc::ECHO;
+        }
+        if options.nchars.is_some() || options.delimiter != '\n' {
+            reading.c_lflag &= !libc::ICANON;
+            reading.c_cc[libc::VMIN] = 1;
+            reading.c_cc[libc::VTIME] = 0;
+        }
+        // SAFETY: both settings are valid termios structures.
+        unsafe { libc::tcsetattr(fd, libc::TCSANOW, &reading) };
+        let result = Self::read_line(fd, options);
+        unsafe { libc::tcsetattr(fd, libc::TCSANOW, &termios) };
+        result
+    }
+
+    /// Splits `line` into at most `max` fields on the characters of `ifs`. IFS
+    /// whitespace around fields is trimmed and runs of it separate once, while each
+    /// other IFS character ends a field. The last field is the rest of the line.
+    fn split_fields(line: &[Escaped], ifs: &str, max: Option<usize>) -> Vec<String> {
+        let is_separator = |(c, escaped): &Escaped| !escaped && ifs.contains(*c);
+        let is_whitespace =
+            |char: &Escaped| is_separator(char) && matches!(char.0, ' ' | '\t' | '\n');
+        let skip_whitespace = |mut i: usize| {
+            while i < line.len() && is_whitespace(&line[i]) {
+                i += 1;
+            }
+            i
+        };
+        let text = |chars: &[Escaped]| chars.iter().map(|(c, _)| c).collect::<String>();
+
+        let mut fields = Vec::new();
+        let mut i = skip_whitespace(0);
+        while i < line.len() {
+            if max.is_some_and(|max| fields.len() + 1 == max) {
+                let mut end = line.len();
+                while end > i && is_whitespace(&line[end - 1]) {
+                    end -= 1;
+                }
+                // A single field followed by a separator loses the separator.
+                let rest = &line[i..end];
+                match Self::split_fields(rest, ifs, None).as_slice() {
+                    [field] => fields.push(field.clone()),
+                    _ => fields.push(text(rest)),
+                }
+                break;
+            }
+
+            let start = i;
+            while i < line.len() && !is_separator(&line[i]) {
+                i += 1;
+            }
+            fields.push(text(&line[start..i]));
+
+            i = skip_whitespace(i);
+            if i < line.len() && is_separator(&line[i]) && !is_whitespace(&line[i]) {
+                i = skip_whitespace(i + 1);
+            }
+        }
+        fields
+    }
+}
+
+impl CommandHandler for ReadCommandHandler {
+    fn builtins(&self) -> &'static [Builtin] {
+        &[BUILTIN]
+    }
+
+    fn handle(&self, cmd: &SupportedCommand, state: &mut ShellState) -> anyhow::Result<String> {
+        let SupportedCommand::Builtin { args, .. } = cmd else {
+            anyhow::bail!("Unsupported command passed to ReadCommandHandler");
+        };
+
+        let (options, names) = Self::parse_options(args)?;
+        for name in names.iter().chain(&options.array) {
+            if !VariableState::is_valid_name(name) {
+                anyhow::bail!("read: `{}': not a valid identifier", name);
+            }
+        }
+
+        let fd = state.stdin_fd();
+        // SAFETY: isatty only inspects the descriptor.
+        let is_terminal = unsafe { libc::isatty(fd) } == 1;
+
+        // `-t 0` only checks whether there is input to read.
+        if options.timeout == Some(Duration::ZERO) {
+            return match Self::wait_for_input(fd, Duration::ZERO)? {
+                true => Ok(String::new()),
+                false => Err(CommandFailure::new(1, String::new()).into()),
+            };
+        }
+
+        if let Some(prompt) = options.prompt.as_ref().filter(|_| is_terminal) {
+            state.write_stderr(prompt)?;
+        }
+
+        let (line, ending) = if is_terminal {
+            Self::read_terminal(fd, &options)?
+        } else {
+            Self::read_line(fd, &options)?
+        };
+        // The newline typed without echo is not shown, so end the line here.
+        if options.silent && is_terminal && options.delimiter == '\n' {
+            state.write_stderr("\n")?;
+        }
+
+        let ifs = state
+            .variables
+            .get("IFS")
+            .unwrap_or_else(|| " \t\n".to_string());
+        if let Some(array) = &options.array {
+            let fields = Self::split_fields(&line, &ifs, None);
+            state.variables.set_array(array, fields);
+        } else if names.is_empty() {
+            let reply: String = line.iter().map(|(c, _)| c).collect();
+            state.variables.set_scalar("REPLY", reply);
+        } else {
+            let mut fields = Self::split_fields(&line, &ifs, Some(names.len())).into_iter();
+            for name in names {
+                state
+                    .variables
+                    .set_scalar(name, fields.next().unwrap_or_default());
+            }
+        }
+
+        match ending {
+            Ending::Complete => Ok(String::new()),
+            Ending::EndOfFile => Err(CommandFailure::new(1, String::new()).into()),
+            Ending::TimedOut => Err(CommandFailure::new(TIMEOUT_STATUS, String::new()).into()),
+        }
+    }
+
+    fn as_any(&self) -> &dyn std::any::Any {
+        self
+    }
+}
@@ -1,7 +1,9 @@
+use crate::commands::command_failure::CommandFailure;
+use crate::commands::shell_state::ShellState;
 use crate::commands::supported_command::RedirectionKind;
 use crate::commands::{command_handler::CommandHandler, supported_command::SupportedCommand};
-use anyhow::{bail, Result};
-use std::fs::OpenOptions;
+use anyhow::{Result, bail};
+use std::fs::{File, OpenOptions};
 use std::io::Write;
 use std::path::Path;
 use std::sync::Arc;
@@ -18,7 +20,7 @@ impl RedirectionCommandHandler {
     fn redirect_output(
         &self,
         kind: &RedirectionKind,
-        output_file: &str,
+        output_file: &Path,
         stdout: String,
         stderr: String,
     ) -> Result<String> {
@@ -34,15 +36,74 @@ impl RedirectionCommandHandler {
             }
 
             RedirectionKind::OverwriteOnlyError | RedirectionKind::AppendOnlyError => {
-                Self::write_to_file(output_file, &stderr, append)?;
+                // `run` has already created or truncated the file.
+                Self::write_to_file(output_file, &stderr, true)?;
                 Ok(stdout) // return stdout to caller
             }
+
+            RedirectionKind::Input => unreachable!("input is redirected by run_with_input"),
         }
     }
 
-    fn write_to_file(path: &str, content: &str, append: bool) -> Result<()> {
-        let path = Path::new(path);
+    /// Runs `cmd` with standard input read from `input_file`. Its output is not
+    /// redirected: an external command writes to `stdout` and `stderr`, and the
+    /// output of a builtin is returned.
+    fn run_with_input(
+        &self,
+        cmd: &SupportedCommand,
+        input_file: &str,
+        stdout: std::process::Stdio,
+        stderr: std::process::Stdio,
+        state: &mut ShellState,
+    ) -> Result<String> {
+        let file = File::open(state.resolve(input_file))
+            .map_err(|err| CommandFailure::open_failed(input_file, &err))?;
+
+        if let SupportedCommand::Unspecified { cmd, args } = cmd {
+            let unspecified_handler = self.handlers.iter()
+                .find_map(|h| h.as_any().downcast_ref::<crate::commands::unspecified_command_handler::UnspecifiedCommandHandler>())
+                .expect("UnspecifiedCommandHandler must be available");
+
+            let mut child = unspecified_handler.spawn_process(
+                cmd,
+                args,
+                std::process::Stdio::from(file),
+                stdout,
+                stderr,
+                state,
+            )?;
+            let status = CommandFailure::exit_code(child.wait()?);
+
+            return CommandFailure::result_of(status, String::new());
+        }
+
+        // `< file` on its own only checks that the file can be opened.
+        let Some(handler) = self.handlers.iter().find(|h| h.can_handle(cmd)) else {
+            return Ok(String::new());
+        };
+
+        let previous = state.replace_stdin(Some(file.into()));
+        let result = handler.handle(cmd, state);
+        state.replace_stdin(previous);
+        result
+    }
+
+    /// Opens the file an output redirection writes to, creating it and either
+    /// truncating it or appending to it as `kind` says.
+    pub fn open_output(kind: &RedirectionKind, path: &Path) -> std::io::Result<File> {
+        let append = matches!(
+            kind,
+            RedirectionKind::AppendOnlySuccess | RedirectionKind::AppendOnlyError
+        );
+        OpenOptions::new()
+            .create(true)
+            .write(true)
+            .append(append)
+            .truncate(!append)
+            .open(path)
+    }
 
+    fn write_to_file(path: &Path, content: &str, append: bool) -> Result<()> {
         if let Some(parent) = path.parent() {
             std::fs::create_dir_all(parent)?;
         }
@@ -57,14 +118,17 @@ impl RedirectionCommandHandler {
         file.write_all(content.as_bytes())?;
         Ok(())
     }
-}
-
-impl CommandHandler for RedirectionCommandHandler {
-    fn can_handle(&self, cmd: &SupportedCommand) -> bool {
-        matches!(cmd, SupportedCommand::Redirection { .. })
-    }
 
-    fn handle(&self, cmd: &SupportedCommand) -> Result<String> {
+    /// Runs a redirection, with the stream that is not redirected going to `stdout`
+    /// or `stderr` when the inner command is external. A relative `output_file` is
+    /// relative to the session's working directory.
+    pub fn run(
+        &self,
+        cmd: &SupportedCommand,
+        stdout: std::process::Stdio,
+        stderr: std::process::Stdio,
+        state: &mut ShellState,
+    ) -> Result<String> {
         let SupportedCommand::Redirection {
             kind,
             inner_cmd,
@@ -73,6 +137,10 @@ impl CommandHandler for RedirectionCommandHandler {
         else {
             bail!("Unsupported command passed to RedirectionCommandHandler");
         };
+        if let RedirectionKind::Input = kind {
+            return self.run_with_input(inner_cmd, output_file, stdout, stderr, state);
+        }
+        let output_file = state.resolve(output_file);
 
         if let SupportedCommand::Unspecified { cmd, args } = inner_cmd.as_ref() {
             let unspecified_handler = self.handlers.iter()
@@ -80,64 +148,74 @@ impl CommandHandler for RedirectionCommandHandler {
                 .and_then(|h| h.as_any().downcast_ref::<crate::commands::unspecified_command_handler::UnspecifiedCommandHandler>())
                 .expect("UnspecifiedCommandHandler must be available");
 
-            let append = matches!(
-                kind,
-                RedirectionKind::AppendOnlySuccess | RedirectionKind::AppendOnlyError
-            );
-            let file = OpenOptions::new()
-                .create(true)
-                .write(true)
-                .append(append)
-                .truncate(!append)
-                .open(output_file)?;
+            let file = Self::open_output(kind, &output_file)?;
 
             let is_stdout_redirection = matches!(
                 kind,
                 RedirectionKind::OverwriteOnlySuccess | RedirectionKind::AppendOnlySuccess
             );
 
-            let stdout = if is_stdout_redirection {
-                std::process::Stdio::from(file.try_clone()?)
-            } else {
-                std::process::Stdio::inherit()
-            };
-
-            let stderr = if !is_stdout_redirection {
-                std::process::Stdio::from(file)
+            let (stdout, stderr) = if is_stdout_redirection {
+                (std::process::Stdio::from(file), stderr)
             } else {
-                std::process::Stdio::inherit()
+                (stdout, std::process::Stdio::from(file))
             };
 
             let mut child = unspecified_handler.spawn_process(
                 cmd,
                 args,
-                std::process::Stdio::inherit(),
+                state.child_stdin(),
                 stdout,
                 stderr,
+                state,
             )?;
-            child.wait()?;
+            let status = CommandFailure::exit_code(child.wait()?);
 
-            return Ok(String::new());
+            return CommandFailure::result_of(status, String::new());
         }
 
-        let handler = self
-            .handlers
-            .iter()
-            .find(|h| h.can_handle(inner_cmd))
-            .expect("Handler must exist");
+        // A redirection with no command, such as `> file`, only creates or
+        // truncates the file, as in bash.
+        let handler = self.handlers.iter().find(|h| h.can_handle(inner_cmd));
 
         let mut stdout = String::new();
         let mut stderr = String::new();
 
-        match handler.handle(inner_cmd) {
+        // A builtin that writes to standard error as it runs, such as `read -p`,
+        // writes to the file, ahead of the error message it returns.
+        let is_stderr_redirection = matches!(
+            kind,
+            RedirectionKind::OverwriteOnlyError | RedirectionKind::AppendOnlyError
+        );
+        let previous_stderr = if is_stderr_redirection {
+            let append = matches!(kind, RedirectionKind::AppendOnlyError);
+            Self::write_to_file(&output_file, "", append)?;
+            let file = OpenOptions::new().append(true).open(&output_file)?;
+            Some(state.replace_stderr(Some(file.into())))
+        } else {
+            None
+        };
+        let result = match handler {
+            Some(handler) => handler.handle(inner_cmd, state),
+            None => Ok(String::new()),
+        };
+        if let Some(previous) = previous_stderr {
+            state.replace_stderr(previous);
+        }
+        let status = CommandFailure::status_of(&result);
+        match result {
             Ok(out) => {
                 stdout.push_str(&out);
 
-                if !stdout.is_empty() && !stdout.ends_with('\n') {
+                if !stdout.is_empty()
+                    && !stdout.ends_with('\n')
+                    && !handler.is_some_and(|h| h.raw_output())
+                {
                     stdout.push('\n');
                 }
             }
             Err(e) => {
+                stdout.push_str(CommandFailure::output_of(&e));
                 stderr.push_str(&format!("{}", e));
                 if !stderr.is_empty() && !stderr.ends_with('\n') {
                     stderr.push('\n');
@@ -145,7 +223,23 @@ impl CommandHandler for RedirectionCommandHandler {
             }
         }
 
-        self.redirect_output(kind, output_file, stdout, stderr)
+        let output = self.redirect_output(kind, &output_file, stdout, stderr)?;
+        CommandFailure::result_of(status, output)
+    }
+}
+
+impl CommandHandler for RedirectionCommandHandler {
+    fn can_handle(&self, cmd: &SupportedCommand) -> bool {
+        matches!(cmd, SupportedCommand::Redirection { .. })
+    }
+
+    fn handle(&self, cmd: &SupportedCommand, state: &mut ShellState) -> Result<String> {
+        self.run(
+            cmd,
+            std::process::Stdio::inherit(),
+            std::process::Stdio::inherit(),
+            state,
+        )
     }
 
     fn as_any(&self) -> &dyn std::any::Any {
@@ -0,0 +1,30 @@
+use crate::commands::builtin::Builtin;
+use crate::commands::shell_state::ShellState;
+use crate::commands::{command_handler::CommandHandler, supported_command::SupportedCommand};
+
+const BUILTIN: Builtin = Builtin {
+    name: "return",
+    usage: "return [n]",
+    help: "Return from a function with a status of n, or that of the last command.\n\
+           A RETURN trap runs once the function has returned.",
+    flags: "",
+    special: true,
+};
+
+/// Declares `return`. Leaving the function is up to the executor, which sets the
+/// status and stops running the function's body once `return` has run.
+pub struct ReturnCommandHandler;
+
+impl CommandHandler for ReturnCommandHandler {
+    fn builtins(&self) -> &'static [Builtin] {
+        &[BUILTIN]
+    }
+
+    fn handle(&self, _cmd: &SupportedCommand, _state: &mut ShellState) -> anyhow::Result<String> {
+        Ok(String::new())
+    }
+
+    fn as_any(&self) -> &dyn std::any::Any {
+        self
+    }
+}
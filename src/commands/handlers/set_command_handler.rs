@@ -0,0 +1,102 @@
+use crate::commands::builtin::Builtin;
+use crate::commands::command_handler::CommandHandler;
+use crate::commands::option_state::OptionState;
+use crate::commands::shell_state::ShellState;
+use crate::commands::supported_command::SupportedCommand;
+
+const BUILTIN: Builtin = Builtin {
+    name: "set",
+    usage: "set [-efnuvxH] [-o option-name] [--] [arg ...]",
+    help: "Set or unset shell options and positional parameters.\n\
+           -e exits when a command fails, -u makes unset variables an error, -x traces\n\
+           commands, -v prints input as it is read, -n reads without running and -f\n\
+           turns off globbing. `-o name` turns an option such as pipefail on and\n\
+           `+o name` off; `-o` alone lists them. Arguments after the options, or after\n\
+           `--`, become the positional parameters.",
+    flags: "efnuvxHo",
+    special: true,
+};
+
+/// Handles `set`, which turns shell options on (`-o name`, `-e`) and off (`+o name`,
+/// `+e`), and replaces the positional parameters with its operands.
+pub struct SetCommandHandler;
+
+impl SetCommandHandler {
+    fn list_options(options: &OptionState, reusable: bool) -> String {
+        let mut output = String::new();
+        for name in OptionState::names() {
+            let enabled = options.get(name).unwrap_or(false);
+            if reusable {
+                let sign = if enabled { '-' } else { '+' };
+                output.push_str(&format!("set {}o {}\n", sign, name));
+            } else {
+                let state = if enabled { "on" } else { "off" };
+                output.push_str(&format!("{:<15}\t{}\n", name, state));
+            }
+        }
+        output
+    }
+}
+
+impl CommandHandler for SetCommandHandler {
+    fn builtins(&self) -> &'static [Builtin] {
+        &[BUILTIN]
+    }
+
+    fn handle(&self, cmd: &SupportedCommand, state: &mut ShellState) -> anyhow::Result<String> {
+        let SupportedCommand::Builtin { args, .. } = cmd else {
+            anyhow::bail!("Unsupported command passed to SetCommandHandler");
+        };
+
+        let mut iter = args.iter();
+        while let Some(arg) = iter.next() {
+            let (enable, flags) = match arg.split_at_checked(1) {
+                _ if arg == "--" => {
+                    state.set_positional(iter.cloned().collect());
+                    break;
+                }
+                // `set -` turns off `-x` and `-v` and ends the options.
+                Some(("-", "")) => {
+                    state.options.xtrace = false;
+                    state.options.verbose = false;
+                    let operands: Vec<String> = iter.cloned().collect();
+                    if !operands.is_empty() {
+                        state.set_positional(operands);
+                    }
+                    break;
+                }
+                Some(("-", flags)) => (true, flags),
+                Some(("+", flags)) if !flags.is_empty() => (false, flags),
+                _ => {
+                    state.set_positional(std::iter::once(arg).chain(iter).cloned().collect());
+                    break;
+                }
+            };
+
+            for flag in flags.chars() {
+                if flag == 'o' {
+                    let Some(name) = iter.next() else {
+                        return Ok(Self::list_options(&state.options, !enable));
+                    };
+                    if !OptionState::names().contains(&name.as_str())
+                        || !state.options.set(name, enable)
+                    {
+                        anyhow::bail!("set: {}: invalid option name", name);
+                    }
+                    continue;
+                }
+
+                let Some(name) = OptionState::flag_name(flag) else {
+                    return Err(BUILTIN.invalid_option(&format!("-{}", flag)));
+                };
+                state.options.set(name, enable);
+            }
+        }
+
+        Ok(String::new())
+    }
+
+    fn as_any(&self) -> &dyn std::any::Any {
+        self
+    }
+}
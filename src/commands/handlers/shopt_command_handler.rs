@@ -0,0 +1,128 @@
+use crate::commands::builtin::Builtin;
+use crate::commands::command_failure::CommandFailure;
+use crate::commands::command_handler::CommandHandler;
+use crate::commands::option_state::OptionState;
+use crate::commands::shell_state::ShellState;
+use crate::commands::supported_command::SupportedCommand;
+
+const BUILTIN: Builtin = Builtin {
+    name: "shopt",
+    usage: "shopt [-pqsu] [-o] [optname ...]",
+    help: "Set and unset optional shell behaviour.\n\
+           -s sets and -u unsets each optname; -q only reports through the exit status\n\
+           and -p lists options as commands that restore them. With -o the names are\n\
+           those of `set -o`.",
+    flags: "opqsu",
+    special: false,
+};
+
+/// Handles `shopt`, which sets (`-s`), unsets (`-u`), queries (`-q`) and lists the
+/// shell's optional behaviours, or with `-o` the options of `set -o`.
+pub struct ShoptCommandHandler;
+
+impl ShoptCommandHandler {
+    fn format_option(name: &str, enabled: bool, reusable: bool, set_options: bool) -> String {
+        if reusable && set_options {
+            let sign = if enabled { '-' } else { '+' };
+            format!("set {}o {}\n", sign, name)
+        } else if reusable {
+            let flag = if enabled { 's' } else { 'u' };
+            format!("shopt -{} {}\n", flag, name)
+        } else {
+            let state = if enabled { "on" } else { "off" };
+            format!("{:<15}\t{}\n", name, state)
+        }
+    }
+}
+
+impl CommandHandler for ShoptCommandHandler {
+    fn builtins(&self) -> &'static [Builtin] {
+        &[BUILTIN]
+    }
+
+    fn handle(&self, cmd: &SupportedCommand, state: &mut ShellState) -> anyhow::Result<String> {
+        let SupportedCommand::Builtin { args, .. } = cmd else {
+            anyhow::bail!("Unsupported command passed to ShoptCommandHandler");
+        };
+
+        let mut set = false;
+        let mut unset = false;
+        let mut quiet = false;
+        let mut reusable = false;
+        let mut set_options = false;
+        let mut names = Vec::new();
+
+        let mut iter = args.iter();
+        while let Some(arg) = iter.next() {
+            if arg == "--" {
+                names.extend(iter.by_ref().cloned());
+                break;
+            }
+            let Some(flags) = arg.strip_prefix('-').filter(|f| !f.is_empty()) else {
+                names.push(arg.clone());
+                continue;
+            };
+            for flag in flags.chars() {
+                match flag {
+                    's' => set = true,
+                    'u' => unset = true,
+                    'q' => quiet = true,
+                    'p' => reusable = true,
+                    'o' => set_options = true,
+                    _ => return Err(BUILTIN.invalid_option(&format!("-{}", flag))),
+                }
+            }
+        }
+
+        if set && unset {
+            anyhow::bail!("shopt: cannot set and unset shell options simultaneously");
+        }
+
+        let known = match set_options {
+            true => OptionState::names(),
+            false => OptionState::shopt_names(),
+        };
+        if let Some(name) = names.iter().find(|name| !known.contains(&name.as_str())) {
+            anyhow::bail!("shopt: {}: invalid shell option name", name);
+        }
+
+        let options = &mut state.options;
+        if (set || unset) && !names.is_empty() {
+            for name in &names {
+                options.set(name, set);
+            }
+            return Ok(String::new());
+        }
+
+        // With no names, `-s` and `-u` list only the options in that state.
+        let listed: Vec<(&str, bool)> = if names.is_empty() {
+            known
+                .iter()
+                .map(|name| (*name, options.get(name).unwrap_or(false)))
+                .filter(|(_, enabled)| !(set && !enabled || unset && *enabled))
+                .collect()
+        } else {
+            names
+                .iter()
+                .map(|name| (name.as_str(), options.get(name).unwrap_or(false)))
+                .collect()
+        };
+
+        if quiet {
+            return if listed.iter().all(|(_, enabled)| *enabled) {
+                Ok(String::new())
+            } else {
+                Err(CommandFailure::new(1, "").into())
+            };
+        }
+
+        Ok(listed
+            .iter()
+            .map(|(name, enabled)| Self::format_option(name, *enabled, reusable, set_options))
+            .collect())
+    }
+
+    fn as_any(&self) -> &dyn std::any::Any {
+        self
+    }
+}
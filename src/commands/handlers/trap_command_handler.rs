@@ -0,0 +1,133 @@
+use crate::commands::builtin::Builtin;
+use crate::commands::command_failure::CommandFailure;
+use crate::commands::command_handler::CommandHandler;
+use crate::commands::shell_state::ShellState;
+use crate::commands::supported_command::SupportedCommand;
+use crate::commands::trap_state::{TrapEvent, TrapState};
+use crate::core::signals;
+
+const BUILTIN: Builtin = Builtin {
+    name: "trap",
+    usage: "trap [-lp] [[action] signal_spec ...]",
+    help: "Run an action when the shell receives a signal or reaches an event.\n\
+           A signal_spec is a signal name or number, EXIT (or 0) when the shell exits,\n\
+           ERR when a command fails, DEBUG before each simple command or RETURN when\n\
+           a function returns. An empty action ignores the signal, and `-` or no\n\
+           action resets it. -p prints the traps as commands and -l lists the signals.",
+    flags: "lp",
+    special: true,
+};
+
+/// Handles `trap`, which sets, resets and prints the actions run on signals and on
+/// the `EXIT`, `ERR`, `DEBUG` and `RETURN` events. The executor runs them.
+pub struct TrapCommandHandler;
+
+impl TrapCommandHandler {
+    /// Formats a trap so the output can be read back in as input.
+    fn format_trap(event: TrapEvent, action: &str) -> String {
+        format!(
+            "trap -- '{}' {}\n",
+            action.replace('\'', "'\\''"),
+            event.name()
+        )
+    }
+
+    /// The signals as `trap -l` lists them, five to a line.
+    fn list_signals() -> String {
+        let entries: Vec<String> = signals::SIGNALS
+            .iter()
+            .map(|(signal, name)| format!("{:>2}) SIG{}", signal, name))
+            .collect();
+        entries
+            .chunks(5)
+            .map(|line| format!("{}\n", line.join("\t")))
+            .collect()
+    }
+
+    fn print_traps(traps: &TrapState, events: &[TrapEvent]) -> String {
+        traps
+            .entries()
+            .filter(|(event, _)| events.is_empty() || events.contains(event))
+            .map(|(event, action)| Self::format_trap(event, action))
+            .collect()
+    }
+}
+
+impl CommandHandler for TrapCommandHandler {
+    fn builtins(&self) -> &'static [Builtin] {
+        &[BUILTIN]
+    }
+
+    fn handle(&self, cmd: &SupportedCommand, state: &mut ShellState) -> anyhow::Result<String> {
+        let SupportedCommand::Builtin { args, .. } = cmd else {
+            anyhow::bail!("Unsupported command passed to TrapCommandHandler");
+        };
+
+        let mut print = false;
+        let mut list = false;
+        let mut operands = args.as_slice();
+        while let Some(arg) = operands.first() {
+            if arg == "--" {
+                operands = &operands[1..];
+                break;
+            }
+            let Some(flags) = arg.strip_prefix('-').filter(|f| !f.is_empty()) else {
+                break;
+            };
+            for flag in flags.chars() {
+                match flag {
+                    'p' => print = true,
+                    'l' => list = true,
+                    _ => return Err(BUILTIN.invalid_option(&format!("-{}", flag))),
+                }
+            }
+            operands = &operands[1..];
+        }
+
+        if list {
+            return Ok(Self::list_signals());
+        }
+
+        // With one operand, or `-` as the action, every operand is a signal to reset.
+        let (action, specs) = match operands {
+            [] => (None, operands),
+            _ if print => (None, operands),
+            [first, rest @ ..] if first == "-" => (None, rest),
+            [_] => (None, operands),
+            [first, rest @ ..] => (Some(first.as_str()), rest),
+        };
+
+        let mut events = Vec::new();
+        let mut errors = Vec::new();
+        for spec in specs {
+            match TrapEvent::parse(spec) {
+                Some(event) => events.push(event),
+                None => errors.push(format!("trap: {}: invalid signal specification", spec)),
+            }
+        }
+
+        let mut output = String::new();
+        if print || operands.is_empty() {
+            output = Self::print_traps(&state.traps, &events);
+        } else {
+            for event in events {
+                match action {
+                    Some(action) => state.traps.set(event, action)?,
+                    None => state.traps.reset(event)?,
+                }
+            }
+        }
+
+        if errors.is_empty() {
+            Ok(output)
+        } else {
+            Err(CommandFailure::new(1, errors.join("\n"))
+                .with_output(output)
+                .into())
+        }
+    }
+
+    fn as_any(&self) -> &dyn std::any::Any {
+        self
+    }
+}
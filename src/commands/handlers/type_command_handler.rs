@@ -1,19 +1,232 @@
+use crate::commands::alias_command_handler::AliasCommandHandler;
+use crate::commands::builtin::Builtin;
+use crate::commands::command_failure::CommandFailure;
+use crate::commands::command_resolver::{CommandKind, CommandResolver};
+use crate::commands::shell_state::ShellState;
 use crate::commands::{command_handler::CommandHandler, supported_command::SupportedCommand};
 
-pub struct TypeCommandHandler;
+const TYPE: Builtin = Builtin {
+    name: "type",
+    usage: "type [-afptP] name [name ...]",
+    help: "Display how each name would be interpreted if used as a command.\n\
+           -a shows every match, -t only the kind, -p and -P the file that would run\n\
+           and -f skips functions.",
+    flags: "afptP",
+    special: false,
+};
 
-impl CommandHandler for TypeCommandHandler {
-    fn can_handle(&self, cmd: &SupportedCommand) -> bool {
-        matches!(cmd, SupportedCommand::TypeCheck { cmd: _ })
+const WHICH: Builtin = Builtin {
+    name: "which",
+    usage: "which [-a] name [name ...]",
+    help: "Print the executable in PATH each name runs.\n\
+           -a prints every matching executable.",
+    flags: "a",
+    special: false,
+};
+
+const COMMAND: Builtin = Builtin {
+    name: "command",
+    usage: "command [-pVv] command [arg ...]",
+    help: "Run a command with arguments, ignoring shell functions.\n\
+           -v prints how the command would be found, -V describes it like `type`.",
+    flags: "pvV",
+    special: false,
+};
+
+/// Handles `type`, `which` and the lookup forms of `command` (`-v` and `-V`), which
+/// all describe names through the same `CommandResolver`.
+pub struct TypeCommandHandler {
+    resolver: CommandResolver,
+}
+
+/// Options for `type`.
+#[derive(Default)]
+struct TypeOptions {
+    all: bool,
+    kind_only: bool,
+    path_only: bool,
+    force_path: bool,
+    skip_functions: bool,
+}
+
+impl TypeCommandHandler {
+    pub fn new(resolver: CommandResolver) -> Self {
+        Self { resolver }
+    }
+
+    /// Describes `name` the way `type` does without options.
+    fn describe(name: &str, kind: &CommandKind) -> String {
+        match kind {
+            CommandKind::Alias(value) => format!("{} is aliased to `{}'\n", name, value),
+            CommandKind::Keyword => format!("{} is a shell keyword\n", name),
+            CommandKind::Function(body) => {
+                let body: String = body.lines().map(|line| format!("    {}\n", line)).collect();
+                format!("{} is a function\n{} () \n{{ \n{}}}\n", name, name, body)
+            }
+            CommandKind::Builtin => format!("{} is a shell builtin\n", name),
+            CommandKind::File { path, hashed: true } => format!("{} is hashed ({})\n", name, path),
+            CommandKind::File { path, .. } => format!("{} is {}\n", name, path),
+        }
     }
 
-    fn handle(&self, cmd: &SupportedCommand) -> anyhow::Result<String> {
-        match cmd {
-            SupportedCommand::TypeCheck { cmd } if cmd == "history" => {
-                Ok(format!("{} is a shell builtin\n", cmd))
+    fn type_names(&self, state: &ShellState, args: &[String]) -> anyhow::Result<String> {
+        let mut options = TypeOptions::default();
+        let mut names = Vec::new();
+        let mut iter = args.iter();
+        while let Some(arg) = iter.next() {
+            if arg == "--" {
+                names.extend(iter.by_ref().cloned());
+                break;
             }
-            SupportedCommand::TypeCheck { cmd } => Ok(format!("{} is a shell builtin\n", cmd)),
-            _ => anyhow::bail!("Unsupported command passed to TypeCommandHandler"),
+            let Some(flags) = arg.strip_prefix('-').filter(|f| !f.is_empty()) else {
+                names.push(arg.clone());
+                names.extend(iter.by_ref().cloned());
+                break;
+            };
+            for flag in flags.chars() {
+                match flag {
+                    'a' => options.all = true,
+                    't' => options.kind_only = true,
+                    'p' => options.path_only = true,
+                    'P' => options.force_path = true,
+                    'f' => options.skip_functions = true,
+                    _ => return Err(TYPE.invalid_option(&format!("-{}", flag))),
+                }
+            }
+        }
+
+        let mut output = String::new();
+        let mut errors = Vec::new();
+        for name in &names {
+            let kinds = if options.force_path {
+                self.resolver.find_files(state, name, options.all)
+            } else {
+                self.resolver
+                    .resolve(state, name, options.all, options.skip_functions)
+            };
+            if kinds.is_empty() {
+                if !options.kind_only && !options.path_only && !options.force_path {
+                    errors.push(format!("type: {}: not found", name));
+                } else {
+                    errors.push(String::new());
+                }
+                continue;
+            }
+
+            for kind in &kinds {
+                if options.kind_only {
+                    output.push_str(kind.type_name());
+                    output.push('\n');
+                } else if options.path_only || options.force_path {
+                    if let CommandKind::File { path, .. } = kind {
+                        output.push_str(&format!("{}\n", path));
+                    }
+                } else {
+                    output.push_str(&Self::describe(name, kind));
+                }
+            }
+        }
+
+        Self::finish(output, errors)
+    }
+
+    fn which(&self, state: &ShellState, args: &[String]) -> anyhow::Result<String> {
+        let all = args.first().is_some_and(|arg| arg == "-a");
+        let names = if all { &args[1..] } else { args };
+
+        let mut output = String::new();
+        let mut errors = Vec::new();
+        for name in names {
+            let files = self.resolver.find_files(state, name, all);
+            if files.is_empty() {
+                errors.push(String::new());
+            }
+            for file in files {
+                if let CommandKind::File { path, .. } = file {
+                    output.push_str(&format!("{}\n", path));
+                }
+            }
+        }
+
+        Self::finish(output, errors)
+    }
+
+    /// `command -v` prints how each name would be run as something that can be fed
+    /// back to the shell; `command -V` describes it like `type`.
+    fn command_lookup(&self, state: &ShellState, args: &[String]) -> anyhow::Result<String> {
+        let mut verbose = false;
+        let mut names = Vec::new();
+        for (i, arg) in args.iter().enumerate() {
+            match arg.as_str() {
+                "--" => {
+                    names.extend(args[i + 1..].iter().cloned());
+                    break;
+                }
+                flags if flags.starts_with('-') && flags.len() > 1 && names.is_empty() => {
+                    if let Some(flag) = flags[1..].chars().find(|c| !COMMAND.flags.contains(*c)) {
+                        return Err(COMMAND.invalid_option(&format!("-{}", flag)));
+                    }
+                    verbose |= flags.contains('V');
+                }
+                _ => names.push(arg.clone()),
+            }
+        }
+
+        let mut output = String::new();
+        let mut errors = Vec::new();
+        for name in &names {
+            let Some(kind) = self
+                .resolver
+                .resolve(state, name, false, false)
+                .into_iter()
+                .next()
+            else {
+                errors.push(if verbose {
+                    format!("command: {}: not found", name)
+                } else {
+                    String::new()
+                });
+                continue;
+            };
+            match (&kind, verbose) {
+                (_, true) => output.push_str(&Self::describe(name, &kind)),
+                (CommandKind::Alias(value), false) => {
+                    output.push_str(&AliasCommandHandler::format_alias(name, value))
+                }
+                (CommandKind::File { path, .. }, false) => output.push_str(&format!("{}\n", path)),
+                (_, false) => output.push_str(&format!("{}\n", name)),
+            }
+        }
+
+        Self::finish(output, errors)
+    }
+
+    /// Succeeds with `output` when every name was found; otherwise fails with status 1
+    /// and the non-empty error messages, still carrying the output for the names found.
+    fn finish(output: String, errors: Vec<String>) -> anyhow::Result<String> {
+        if errors.is_empty() {
+            return Ok(output);
+        }
+        let errors: Vec<String> = errors.into_iter().filter(|e| !e.is_empty()).collect();
+        Err(CommandFailure::new(1, errors.join("\n"))
+            .with_output(output)
+            .into())
+    }
+}
+
+impl CommandHandler for TypeCommandHandler {
+    fn builtins(&self) -> &'static [Builtin] {
+        &[TYPE, WHICH, COMMAND]
+    }
+
+    fn handle(&self, cmd: &SupportedCommand, state: &mut ShellState) -> anyhow::Result<String> {
+        let SupportedCommand::Builtin { name, args } = cmd else {
+            anyhow::bail!("Unsupported command passed to TypeCommandHandler");
+        };
+        match name.as_str() {
+            "type" => self.type_names(state, args),
+            "which" => self.which(state, args),
+            _ => self.command_lookup(state, args),
         }
     }
 
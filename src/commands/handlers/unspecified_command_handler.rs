@@ -1,8 +1,13 @@
+use crate::commands::command_failure::CommandFailure;
 use crate::commands::command_handler::CommandHandler;
-use crate::commands::path_helper::PathHelper;
+use crate::commands::command_hash::SharedCommandHash;
+use crate::commands::path_helper::{PathHelper, SearchPath};
+use crate::commands::shell_state::ShellState;
 use crate::commands::supported_command::SupportedCommand;
 
-pub struct UnspecifiedCommandHandler;
+pub struct UnspecifiedCommandHandler {
+    hash: SharedCommandHash,
+}
 
 impl CommandHandler for UnspecifiedCommandHandler {
     fn can_handle(&self, cmd: &SupportedCommand) -> bool {
@@ -12,7 +17,7 @@ impl CommandHandler for UnspecifiedCommandHandler {
     /// Handles a standalone external command execution.
     /// Processes are spawned using .spawn() and waited for using .wait_with_output()
     /// to avoid buffering large or streaming outputs entirely into memory.
-    fn handle(&self, cmd: &SupportedCommand) -> anyhow::Result<String> {
+    fn handle(&self, cmd: &SupportedCommand, state: &mut ShellState) -> anyhow::Result<String> {
         let SupportedCommand::Unspecified { cmd, args } = cmd else {
             anyhow::bail!("Unsupported command passed to UnspecifiedCommandHandler");
         };
@@ -20,9 +25,10 @@ impl CommandHandler for UnspecifiedCommandHandler {
         let mut child = self.spawn_process(
             cmd,
             args,
+            state.child_stdin(),
             std::process::Stdio::inherit(),
             std::process::Stdio::inherit(),
-            std::process::Stdio::inherit(),
+            state,
         )?;
 
         child.wait()?;
@@ -35,10 +41,17 @@ impl CommandHandler for UnspecifiedCommandHandler {
     }
 }
 
+use std::io;
 use std::os::unix::process::CommandExt;
+use std::path::Path;
 
 impl UnspecifiedCommandHandler {
+    pub fn new(hash: SharedCommandHash) -> Self {
+        Self { hash }
+    }
+
     /// Spawns an external process with specific Stdio configuration for redirection.
+    /// It runs in the session's working directory and environment.
     pub fn spawn_process(
         &self,
         cmd: &str,
@@ -46,18 +59,59 @@ impl UnspecifiedCommandHandler {
         stdin: std::process::Stdio,
         stdout: std::process::Stdio,
         stderr: std::process::Stdio,
+        state: &ShellState,
     ) -> anyhow::Result<std::process::Child> {
-        let Some(path) = PathHelper::find_executable(cmd) else {
-            anyhow::bail!("{}: command not found", cmd);
+        let search_path = state.search_path();
+        let located = if cmd.contains('/') {
+            state.resolve(cmd).display().to_string()
+        } else {
+            cmd.to_string()
+        };
+        let Some(path) = self.hash.lock().unwrap().find(&located, &search_path) else {
+            return Err(Self::not_runnable(&located, cmd, &search_path).into());
         };
 
-        std::process::Command::new(&path)
+        state
+            .command(&path)
             .arg0(cmd) // Senior Note: Set argv[0] to shorthand name as expected by convention
             .args(args)
             .stdin(stdin)
             .stdout(stdout)
             .stderr(stderr)
             .spawn()
-            .map_err(|e| anyhow::anyhow!("failed to spawn {}: {}", cmd, e))
+            .map_err(|e| match e.kind() {
+                io::ErrorKind::PermissionDenied => {
+                    CommandFailure::new(126, format!("{}: Permission denied", cmd)).into()
+                }
+                _ => anyhow::anyhow!("failed to spawn {}: {}", cmd, e),
+            })
+    }
+
+    /// Executable names in PATH, for suggesting corrections to unknown commands.
+    pub fn executables(&self, state: &ShellState) -> Vec<String> {
+        self.hash.lock().unwrap().executables(&state.search_path())
+    }
+
+    /// Explains why `cmd` cannot be run, with bash's exit statuses: 126 when it
+    /// exists but cannot be executed, 127 when it does not exist. `located` is `cmd`
+    /// with a relative path resolved against the session's directory.
+    fn not_runnable(located: &str, cmd: &str, search_path: &SearchPath) -> CommandFailure {
+        let path = if cmd.contains('/') {
+            Some(located.to_string())
+        } else {
+            PathHelper::find_file(cmd, search_path)
+        };
+        let Some(path) = path else {
+            return CommandFailure::new(127, format!("{}: command not found", cmd));
+        };
+
+        let path = Path::new(&path);
+        if path.is_dir() {
+            CommandFailure::new(126, format!("{}: Is a directory", cmd))
+        } else if path.exists() {
+            CommandFailure::new(126, format!("{}: Permission denied", cmd))
+        } else {
+            CommandFailure::new(127, format!("{}: No such file or directory", cmd))
+        }
     }
 }
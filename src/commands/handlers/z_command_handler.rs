@@ -0,0 +1,107 @@
+use crate::commands::builtin::Builtin;
+use crate::commands::cd_command_handler::change_directory;
+use crate::commands::command_handler::CommandHandler;
+use crate::commands::frecency::{self, FrecencyDatabase, RankMode};
+use crate::commands::shell_state::ShellState;
+use crate::commands::supported_command::SupportedCommand;
+use std::path::Path;
+
+const BUILTIN: Builtin = Builtin {
+    name: "z",
+    usage: "z [-chlrtx] [fragment ...]",
+    help: "Jump to the highest-ranked visited directory matching the fragments.\n\
+           -l lists the ranking, -r and -t rank by visits or recency, -x forgets the\n\
+           current directory and -c only matches its subdirectories.",
+    flags: "chlrtx",
+    special: false,
+};
+
+/// Handles `z`, which jumps to the best-ranked visited directory matching the
+/// given fragments. Every directory change records a visit, see `frecency`.
+pub struct ZCommandHandler;
+
+impl CommandHandler for ZCommandHandler {
+    fn builtins(&self) -> &'static [Builtin] {
+        &[BUILTIN]
+    }
+
+    fn handle(&self, cmd: &SupportedCommand, state: &mut ShellState) -> anyhow::Result<String> {
+        let SupportedCommand::Builtin { args, .. } = cmd else {
+            anyhow::bail!("Unsupported command passed to ZCommandHandler");
+        };
+
+        let mut list = false;
+        let mut remove = false;
+        let mut below_cwd = false;
+        let mut mode = RankMode::Frecency;
+        let mut fragments = Vec::new();
+
+        let mut iter = args.iter();
+        while let Some(arg) = iter.next() {
+            if arg == "--" {
+                fragments.extend(iter.by_ref().cloned());
+                break;
+            }
+            let Some(flags) = arg.strip_prefix('-').filter(|f| !f.is_empty()) else {
+                fragments.push(arg.clone());
+                continue;
+            };
+            for flag in flags.chars() {
+                match flag {
+                    'l' => list = true,
+                    'r' => mode = RankMode::Rank,
+                    't' => mode = RankMode::Recent,
+                    'x' => remove = true,
+                    'c' => below_cwd = true,
+                    'h' => return Ok(format!("{}\n", BUILTIN.usage_line())),
+                    _ => return Err(BUILTIN.invalid_option(&format!("-{}", flag))),
+                }
+            }
+        }
+
+        let cwd = state.cwd().to_string();
+        let Some(path) = FrecencyDatabase::data_path(&state.variables) else {
+            anyhow::bail!("z: HOME not set");
+        };
+        if remove {
+            FrecencyDatabase::update(&path, |database| database.remove(&cwd))?;
+            return Ok(String::new());
+        }
+        let database = FrecencyDatabase::load(&path);
+
+        // A completed path, as completion inserts it, is used as is.
+        if let [dir] = fragments.as_slice()
+            && dir.starts_with('/')
+            && Path::new(dir).is_dir()
+            && !list
+        {
+            change_directory(state, "z", dir, dir, false)?;
+            return Ok(String::new());
+        }
+
+        let mut ranked = database.rank(&fragments, mode, frecency::now());
+        if below_cwd {
+            let prefix = format!("{}/", cwd.trim_end_matches('/'));
+            ranked.retain(|(_, dir)| dir.starts_with(&prefix));
+        }
+
+        // Listing shows the best match last, nearest the prompt.
+        if list || fragments.is_empty() {
+            return Ok(ranked
+                .iter()
+                .rev()
+                .map(|(score, dir)| format!("{:<10} {}\n", format!("{:.1}", score), dir))
+                .collect());
+        }
+
+        let Some((_, dir)) = ranked.first() else {
+            anyhow::bail!("z: no match for {}", fragments.join(" "));
+        };
+        change_directory(state, "z", dir, dir, false)?;
+        Ok(String::new())
+    }
+
+    fn as_any(&self) -> &dyn std::any::Any {
+        self
+    }
+}
@@ -1,57 +1,106 @@
+use std::fs;
 use std::os::unix::fs::PermissionsExt;
-use std::path::Path;
-use std::{env, fs};
+use std::path::{Path, PathBuf};
+
+/// The directories of a `PATH` value. As in bash, relative entries, including
+/// empty ones, are relative to the shell's working directory rather than the
+/// process's.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct SearchPath {
+    /// Each directory, with whether its entry was relative.
+    dirs: Vec<(PathBuf, bool)>,
+}
+
+impl SearchPath {
+    pub fn new(path_var: &str, cwd: &str) -> Self {
+        let dirs = path_var
+            .split(':')
+            .map(|dir| match dir {
+                "" | "." => (PathBuf::from(cwd), true),
+                dir if Path::new(dir).is_relative() => (Path::new(cwd).join(dir), true),
+                dir => (PathBuf::from(dir), false),
+            })
+            .collect();
+        Self { dirs }
+    }
+
+    /// The directories to search, in order.
+    pub fn dirs(&self) -> impl Iterator<Item = &Path> {
+        self.dirs.iter().map(|(dir, _)| dir.as_path())
+    }
+}
 
 pub struct PathHelper;
 
 impl PathHelper {
-    pub fn find_executable(cmd: &str) -> Option<String> {
-        let path_var = env::var("PATH").ok()?;
+    /// Finds the executable named `cmd` in `path`.
+    pub fn find_executable(cmd: &str, path: &SearchPath) -> Option<String> {
+        Self::locate_executable(cmd, path).map(|(found, _)| found)
+    }
 
-        for dir in path_var.split(':') {
-            let full_path = Path::new(dir).join(cmd);
+    /// Finds the executable named `cmd` in `path`, along with whether it was found
+    /// through a relative entry, so that it depends on the working directory.
+    pub fn locate_executable(cmd: &str, path: &SearchPath) -> Option<(String, bool)> {
+        path.dirs.iter().find_map(|(dir, relative)| {
+            let full_path = dir.join(cmd);
+            Self::is_executable(&full_path)
+                .then(|| (full_path.to_string_lossy().to_string(), *relative))
+        })
+    }
 
-            if let Ok(metadata) = fs::metadata(&full_path) {
-                if metadata.is_file() {
-                    let perms = metadata.permissions();
-                    if perms.mode() & 0o111 != 0 {
-                        return Some(full_path.to_string_lossy().to_string());
-                    }
+    /// Finds every executable named `cmd` in PATH, in PATH order.
+    pub fn find_all_executables(cmd: &str, path: &SearchPath) -> Vec<String> {
+        let mut found: Vec<String> = Vec::new();
+        for dir in path.dirs() {
+            let full_path = dir.join(cmd);
+            if Self::is_executable(&full_path) {
+                let path = full_path.to_string_lossy().to_string();
+                if !found.contains(&path) {
+                    found.push(path);
                 }
             }
         }
-        None
+        found
     }
 
-    pub fn get_all_executables() -> Option<Vec<String>> {
-        let mut executables = Vec::new();
-        if let Ok(path_var) = env::var("PATH") {
-            for dir in path_var.split(':') {
-                let dir_path = Path::new(dir);
+    /// Finds a regular file named `cmd` in PATH whether or not it is executable.
+    pub fn find_file(cmd: &str, path: &SearchPath) -> Option<String> {
+        path.dirs()
+            .map(|dir| dir.join(cmd))
+            .find(|path| path.is_file())
+            .map(|path| path.to_string_lossy().to_string())
+    }
+
+    fn is_executable(path: &Path) -> bool {
+        fs::metadata(path)
+            .is_ok_and(|metadata| metadata.is_file() && metadata.permissions().mode() & 0o111 != 0)
+    }
 
-                if let Ok(entries) = fs::read_dir(dir_path) {
-                    for entry in entries.flatten() {
-                        let path = entry.path();
+    pub fn get_all_executables(path: &SearchPath) -> Option<Vec<String>> {
+        let mut executables = Vec::new();
+        for dir in path.dirs() {
+            if let Ok(entries) = fs::read_dir(dir) {
+                for entry in entries.flatten() {
+                    let path = entry.path();
 
-                        if path.is_file() {
-                            #[cfg(unix)]
+                    if path.is_file() {
+                        #[cfg(unix)]
+                        {
+                            use std::os::unix::fs::PermissionsExt;
+                            let perm = path.metadata().ok()?.permissions();
+                            if perm.mode() & 0o111 != 0
+                                && let Some(name) = path.file_name()
                             {
-                                use std::os::unix::fs::PermissionsExt;
-                                let perm = path.metadata().ok()?.permissions();
-                                if perm.mode() & 0o111 != 0 {
-                                    if let Some(name) = path.file_name() {
-                                        executables.push(name.to_string_lossy().to_string());
-                                    }
-                                }
+                                executables.push(name.to_string_lossy().to_string());
                             }
+                        }
 
-                            #[cfg(windows)]
-                            {
-                                if let Some(ext) = path.extension() {
-                                    if ext == "exe" {
-                                        if let Some(name) = path.file_name() {
-                                            executables.push(name.to_string_lossy().to_string());
-                                        }
+                        #[cfg(windows)]
+                        {
+                            if let Some(ext) = path.extension() {
+                                if ext == "exe" {
+                                    if let Some(name) = path.file_name() {
+                                        executables.push(name.to_string_lossy().to_string());
                                     }
                                 }
                             }
@@ -64,15 +113,7 @@ impl PathHelper {
         Some(executables)
     }
 
-    pub fn get_current_dir() -> Option<String> {
-        let dir = env::current_dir().unwrap();
-        Some(format!("{}", dir.display()))
-    }
     pub fn path_exists(path: &String) -> bool {
         Path::new(path).exists()
     }
-
-    pub fn change_dir(path: &String) -> std::io::Result<()> {
-        env::set_current_dir(path)
-    }
 }
@@ -0,0 +1,165 @@
+use crate::core::pattern::{self, MatchOptions};
+use std::fs;
+use std::path::Path;
+
+/// The shell options that change pathname expansion.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct GlobOptions {
+    /// `?(...)`, `*(...)`, `+(...)`, `@(...)` and `!(...)` are patterns too.
+    pub extglob: bool,
+    /// `**` as a whole component matches any number of directories.
+    pub globstar: bool,
+    /// Names are matched without regard to case.
+    pub nocaseglob: bool,
+}
+
+impl GlobOptions {
+    fn match_options(&self) -> MatchOptions {
+        MatchOptions {
+            extglob: self.extglob,
+            ignore_case: self.nocaseglob,
+        }
+    }
+}
+
+/// Pathname expansion: the paths matching `pattern`, sorted, relative to `cwd`
+/// unless the pattern is absolute.
+///
+/// Each `/`-separated component with a wildcard is matched against the entries of
+/// the directories matched so far. Names starting with `.` only match a component
+/// that starts with a literal `.`, and a trailing `/` only matches directories.
+/// With `globstar`, a `**` component matches the directory it is in and every
+/// directory below it, or when it is last, every file below it as well.
+pub fn expand(pattern: &str, cwd: &str, options: GlobOptions) -> Vec<String> {
+    let (mut paths, rest) = match pattern.strip_prefix('/') {
+        Some(rest) => (vec!["/".to_string()], rest),
+        None => (vec![String::new()], pattern),
+    };
+
+    let components: Vec<&str> = rest.split('/').filter(|c| !c.is_empty()).collect();
+    for (i, component) in components.iter().enumerate() {
+        let last = i + 1 == components.len() && !rest.ends_with('/');
+        paths = paths
+            .iter()
+            .flat_map(|base| match *component {
+                "**" if options.globstar => descendants(base, cwd, last),
+                _ => expand_component(base, component, cwd, options),
+            })
+            .collect();
+    }
+    // `**/` also matches `cwd` itself, which is the empty path and not listed.
+    paths.retain(|path| !path.is_empty());
+
+    if rest.ends_with('/') {
+        paths.retain(|path| Path::new(cwd).join(path).is_dir());
+        for path in &mut paths {
+            path.push('/');
+        }
+    }
+    paths.sort();
+    paths.dedup();
+    paths
+}
+
+/// Whether `pattern` has a `*`, `?` or `[` that is not escaped, or with `extglob`
+/// an unescaped `+(`, `@(` or `!(`.
+pub fn has_wildcards(pattern: &str, options: GlobOptions) -> bool {
+    let mut chars = pattern.chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            '\\' => {
+                chars.next();
+            }
+            '*' | '?' | '[' => return true,
+            '+' | '@' | '!' if options.extglob && chars.peek() == Some(&'(') => return true,
+            _ => {}
+        }
+    }
+    false
+}
+
+/// The paths under `base` matching one component of a pattern.
+fn expand_component(base: &str, component: &str, cwd: &str, options: GlobOptions) -> Vec<String> {
+    if !has_wildcards(component, options) {
+        let path = join(base, &unescape(component));
+        return match Path::new(cwd).join(&path).symlink_metadata() {
+            Ok(_) => vec![path],
+            Err(_) => Vec::new(),
+        };
+    }
+
+    let dir = Path::new(cwd).join(if base.is_empty() { "." } else { base });
+    let Ok(entries) = fs::read_dir(dir) else {
+        return Vec::new();
+    };
+    entries
+        .flatten()
+        .filter_map(|entry| {
+            let name = entry.file_name().into_string().ok()?;
+            if name.starts_with('.') && !component.starts_with('.') {
+                return None;
+            }
+            pattern::matches_with(component, &name, options.match_options())
+                .then(|| join(base, &name))
+        })
+        .collect()
+}
+
+/// `base` and the directories below it, or with `files` set, everything below it.
+/// Hidden entries are skipped and symbolic links are not followed.
+fn descendants(base: &str, cwd: &str, files: bool) -> Vec<String> {
+    let mut paths = Vec::new();
+    if !files {
+        paths.push(base.to_string());
+    }
+
+    let dir = Path::new(cwd).join(if base.is_empty() { "." } else { base });
+    let Ok(entries) = fs::read_dir(dir) else {
+        return paths;
+    };
+    let mut entries: Vec<_> = entries
+        .flatten()
+        .filter_map(|entry| {
+            let name = entry.file_name().into_string().ok()?;
+            let is_dir = entry.file_type().ok()?.is_dir();
+            (!name.starts_with('.')).then_some((name, is_dir))
+        })
+        .collect();
+    entries.sort();
+
+    for (name, is_dir) in entries {
+        let path = join(base, &name);
+        match (is_dir, files) {
+            (true, true) => {
+                paths.push(path.clone());
+                paths.extend(descendants(&path, cwd, true));
+            }
+            (true, false) => paths.extend(descendants(&path, cwd, false)),
+            (false, true) => paths.push(path),
+            (false, false) => {}
+        }
+    }
+    paths
+}
+
+/// `name` inside the directory `base`, which is empty for the working directory.
+fn join(base: &str, name: &str) -> String {
+    match base {
+        "" => name.to_string(),
+        _ if base.ends_with('/') => format!("{}{}", base, name),
+        _ => format!("{}/{}", base, name),
+    }
+}
+
+/// Removes the backslashes escaping characters of a pattern.
+fn unescape(pattern: &str) -> String {
+    let mut text = String::new();
+    let mut chars = pattern.chars();
+    while let Some(c) = chars.next() {
+        match c {
+            '\\' => text.extend(chars.next()),
+            _ => text.push(c),
+        }
+    }
+    text
+}
@@ -1 +1,7 @@
+pub mod edit_distance;
+pub mod glob;
+pub mod pattern;
+pub mod printf_format;
+pub mod signals;
+pub mod time_format;
 pub mod utils;
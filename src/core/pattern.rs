@@ -0,0 +1,203 @@
+/// Options changing how [`matches_with`] reads a pattern.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct MatchOptions {
+    /// Recognise the `extglob` operators `?(...)`, `*(...)`, `+(...)`, `@(...)` and
+    /// `!(...)`, whose `|`-separated alternatives are patterns themselves.
+    pub extglob: bool,
+    /// Compare letters without regard to case.
+    pub ignore_case: bool,
+}
+
+/// Matches `text` against a shell glob pattern supporting `*`, `?`, bracket
+/// expressions (`[abc]`, `[a-z]`, `[!x]`) and backslash escapes.
+pub fn matches(pattern: &str, text: &str) -> bool {
+    matches_with(pattern, text, MatchOptions::default())
+}
+
+/// Like [`matches`], with `extglob` operators or case-insensitive matching.
+pub fn matches_with(pattern: &str, text: &str, options: MatchOptions) -> bool {
+    let pattern: Vec<char> = pattern.chars().collect();
+    let text: Vec<char> = text.chars().collect();
+    Matcher { options }.matches_from(&pattern, &text)
+}
+
+struct Matcher {
+    options: MatchOptions,
+}
+
+impl Matcher {
+    fn matches_from(&self, pattern: &[char], text: &[char]) -> bool {
+        let Some(&first) = pattern.first() else {
+            return text.is_empty();
+        };
+
+        if self.options.extglob
+            && let Some((len, alternatives)) = extglob_group(pattern)
+        {
+            return self.matches_group(first, &alternatives, &pattern[len..], text);
+        }
+
+        match first {
+            '*' => (0..=text.len()).any(|skip| self.matches_from(&pattern[1..], &text[skip..])),
+            '?' => !text.is_empty() && self.matches_from(&pattern[1..], &text[1..]),
+            '[' => match bracket_expression(pattern) {
+                Some((len, set)) => {
+                    !text.is_empty()
+                        && set.contains(text[0], self.options.ignore_case)
+                        && self.matches_from(&pattern[len..], &text[1..])
+                }
+                None => self.literal(pattern, text),
+            },
+            '\\' if pattern.len() > 1 => {
+                !text.is_empty()
+                    && self.same(text[0], pattern[1])
+                    && self.matches_from(&pattern[2..], &text[1..])
+            }
+            _ => self.literal(pattern, text),
+        }
+    }
+
+    fn literal(&self, pattern: &[char], text: &[char]) -> bool {
+        !text.is_empty()
+            && self.same(text[0], pattern[0])
+            && self.matches_from(&pattern[1..], &text[1..])
+    }
+
+    fn same(&self, a: char, b: char) -> bool {
+        a == b || (self.options.ignore_case && a.to_lowercase().eq(b.to_lowercase()))
+    }
+
+    /// Matches an `extglob` group, `op(alternatives)`, followed by `rest`.
+    fn matches_group(
+        &self,
+        op: char,
+        alternatives: &[Vec<char>],
+        rest: &[char],
+        text: &[char],
+    ) -> bool {
+        let splits = 0..=text.len();
+        match op {
+            '?' => {
+                self.matches_from(rest, text)
+                    || splits.into_iter().any(|k| {
+                        self.matches_any(alternatives, &text[..k])
+                            && self.matches_from(rest, &text[k..])
+                    })
+            }
+            '@' => splits.into_iter().any(|k| {
+                self.matches_any(alternatives, &text[..k]) && self.matches_from(rest, &text[k..])
+            }),
+            '!' => splits.into_iter().any(|k| {
+                !self.matches_any(alternatives, &text[..k]) && self.matches_from(rest, &text[k..])
+            }),
+            '*' => self.matches_repeated(alternatives, rest, text),
+            _ => splits.into_iter().any(|k| {
+                self.matches_any(alternatives, &text[..k])
+                    && self.matches_repeated(alternatives, rest, &text[k..])
+            }),
+        }
+    }
+
+    /// Matches zero or more non-empty repetitions of `alternatives` followed by `rest`.
+    fn matches_repeated(&self, alternatives: &[Vec<char>], rest: &[char], text: &[char]) -> bool {
+        self.matches_from(rest, text)
+            || (1..=text.len()).any(|k| {
+                self.matches_any(alternatives, &text[..k])
+                    && self.matches_repeated(alternatives, rest, &text[k..])
+            })
+    }
+
+    fn matches_any(&self, alternatives: &[Vec<char>], text: &[char]) -> bool {
+        alternatives
+            .iter()
+            .any(|alternative| self.matches_from(alternative, text))
+    }
+}
+
+/// Parses the `extglob` group at the start of `pattern`, such as `@(a|b*)`,
+/// returning its length and alternatives.
+fn extglob_group(pattern: &[char]) -> Option<(usize, Vec<Vec<char>>)> {
+    if !matches!(pattern.first(), Some('?' | '*' | '+' | '@' | '!')) || pattern.get(1) != Some(&'(')
+    {
+        return None;
+    }
+
+    let mut alternatives = vec![Vec::new()];
+    let mut depth = 0;
+    let mut i = 2;
+    while i < pattern.len() {
+        let c = pattern[i];
+        match c {
+            '\\' if i + 1 < pattern.len() => {
+                alternatives.last_mut()?.extend([c, pattern[i + 1]]);
+                i += 2;
+                continue;
+            }
+            ')' if depth == 0 => return Some((i + 1, alternatives)),
+            '|' if depth == 0 => {
+                alternatives.push(Vec::new());
+                i += 1;
+                continue;
+            }
+            '(' => depth += 1,
+            ')' => depth -= 1,
+            _ => {}
+        }
+        alternatives.last_mut()?.push(c);
+        i += 1;
+    }
+    None
+}
+
+/// A parsed `[...]` expression.
+struct CharSet {
+    negated: bool,
+    ranges: Vec<(char, char)>,
+}
+
+impl CharSet {
+    fn contains(&self, c: char, ignore_case: bool) -> bool {
+        let in_range = |c: char| self.ranges.iter().any(|&(lo, hi)| lo <= c && c <= hi);
+        let found = match ignore_case {
+            true => c.to_lowercase().chain(c.to_uppercase()).any(in_range) || in_range(c),
+            false => in_range(c),
+        };
+        found != self.negated
+    }
+}
+
+/// Parses the bracket expression at the start of `pattern`, returning its length.
+fn bracket_expression(pattern: &[char]) -> Option<(usize, CharSet)> {
+    let mut i = 1;
+    let negated = matches!(pattern.get(i), Some('!' | '^'));
+    if negated {
+        i += 1;
+    }
+
+    let mut ranges = Vec::new();
+    let mut first = true;
+    while i < pattern.len() {
+        let c = pattern[i];
+        if c == ']' && !first {
+            return Some((i + 1, CharSet { negated, ranges }));
+        }
+        first = false;
+
+        let lo = if c == '\\' && i + 1 < pattern.len() {
+            i += 1;
+            pattern[i]
+        } else {
+            c
+        };
+
+        if pattern.get(i + 1) == Some(&'-') && pattern.get(i + 2).is_some_and(|c| *c != ']') {
+            ranges.push((lo, pattern[i + 2]));
+            i += 3;
+        } else {
+            ranges.push((lo, lo));
+            i += 1;
+        }
+    }
+
+    None
+}
@@ -0,0 +1,485 @@
+use std::ffi::CString;
+
+/// What formatting with [`printf`] produced.
+pub struct Printed {
+    pub output: Vec<u8>,
+    /// Diagnostics for arguments that are not valid numbers and for bad formats.
+    pub errors: Vec<String>,
+    /// Set when one of the errors should make the status non-zero. A number out of
+    /// range only gives a warning.
+    pub failed: bool,
+}
+
+/// A conversion specification, such as `%-8.3s`.
+struct Spec {
+    flags: String,
+    width: Option<Count>,
+    precision: Option<Count>,
+    conversion: char,
+}
+
+/// A width or precision: a number, or `*` to take it from the arguments.
+enum Count {
+    Fixed(usize),
+    Next,
+}
+
+/// A value to format with `snprintf(3)`.
+enum CValue {
+    Signed(i64),
+    Unsigned(u64),
+    Float(f64),
+}
+
+/// Formats `args` with a `printf(1)` format. The format is reused while arguments
+/// are left, and missing arguments are empty strings or zero.
+pub fn printf(format: &str, args: &[String]) -> Printed {
+    let mut printer = Printer {
+        format: format.chars().collect(),
+        args,
+        next_arg: 0,
+        printed: Printed {
+            output: Vec::new(),
+            errors: Vec::new(),
+            failed: false,
+        },
+    };
+    loop {
+        let first = printer.next_arg;
+        if !printer.format_once() || printer.next_arg == first || printer.next_arg >= args.len() {
+            break;
+        }
+    }
+    printer.printed
+}
+
+struct Printer<'a> {
+    format: Vec<char>,
+    args: &'a [String],
+    next_arg: usize,
+    printed: Printed,
+}
+
+impl Printer<'_> {
+    /// Formats the whole format once. Returns `false` when output should stop, at an
+    /// invalid conversion or at `\c` in a `%b` argument.
+    fn format_once(&mut self) -> bool {
+        let mut i = 0;
+        while i < self.format.len() {
+            match self.format[i] {
+                '\\' => {
+                    let (bytes, _) = unescape(&self.format, &mut i, false);
+                    self.printed.output.extend(bytes);
+                }
+                '%' if self.format.get(i + 1) == Some(&'%') => {
+                    self.printed.output.push(b'%');
+                    i += 2;
+                }
+                '%' => {
+                    i += 1;
+                    let Some(spec) = self.parse_spec(&mut i) else {
+                        return false;
+                    };
+                    if !self.convert(&spec) {
+                        return false;
+                    }
+                }
+                c => {
+                    push_char(&mut self.printed.output, c);
+                    i += 1;
+                }
+            }
+        }
+        true
+    }
+
+    /// Parses the flags, width, precision and conversion after a `%`.
+    fn parse_spec(&mut self, i: &mut usize) -> Option<Spec> {
+        let at = |i: usize| self.format.get(i).copied();
+        let start = *i;
+        let mut flags = String::new();
+        while let Some(flag @ ('-' | '+' | ' ' | '#' | '0')) = at(*i) {
+            flags.push(flag);
+            *i += 1;
+        }
+
+        let count = |i: &mut usize| {
+            if at(*i) == Some('*') {
+                *i += 1;
+                return Some(Count::Next);
+            }
+            let digits: String = self.format[*i..]
+                .iter()
+                .take_while(|c| c.is_ascii_digit())
+                .collect();
+            *i += digits.len();
+            (!digits.is_empty()).then(|| Count::Fixed(digits.parse().unwrap_or(usize::MAX)))
+        };
+        let width = count(i);
+        let precision = match at(*i) {
+            Some('.') => {
+                *i += 1;
+                Some(count(i).unwrap_or(Count::Fixed(0)))
+            }
+            _ => None,
+        };
+
+        // Length modifiers change nothing, as every number is 64 bits wide.
+        while let Some('h' | 'l' | 'L' | 'j' | 'z' | 't') = at(*i) {
+            *i += 1;
+        }
+
+        let Some(conversion) = at(*i) else {
+            let spec: String = self.format[start - 1..].iter().collect();
+            self.fail(format!("`{}': missing format character", spec));
+            return None;
+        };
+        *i += 1;
+        Some(Spec {
+            flags,
+            width,
+            precision,
+            conversion,
+        })
+    }
+
+    /// Formats the next argument with `spec`. Returns `false` when output should stop.
+    fn convert(&mut self, spec: &Spec) -> bool {
+        let mut flags = spec.flags.clone();
+        let mut width = match spec.width {
+            Some(Count::Next) => Some(self.next_integer()),
+            Some(Count::Fixed(width)) => Some(width as i64),
+            None => None,
+        };
+        // A negative `*` width left-justifies, and a negative precision is ignored.
+        if let Some(w) = width.filter(|w| *w < 0) {
+            flags.push('-');
+            width = Some(-w);
+        }
+        let precision = match spec.precision {
+            Some(Count::Next) => Some(self.next_integer()).filter(|p| *p >= 0),
+            Some(Count::Fixed(precision)) => Some(precision as i64),
+            None => None,
+        };
+        let width = width.map(|w| w as usize);
+        let precision = precision.map(|p| p as usize);
+
+        match spec.conversion {
+            's' | 'q' | 'c' | 'b' => {
+                let arg = self.next_arg();
+                let (bytes, keep_going) = match spec.conversion {
+                    's' => (arg.unwrap_or_default().as_bytes().to_vec(), true),
+                    'q' => (shell_quote(arg.unwrap_or_default()).into_bytes(), true),
+                    'c' => {
+                        let c = arg.and_then(|arg| arg.chars().next());
+                        (c.map(String::from).unwrap_or_default().into_bytes(), true)
+                    }
+                    _ => {
+                        let chars: Vec<char> = arg.unwrap_or_default().chars().collect();
+                        let mut bytes = Vec::new();
+                        let mut i = 0;
+                        let mut keep_going = true;
+                        while i < chars.len() {
+                            if chars[i] != '\\' {
+                                push_char(&mut bytes, chars[i]);
+                                i += 1;
+                                continue;
+                            }
+                            let (escaped, stop) = unescape(&chars, &mut i, true);
+                            bytes.extend(escaped);
+                            if stop {
+                                keep_going = false;
+                                break;
+                            }
+                        }
+                        (bytes, keep_going)
+                    }
+                };
+                if width.is_none() && precision.is_none() {
+                    self.printed.output.extend(bytes);
+                    return keep_going;
+                }
+                let text = String::from_utf8_lossy(&bytes);
+                let text: String = match precision {
+                    Some(precision) => text.chars().take(precision).collect(),
+                    None => text.into_owned(),
+                };
+                let padding = " ".repeat(width.unwrap_or(0).saturating_sub(text.chars().count()));
+                let padded = match flags.contains('-') {
+                    true => format!("{}{}", text, padding),
+                    false => format!("{}{}", padding, text),
+                };
+                self.printed.output.extend(padded.into_bytes());
+                keep_going
+            }
+            conversion
+            @ ('d' | 'i' | 'u' | 'o' | 'x' | 'X' | 'e' | 'E' | 'f' | 'F' | 'g' | 'G') => {
+                let (length, value) = match conversion {
+                    'd' | 'i' => ("ll", CValue::Signed(self.next_integer())),
+                    'u' | 'o' | 'x' | 'X' => ("ll", CValue::Unsigned(self.next_unsigned())),
+                    _ => ("", CValue::Float(self.next_float())),
+                };
+                let mut c_spec = format!("%{}", flags);
+                if let Some(width) = width {
+                    c_spec.push_str(&width.to_string());
+                }
+                if let Some(precision) = precision {
+                    c_spec.push_str(&format!(".{}", precision));
+                }
+                c_spec.push_str(length);
+                c_spec.push(conversion);
+                self.printed.output.extend(c_format(&c_spec, value));
+                true
+            }
+            conversion => {
+                self.fail(format!("`{}': invalid format character", conversion));
+                false
+            }
+        }
+    }
+
+    fn next_arg(&mut self) -> Option<&str> {
+        let arg = self.args.get(self.next_arg)?;
+        self.next_arg += 1;
+        Some(arg)
+    }
+
+    fn next_integer(&mut self) -> i64 {
+        let Some(arg) = self.next_arg().map(str::to_string) else {
+            return 0;
+        };
+        let value = self.parse_integer(&arg);
+        match i64::try_from(value) {
+            Ok(value) => value,
+            Err(_) => {
+                self.warn_out_of_range(&arg);
+                if value < 0 { i64::MIN } else { i64::MAX }
+            }
+        }
+    }
+
+    /// An argument for `%u`, `%o` or `%x`, where negative numbers wrap around.
+    fn next_unsigned(&mut self) -> u64 {
+        let Some(arg) = self.next_arg().map(str::to_string) else {
+            return 0;
+        };
+        let value = self.parse_integer(&arg);
+        match u64::try_from(value) {
+            Ok(value) => value,
+            Err(_) if value >= i64::MIN as i128 => value as i64 as u64,
+            Err(_) => {
+                self.warn_out_of_range(&arg);
+                if value < 0 { i64::MIN as u64 } else { u64::MAX }
+            }
+        }
+    }
+
+    fn next_float(&mut self) -> f64 {
+        let Some(arg) = self.next_arg().map(str::to_string) else {
+            return 0.0;
+        };
+        if let Some(code) = char_code(&arg) {
+            return code as f64;
+        }
+        let Ok(c_arg) = CString::new(arg.as_str()) else {
+            self.fail(format!("{}: invalid number", arg));
+            return 0.0;
+        };
+        let mut end = std::ptr::null_mut();
+        // SAFETY: `c_arg` is a valid C string, and `end` is set to a pointer into it.
+        let (value, parsed) = unsafe {
+            let value = libc::strtod(c_arg.as_ptr(), &mut end);
+            (value, end.offset_from(c_arg.as_ptr()) as usize)
+        };
+        if parsed < arg.len() || (parsed == 0 && !arg.is_empty()) {
+            self.fail(format!("{}: invalid number", arg));
+        }
+        value
+    }
+
+    /// Parses an integer as `strtoll` does: decimal, octal with a leading `0` or
+    /// hexadecimal with `0x`. A leading quote gives the code of the next character.
+    /// Values too large for any 64-bit type are clamped just beyond it.
+    fn parse_integer(&mut self, arg: &str) -> i128 {
+        if let Some(code) = char_code(arg) {
+            return code as i128;
+        }
+
+        let text = arg.trim_start();
+        let (negative, digits) = match text.as_bytes().first() {
+            Some(b'-') => (true, &text[1..]),
+            Some(b'+') => (false, &text[1..]),
+            _ => (false, text),
+        };
+        let (radix, digits) = match digits.strip_prefix("0x").or(digits.strip_prefix("0X")) {
+            Some(hex) if hex.starts_with(|c: char| c.is_ascii_hexdigit()) => (16, hex),
+            _ if digits.starts_with('0') => (8, digits),
+            _ => (10, digits),
+        };
+
+        let limit = u64::MAX as i128 + 1;
+        let mut value: i128 = 0;
+        let mut parsed = 0;
+        for digit in digits.chars().map_while(|c| c.to_digit(radix)) {
+            value = (value * radix as i128 + digit as i128).min(limit);
+            parsed += 1;
+        }
+
+        if !arg.is_empty() && (parsed == 0 || parsed < digits.len()) {
+            self.fail(format!("{}: invalid number", arg));
+        }
+        if negative { -value } else { value }
+    }
+
+    fn warn_out_of_range(&mut self, arg: &str) {
+        self.printed
+            .errors
+            .push(format!("warning: {}: Numerical result out of range", arg));
+    }
+
+    fn fail(&mut self, message: String) {
+        self.printed.errors.push(message);
+        self.printed.failed = true;
+    }
+}
+
+/// Quotes `word` for `%q` as bash does: control characters use `$'...'`, and
+/// otherwise each character the shell treats specially gets a backslash.
+fn shell_quote(word: &str) -> String {
+    if word.is_empty() {
+        return "''".to_string();
+    }
+
+    if word.chars().any(char::is_control) {
+        let mut quoted = String::from("$'");
+        for c in word.chars() {
+            match c {
+                '\x07' => quoted.push_str("\\a"),
+                '\x08' => quoted.push_str("\\b"),
+                '\x1b' => quoted.push_str("\\E"),
+                '\x0c' => quoted.push_str("\\f"),
+                '\n' => quoted.push_str("\\n"),
+                '\r' => quoted.push_str("\\r"),
+                '\t' => quoted.push_str("\\t"),
+                '\x0b' => quoted.push_str("\\v"),
+                '\\' | '\'' => {
+                    quoted.push('\\');
+                    quoted.push(c);
+                }
+                c if c.is_ascii_control() => quoted.push_str(&format!("\\{:03o}", c as u32)),
+                c => quoted.push(c),
+            }
+        }
+        quoted.push('\'');
+        return quoted;
+    }
+
+    let mut quoted = String::new();
+    for (i, c) in word.chars().enumerate() {
+        // `~` and `#` are only special at the start of a word.
+        let special = matches!(c, '~' | '#') && i == 0 || " '\"\\|&;()<>!{}*?[]^$`,".contains(c);
+        if special {
+            quoted.push('\\');
+        }
+        quoted.push(c);
+    }
+    quoted
+}
+
+/// The code of the character after a leading `'` or `"`, as in `printf %d "'A"`.
+fn char_code(arg: &str) -> Option<u32> {
+    let rest = arg.strip_prefix(['\'', '"'])?;
+    Some(rest.chars().next().map_or(0, u32::from))
+}
+
+/// Formats one value with a C conversion specification.
+fn c_format(spec: &str, value: CValue) -> Vec<u8> {
+    let Ok(c_spec) = CString::new(spec) else {
+        return Vec::new();
+    };
+    // SAFETY: the conversion in `c_spec` takes the type of the value passed for it,
+    // and `buffer` is one byte longer than the length `snprintf` reports it needs.
+    unsafe {
+        let print = |buffer: *mut libc::c_char, len: usize| match value {
+            CValue::Signed(value) => {
+                libc::snprintf(buffer, len, c_spec.as_ptr(), value as libc::c_longlong)
+            }
+            CValue::Unsigned(value) => {
+                libc::snprintf(buffer, len, c_spec.as_ptr(), value as libc::c_ulonglong)
+            }
+            CValue::Float(value) => libc::snprintf(buffer, len, c_spec.as_ptr(), value),
+        };
+        let Ok(len) = usize::try_from(print(std::ptr::null_mut(), 0)) else {
+            return Vec::new();
+        };
+        let mut buffer = vec![0u8; len + 1];
+        print(buffer.as_mut_ptr().cast(), buffer.len());
+        buffer.truncate(len);
+        buffer
+    }
+}
+
+fn push_char(output: &mut Vec<u8>, c: char) {
+    output.extend(c.encode_utf8(&mut [0; 4]).as_bytes());
+}
+
+/// Decodes the backslash escape at `chars[*i]`, moving `i` past it. `%b` arguments
+/// also accept `\0nnn` and `\c`, which stops the output and is reported with `true`.
+fn unescape(chars: &[char], i: &mut usize, argument: bool) -> (Vec<u8>, bool) {
+    let Some(&c) = chars.get(*i + 1) else {
+        *i += 1;
+        return (b"\\".to_vec(), false);
+    };
+    *i += 2;
+
+    let byte = match c {
+        'a' => 0x07,
+        'b' => 0x08,
+        'e' | 'E' => 0x1b,
+        'f' => 0x0c,
+        'n' => b'\n',
+        'r' => b'\r',
+        't' => b'\t',
+        'v' => 0x0b,
+        '\\' | '"' | '\'' | '?' => c as u8,
+        'c' if argument => return (Vec::new(), true),
+        '0' if argument => number(chars, i, 8, 3).unwrap_or(0) as u8,
+        '0'..='7' => {
+            *i -= 1;
+            number(chars, i, 8, 3).unwrap_or(0) as u8
+        }
+        'x' => match number(chars, i, 16, 2) {
+            Some(value) => value as u8,
+            None => return (b"\\x".to_vec(), false),
+        },
+        'u' | 'U' => {
+            let max_digits = if c == 'u' { 4 } else { 8 };
+            let Some(decoded) = number(chars, i, 16, max_digits).and_then(char::from_u32) else {
+                return (format!("\\{}", c).into_bytes(), false);
+            };
+            let mut bytes = Vec::new();
+            push_char(&mut bytes, decoded);
+            return (bytes, false);
+        }
+        c => {
+            let mut bytes = b"\\".to_vec();
+            push_char(&mut bytes, c);
+            return (bytes, false);
+        }
+    };
+    (vec![byte], false)
+}
+
+/// Reads up to `max_digits` digits in `radix` at `chars[*i]`, moving `i` past them.
+fn number(chars: &[char], i: &mut usize, radix: u32, max_digits: usize) -> Option<u32> {
+    let mut value = 0u32;
+    let mut count = 0;
+    while count < max_digits
+        && let Some(digit) = chars.get(*i).and_then(|c| c.to_digit(radix))
+    {
+        value = value * radix + digit;
+        *i += 1;
+        count += 1;
+    }
+    (count > 0).then_some(value)
+}
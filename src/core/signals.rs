@@ -0,0 +1,111 @@
+use std::io;
+use std::sync::atomic::{AtomicU64, Ordering};
+
+/// The signals known by name, in the order `trap -l` lists them.
+pub const SIGNALS: [(i32, &str); 29] = [
+    (libc::SIGHUP, "HUP"),
+    (libc::SIGINT, "INT"),
+    (libc::SIGQUIT, "QUIT"),
+    (libc::SIGILL, "ILL"),
+    (libc::SIGTRAP, "TRAP"),
+    (libc::SIGABRT, "ABRT"),
+    (libc::SIGBUS, "BUS"),
+    (libc::SIGFPE, "FPE"),
+    (libc::SIGKILL, "KILL"),
+    (libc::SIGUSR1, "USR1"),
+    (libc::SIGSEGV, "SEGV"),
+    (libc::SIGUSR2, "USR2"),
+    (libc::SIGPIPE, "PIPE"),
+    (libc::SIGALRM, "ALRM"),
+    (libc::SIGTERM, "TERM"),
+    (libc::SIGCHLD, "CHLD"),
+    (libc::SIGCONT, "CONT"),
+    (libc::SIGSTOP, "STOP"),
+    (libc::SIGTSTP, "TSTP"),
+    (libc::SIGTTIN, "TTIN"),
+    (libc::SIGTTOU, "TTOU"),
+    (libc::SIGURG, "URG"),
+    (libc::SIGXCPU, "XCPU"),
+    (libc::SIGXFSZ, "XFSZ"),
+    (libc::SIGVTALRM, "VTALRM"),
+    (libc::SIGPROF, "PROF"),
+    (libc::SIGWINCH, "WINCH"),
+    (libc::SIGIO, "IO"),
+    (libc::SIGSYS, "SYS"),
+];
+
+/// Signals caught since the last call to [`take_pending`], one bit per number. Only
+/// the interactive shell installs the handler that sets them.
+static PENDING: AtomicU64 = AtomicU64::new(0);
+
+/// What the process does when it receives a signal.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Disposition {
+    Default,
+    Ignore,
+    /// Record the signal for [`take_pending`]. Like any caught signal, it is back
+    /// to its default in a child process once that runs a new program, while an
+    /// ignored one stays ignored.
+    Catch,
+}
+
+/// The number of a signal given as a name, with or without `SIG` and in any case,
+/// or as a number.
+pub fn number(spec: &str) -> Option<i32> {
+    if let Ok(number) = spec.parse::<i32>() {
+        return SIGNALS
+            .iter()
+            .any(|(signal, _)| *signal == number)
+            .then_some(number);
+    }
+    let spec = spec.to_ascii_uppercase();
+    let name = spec.strip_prefix("SIG").unwrap_or(&spec);
+    SIGNALS
+        .iter()
+        .find(|(_, signal_name)| *signal_name == name)
+        .map(|(signal, _)| *signal)
+}
+
+/// The name of a signal without its `SIG` prefix.
+pub fn name(number: i32) -> Option<&'static str> {
+    SIGNALS
+        .iter()
+        .find(|(signal, _)| *signal == number)
+        .map(|(_, name)| *name)
+}
+
+/// Changes what the process does when it receives `signal`. Interrupted system
+/// calls are restarted, so a caught signal never cuts short waiting for a child.
+pub fn set_disposition(signal: i32, disposition: Disposition) -> io::Result<()> {
+    let handler = match disposition {
+        Disposition::Default => libc::SIG_DFL,
+        Disposition::Ignore => libc::SIG_IGN,
+        Disposition::Catch => record as extern "C" fn(libc::c_int) as libc::sighandler_t,
+    };
+    // SAFETY: the action is fully initialised, and `record` only touches an atomic,
+    // which is safe to do from a signal handler.
+    unsafe {
+        let mut action: libc::sigaction = std::mem::zeroed();
+        action.sa_sigaction = handler;
+        action.sa_flags = libc::SA_RESTART;
+        libc::sigemptyset(&mut action.sa_mask);
+        if libc::sigaction(signal, &action, std::ptr::null_mut()) != 0 {
+            return Err(io::Error::last_os_error());
+        }
+    }
+    Ok(())
+}
+
+/// The signals caught since the last call, in increasing order.
+pub fn take_pending() -> Vec<i32> {
+    let pending = PENDING.swap(0, Ordering::SeqCst);
+    (1..64)
+        .filter(|signal| pending & (1 << signal) != 0)
+        .collect()
+}
+
+extern "C" fn record(signal: libc::c_int) {
+    if (1..64).contains(&signal) {
+        PENDING.fetch_or(1 << signal, Ordering::SeqCst);
+    }
+}
@@ -0,0 +1,39 @@
+use std::ffi::CString;
+
+/// Formats a Unix timestamp in the local time zone with a `strftime(3)` format,
+/// as `HISTTIMEFORMAT` expects.
+pub fn format_local(format: &str, timestamp: i64) -> String {
+    if format.is_empty() {
+        return String::new();
+    }
+    let Ok(c_format) = CString::new(format) else {
+        return String::new();
+    };
+
+    let time = timestamp as libc::time_t;
+    // SAFETY: `tm` is plain old data that `localtime_r` fills in, and `buffer` is
+    // large enough for the length passed to `strftime`, which never writes past it.
+    unsafe {
+        let mut tm: libc::tm = std::mem::zeroed();
+        if libc::localtime_r(&time, &mut tm).is_null() {
+            return String::new();
+        }
+
+        // `strftime` returns 0 both on overflow and for an empty result, so grow
+        // the buffer a few times before giving up.
+        let mut buffer = vec![0u8; 256.max(format.len() * 4)];
+        for _ in 0..4 {
+            let len = libc::strftime(
+                buffer.as_mut_ptr().cast(),
+                buffer.len(),
+                c_format.as_ptr(),
+                &tm,
+            );
+            if len > 0 {
+                return String::from_utf8_lossy(&buffer[..len]).into_owned();
+            }
+            buffer.resize(buffer.len() * 2, 0);
+        }
+    }
+    String::new()
+}
@@ -1,7 +1,10 @@
 use std::env;
+use std::ffi::CString;
 use std::fs;
+use std::os::unix::ffi::OsStrExt;
+use std::os::unix::fs::MetadataExt;
 use std::os::unix::fs::PermissionsExt;
-use std::path::Path;
+use std::path::{Component, Path};
 
 pub struct PathHelper;
 
@@ -13,13 +16,11 @@ impl PathHelper {
         for dir in path_var.split(':') {
             let full_path = Path::new(dir).join(cmd);
 
-            if let Ok(metadata) = fs::metadata(&full_path) {
-                if metadata.is_file() {
-                    let perms = metadata.permissions();
-                    if perms.mode() & 0o111 != 0 {
-                        return Some(full_path.to_string_lossy().to_string());
-                    }
-                }
+            if let Ok(metadata) = fs::metadata(&full_path)
+                && metadata.is_file()
+                && metadata.permissions().mode() & 0o111 != 0
+            {
+                return Some(full_path.to_string_lossy().to_string());
             }
         }
         None
@@ -39,13 +40,11 @@ impl PathHelper {
                         if path.is_file() {
                             #[cfg(unix)]
                             {
-                                if let Ok(metadata) = path.metadata() {
-                                    let perm = metadata.permissions();
-                                    if perm.mode() & 0o111 != 0 {
-                                        if let Some(name) = path.file_name() {
-                                            executables.push(name.to_string_lossy().to_string());
-                                        }
-                                    }
+                                if let Ok(metadata) = path.metadata()
+                                    && metadata.permissions().mode() & 0o111 != 0
+                                    && let Some(name) = path.file_name()
+                                {
+                                    executables.push(name.to_string_lossy().to_string());
                                 }
                             }
 
@@ -73,13 +72,133 @@ impl PathHelper {
         env::current_dir().ok().map(|dir| dir.display().to_string())
     }
 
+    /// Returns `pwd` when it is an absolute path naming the same directory as `cwd`
+    /// without `.` or `..` components, so symlinks followed by `cd` stay visible.
+    pub fn logical_dir(pwd: Option<&str>, cwd: &str) -> Option<String> {
+        let pwd = pwd.filter(|pwd| pwd.starts_with('/') && Self::same_file(pwd, cwd))?;
+        let has_dots = Path::new(pwd)
+            .components()
+            .any(|c| matches!(c, Component::CurDir | Component::ParentDir));
+        (!has_dots).then(|| pwd.to_string())
+    }
+
+    /// Whether a directory may be entered, as `chdir` requires search permission.
+    pub fn is_searchable(dir: &Path) -> bool {
+        let Ok(path) = CString::new(dir.as_os_str().as_bytes()) else {
+            return false;
+        };
+        // SAFETY: `path` is a valid C string for the duration of the call.
+        unsafe { libc::access(path.as_ptr(), libc::X_OK) == 0 }
+    }
+
+    /// Resolves `.` and `..` in an absolute path textually, without following
+    /// symlinks, so `/a/link/..` becomes `/a`.
+    pub fn normalize_logical(path: &str) -> String {
+        let mut parts: Vec<&str> = Vec::new();
+        for part in path.split('/') {
+            match part {
+                "" | "." => {}
+                ".." => {
+                    parts.pop();
+                }
+                _ => parts.push(part),
+            }
+        }
+        format!("/{}", parts.join("/"))
+    }
+
+    /// Whether two paths name the same file once symlinks are followed.
+    pub fn same_file(a: &str, b: &str) -> bool {
+        match (fs::metadata(a), fs::metadata(b)) {
+            (Ok(a), Ok(b)) => a.dev() == b.dev() && a.ino() == b.ino(),
+            _ => false,
+        }
+    }
+
+    /// Corrects small typos in the components of `path` that do not exist, the way
+    /// bash's `cdspell` does: each one may have two characters transposed, or one
+    /// character missing, extra or wrong. A relative `path` is looked up in `cwd`.
+    /// Returns `None` when a component cannot be corrected or nothing needed
+    /// correcting.
+    pub fn correct_spelling(path: &str, cwd: &str) -> Option<String> {
+        let cwd = Path::new(cwd);
+        let mut corrected = if path.starts_with('/') {
+            "/".to_string()
+        } else {
+            String::new()
+        };
+        let mut changed = false;
+
+        for component in path.split('/').filter(|c| !c.is_empty()) {
+            let candidate = format!("{}{}", corrected, component);
+            if component == "." || component == ".." || cwd.join(&candidate).exists() {
+                corrected = format!("{}/", candidate);
+                continue;
+            }
+
+            let parent = if corrected.is_empty() {
+                "."
+            } else {
+                &corrected
+            };
+            let best = fs::read_dir(cwd.join(parent))
+                .ok()?
+                .flatten()
+                .filter_map(|entry| entry.file_name().into_string().ok())
+                .map(|name| (Self::spelling_distance(component, &name), name))
+                .filter(|(distance, _)| *distance <= 2)
+                .min()?;
+            corrected = format!("{}{}/", corrected, best.1);
+            changed = true;
+        }
+
+        if !changed {
+            return None;
+        }
+        if corrected.len() > 1 && !path.ends_with('/') {
+            corrected.pop();
+        }
+        Some(corrected)
+    }
+
+    /// 0 for identical names, 1 for two adjacent characters transposed, 2 for one
+    /// character missing, extra or wrong, and 3 for anything else.
+    fn spelling_distance(typed: &str, name: &str) -> u8 {
+        let a: Vec<char> = typed.chars().collect();
+        let b: Vec<char> = name.chars().collect();
+        if a == b {
+            return 0;
+        }
+
+        if a.len() == b.len() {
+            let differences: Vec<usize> = (0..a.len()).filter(|&i| a[i] != b[i]).collect();
+            return match differences.as_slice() {
+                [_] => 2,
+                [i, j] if *j == i + 1 && a[*i] == b[*j] && a[*j] == b[*i] => 1,
+                _ => 3,
+            };
+        }
+
+        let (shorter, longer) = if a.len() < b.len() {
+            (&a, &b)
+        } else {
+            (&b, &a)
+        };
+        if longer.len() != shorter.len() + 1 {
+            return 3;
+        }
+        let split = (0..shorter.len())
+            .find(|&i| shorter[i] != longer[i])
+            .unwrap_or(shorter.len());
+        if shorter[split..] == longer[split + 1..] {
+            2
+        } else {
+            3
+        }
+    }
+
     /// Checks if a path exists.
     pub fn path_exists(path: &str) -> bool {
         Path::new(path).exists()
     }
-
-    /// Changes the current working directory.
-    pub fn change_dir(path: &str) -> std::io::Result<()> {
-        env::set_current_dir(path)
-    }
 }
@@ -1,14 +1,44 @@
 use codecrafters_shell::{
-    commands::{history_state::HistoryState, registry::CommandRegistry},
+    auto_complete::completion_spec::CompletionState,
+    commands::{
+        alias_state::AliasState, command_hash::CommandHashTable, function_state::FunctionState,
+        history_state::HistoryState, plugin_state::PluginState, registry::CommandRegistry,
+        shell_state::ShellState,
+    },
     shell::Shell,
 };
 use std::sync::{Arc, Mutex};
 
 fn main() -> anyhow::Result<()> {
     let state = Arc::new(Mutex::new(HistoryState::default()));
-    let registry = CommandRegistry::new(state.clone());
-    let mut shell = Shell::new(registry.handlers(), state);
-    shell.run()?;
-
-    Ok(())
+    let mut shell_state = ShellState::from_process();
+    shell_state.options.set_interactive();
+    shell_state.traps.handle_signals();
+    let shell_state = Arc::new(Mutex::new(shell_state));
+    let functions = Arc::new(Mutex::new(FunctionState::default()));
+    let completions = Arc::new(Mutex::new(CompletionState::default()));
+    let hash = Arc::new(Mutex::new(CommandHashTable::default()));
+    let aliases = Arc::new(Mutex::new(AliasState::default()));
+    let plugins = Arc::new(Mutex::new(PluginState::default()));
+    let registry = CommandRegistry::new(
+        state.clone(),
+        functions.clone(),
+        completions.clone(),
+        hash.clone(),
+        aliases.clone(),
+        plugins.clone(),
+    );
+    let mut shell = Shell::new(
+        registry.handlers(),
+        registry.builtins(),
+        state,
+        shell_state,
+        functions,
+        completions,
+        hash,
+        aliases,
+        plugins,
+    );
+    let status = shell.run()?;
+    std::process::exit(status);
 }
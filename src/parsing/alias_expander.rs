@@ -0,0 +1,98 @@
+use crate::commands::alias_state::AliasState;
+use crate::parsing::expander::Expander;
+use std::collections::HashSet;
+
+/// Characters that end an unquoted word.
+const WORD_BREAKS: [char; 11] = [' ', '\t', '\n', ';', '|', '&', '(', ')', '<', '>', '`'];
+
+/// Replaces aliases in command position before a line is split into statements.
+///
+/// A word is in command position at the start of the input, after `;`, `|`, `&`,
+/// `(`, a newline or a `{` word, and after an alias whose value ends in a blank.
+/// Quoted or escaped words are never expanded, and command substitutions are left
+/// alone since they are expanded when they run. An alias is not expanded again
+/// inside its own value.
+pub struct AliasExpander<'a> {
+    aliases: &'a AliasState,
+}
+
+impl<'a> AliasExpander<'a> {
+    pub fn new(aliases: &'a AliasState) -> Self {
+        Self { aliases }
+    }
+
+    pub fn expand(&self, input: &str) -> String {
+        self.expand_excluding(input, &mut HashSet::new())
+    }
+
+    fn expand_excluding(&self, input: &str, active: &mut HashSet<String>) -> String {
+        let chars: Vec<char> = input.chars().collect();
+        let mut output = String::new();
+        let mut command_position = true;
+        let mut i = 0;
+
+        while i < chars.len() {
+            let c = chars[i];
+            match c {
+                ' ' | '\t' => {
+                    output.push(c);
+                    i += 1;
+                }
+                ';' | '\n' | '|' | '&' | '(' => {
+                    output.push(c);
+                    command_position = true;
+                    i += 1;
+                }
+                '\'' | '"' | '`' => {
+                    let end = chars[i + 1..]
+                        .iter()
+                        .position(|&q| q == c)
+                        .map_or(chars.len(), |p| i + p + 2);
+                    output.extend(&chars[i..end]);
+                    command_position = false;
+                    i = end;
+                }
+                '\\' => {
+                    output.extend(chars.iter().skip(i).take(2));
+                    command_position = false;
+                    i += 2;
+                }
+                '$' if chars.get(i + 1) == Some(&'(') => {
+                    let end = Expander::find_closing(&chars[i + 1..], '(', ')')
+                        .map_or(chars.len(), |p| i + p + 2);
+                    output.extend(&chars[i..end]);
+                    command_position = false;
+                    i = end;
+                }
+                _ => {
+                    let end = chars[i..]
+                        .iter()
+                        .position(|c| WORD_BREAKS.contains(c) || matches!(c, '\'' | '"' | '\\'))
+                        .map_or(chars.len(), |p| i + p);
+                    // A word running into a quote or escape is partly quoted.
+                    let quoted = chars
+                        .get(end)
+                        .is_some_and(|c| matches!(c, '\'' | '"' | '\\'));
+                    let word: String = chars[i..end].iter().collect();
+                    let end = end.max(i + 1);
+
+                    match self.aliases.get(&word) {
+                        Some(value) if command_position && !quoted && !active.contains(&word) => {
+                            active.insert(word.clone());
+                            output.push_str(&self.expand_excluding(&value, active));
+                            active.remove(&word);
+                            command_position = value.ends_with([' ', '\t']);
+                        }
+                        _ => {
+                            output.extend(&chars[i..end]);
+                            command_position = word == "{";
+                        }
+                    }
+                    i = end;
+                }
+            }
+        }
+
+        output
+    }
+}
@@ -1,32 +1,53 @@
-use crate::commands::supported_command::{HistoryAction, RedirectionKind, SupportedCommand};
+use crate::commands::builtin::BuiltinTable;
+use crate::commands::option_state::OptionState;
+use crate::commands::supported_command::{RedirectionKind, SupportedCommand};
+use crate::commands::variable_state::VariableValue;
+use regex::Regex;
+use std::sync::LazyLock;
+
+/// Matches `NAME=value` words.
+static ASSIGNMENT: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r"(?s)^([A-Za-z_][A-Za-z0-9_]*)=(.*)$").unwrap());
+
+/// Matches a whole `NAME=(elements...)` array assignment.
+static ARRAY_ASSIGNMENT: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r"(?s)^([A-Za-z_][A-Za-z0-9_]*)=\((.*)\)$").unwrap());
 
 pub struct CommandParser;
 
 impl CommandParser {
-    pub fn parse(input: &str) -> SupportedCommand {
+    /// Parses one expanded statement. `options` decides whether `extglob` groups
+    /// such as `@(a|b)` keep their `|`.
+    pub fn parse(input: &str, builtins: &BuiltinTable, options: &OptionState) -> SupportedCommand {
         let input = input.trim();
         let input = input.replace("1>>", ">>").replace("1>", ">");
-        let tokens = Self::lexer(input.as_str());
-        let args = Self::build_arguments(tokens);
+
+        if let Some(caps) = ARRAY_ASSIGNMENT.captures(&input) {
+            return SupportedCommand::Assignment {
+                name: caps[1].to_string(),
+                value: VariableValue::Array(Self::split_words(&caps[2])),
+            };
+        }
+
+        let args = Self::split_words(input.as_str());
 
         if args.is_empty() {
             return SupportedCommand::NoArgument;
         }
 
-        let pipelines: Vec<&str> = input.split('|').collect();
+        let pipelines = Self::split_pipeline(&input, options.extglob);
 
         if pipelines.len() > 1 {
             let mut commands = Vec::new();
 
             for part in pipelines {
-                let tokens = Self::lexer(part.trim());
-                let args = Self::build_arguments(tokens);
+                let args = Self::split_words(part.trim());
 
                 if args.is_empty() {
                     return SupportedCommand::NoArgument;
                 }
 
-                let cmd = Self::build_command(&args);
+                let cmd = Self::build_inner_command(&args, builtins);
                 commands.push(cmd);
             }
 
@@ -37,85 +58,245 @@ impl CommandParser {
 
         if let Some(pos) = args
             .iter()
-            .position(|arg| matches!(arg.as_str(), ">" | "2>" | "2>>" | ">>"))
+            .position(|arg| matches!(arg.as_str(), ">" | "2>" | "2>>" | ">>" | "<"))
+            && pos + 1 < args.len()
         {
-            if pos + 1 < args.len() {
-                let cmd_args = &args[..pos];
-                let output_file = &args[pos + 1];
-
-                let cmd = if cmd_args.is_empty() {
-                    SupportedCommand::NoArgument
-                } else {
-                    Self::build_command(cmd_args)
-                };
-
-                let identifier = &args[pos];
-                let kind = match identifier.as_str() {
-                    ">" => RedirectionKind::OverwriteOnlySuccess,
-                    "2>" => RedirectionKind::OverwriteOnlyError,
-                    "2>>" => RedirectionKind::AppendOnlyError,
-                    ">>" => RedirectionKind::AppendOnlySuccess,
-                    _ => unreachable!(),
-                };
-
-                return SupportedCommand::Redirection {
-                    kind,
-                    inner_cmd: Box::new(cmd),
-                    output_file: output_file.clone(),
-                };
+            let cmd_args = &args[..pos];
+            let output_file = &args[pos + 1];
+
+            let cmd = if cmd_args.is_empty() {
+                SupportedCommand::NoArgument
+            } else {
+                Self::build_inner_command(cmd_args, builtins)
+            };
+            // Assignments before a redirected command apply to all of it.
+            let (assignments, cmd) = match cmd {
+                SupportedCommand::Prefixed {
+                    assignments,
+                    inner_cmd,
+                } => (assignments, *inner_cmd),
+                cmd => (Vec::new(), cmd),
+            };
+
+            let identifier = &args[pos];
+            let kind = match identifier.as_str() {
+                ">" => RedirectionKind::OverwriteOnlySuccess,
+                "2>" => RedirectionKind::OverwriteOnlyError,
+                "2>>" => RedirectionKind::AppendOnlyError,
+                ">>" => RedirectionKind::AppendOnlySuccess,
+                "<" => RedirectionKind::Input,
+                _ => unreachable!(),
+            };
+
+            let redirection = SupportedCommand::Redirection {
+                kind,
+                inner_cmd: Box::new(cmd),
+                output_file: output_file.clone(),
+            };
+            if assignments.is_empty() {
+                return redirection;
             }
+            return SupportedCommand::Prefixed {
+                assignments,
+                inner_cmd: Box::new(redirection),
+            };
         }
 
-        Self::build_command(&args)
+        Self::build_command(&args, builtins)
     }
 
-    fn build_command(args: &[String]) -> SupportedCommand {
-        let cmd = &args[0];
-        let cmd_args = args[1..].to_vec();
+    /// Splits input into words, applying quote removal and backslash escapes.
+    pub fn split_words(input: &str) -> Vec<String> {
+        Self::build_arguments(Self::lexer(input))
+    }
 
-        match cmd.as_str() {
-            "echo" => SupportedCommand::Echo { args: cmd_args },
-            "exit" => SupportedCommand::Exit,
-            "type" => {
-                if cmd_args.is_empty() {
-                    SupportedCommand::NoArgument
-                } else {
-                    let arg = &cmd_args[0];
-                    if SupportedCommand::supported(arg) {
-                        SupportedCommand::TypeCheck { cmd: arg.clone() }
-                    } else {
-                        SupportedCommand::LocateExecutable { cmd: arg.clone() }
+    /// Quotes `word` so that [`split_words`](Self::split_words) reads it back as one
+    /// word, using single quotes only when it has characters that need them.
+    pub fn quote(word: &str) -> String {
+        let plain = |c: char| c.is_alphanumeric() || "_-./:=,+@%^".contains(c);
+        if !word.is_empty() && word.chars().all(plain) {
+            return word.to_string();
+        }
+        format!("'{}'", word.replace('\'', "'\\''"))
+    }
+
+    /// Splits a line into tokens with their byte ranges, keeping quotes and escapes in
+    /// place. Unlike [`split_words`](Self::split_words) this never changes the text,
+    /// so callers such as the highlighter can map tokens back onto the line.
+    pub fn tokenize(input: &str) -> Vec<Token> {
+        let chars: Vec<(usize, char)> = input.char_indices().collect();
+        let at = |i: usize| chars.get(i).map(|&(_, c)| c);
+        let offset = |i: usize| chars.get(i).map_or(input.len(), |&(o, _)| o);
+
+        let mut tokens = Vec::new();
+        let mut i = 0;
+        while let Some(c) = at(i) {
+            if c == ' ' || c == '\t' {
+                i += 1;
+                continue;
+            }
+
+            let start = i;
+            let kind = match c {
+                '#' => {
+                    i = chars.len();
+                    TokenKind::Comment
+                }
+                '|' | '&' | ';' => {
+                    i += 1;
+                    if c != ';' && at(i) == Some(c) {
+                        i += 1;
+                    }
+                    TokenKind::Operator
+                }
+                '>' | '<' => {
+                    i += 1;
+                    if c == '>' && at(i) == Some('>') {
+                        i += 1;
+                    }
+                    TokenKind::Redirection
+                }
+                '1' | '2' if at(i + 1) == Some('>') => {
+                    i += 2;
+                    if at(i) == Some('>') {
+                        i += 1;
+                    }
+                    TokenKind::Redirection
+                }
+                _ => {
+                    i = Self::word_end(&chars, i);
+                    TokenKind::Word
+                }
+            };
+
+            tokens.push(Token {
+                kind,
+                start: offset(start),
+                end: offset(i),
+            });
+        }
+        tokens
+    }
+
+    /// Returns the index just past the word starting at `i`: the first unquoted blank
+    /// or operator character outside a `$(...)` substitution.
+    fn word_end(chars: &[(usize, char)], mut i: usize) -> usize {
+        let mut quote: Option<char> = None;
+        let mut depth = 0;
+
+        while let Some(&(_, c)) = chars.get(i) {
+            match (c, quote) {
+                ('\\', q) if q != Some('\'') => {
+                    i += 2;
+                    continue;
+                }
+                ('$', q) if q != Some('\'') && chars.get(i + 1).map(|&(_, c)| c) == Some('(') => {
+                    depth += 1;
+                    i += 2;
+                    continue;
+                }
+                (')', q) if depth > 0 && q != Some('\'') => depth -= 1,
+                ('\'', None) | ('"', None) => quote = Some(c),
+                (_, Some(q)) if c == q => quote = None,
+                (' ' | '\t' | '|' | '&' | ';' | '<' | '>', None) if depth == 0 => break,
+                _ => {}
+            }
+            i += 1;
+        }
+        i.min(chars.len())
+    }
+
+    /// Splits input on `|` characters that are neither quoted nor escaped, nor with
+    /// `extglob` inside parentheses.
+    fn split_pipeline(input: &str, extglob: bool) -> Vec<String> {
+        let mut parts = Vec::new();
+        let mut current = String::new();
+        let mut quote: Option<char> = None;
+        let mut depth = 0usize;
+        let mut chars = input.chars();
+
+        while let Some(c) = chars.next() {
+            match (c, quote) {
+                ('\\', q) if q != Some('\'') => {
+                    current.push(c);
+                    if let Some(next) = chars.next() {
+                        current.push(next);
                     }
+                    continue;
+                }
+                ('\'', None) | ('"', None) => quote = Some(c),
+                (_, Some(q)) if c == q => quote = None,
+                ('(', None) if extglob => depth += 1,
+                (')', None) if extglob => depth = depth.saturating_sub(1),
+                ('|', None) if depth == 0 => {
+                    parts.push(std::mem::take(&mut current));
+                    continue;
                 }
+                _ => {}
             }
-            "pwd" => SupportedCommand::Pwd,
-            "history" => {
-                let action = if let Some(flag) = cmd_args.get(0) {
-                    match flag.as_str() {
-                        "-r" => {
-                            let path = cmd_args.get(1).cloned().unwrap_or_default();
-                            HistoryAction::Read { path }
-                        }
-                        "-w" => {
-                            let path = cmd_args.get(1).cloned().unwrap_or_default();
-                            HistoryAction::Write { path }
-                        }
-                        "-a" => {
-                            let path = cmd_args.get(1).cloned().unwrap_or_default();
-                            HistoryAction::Append { path }
-                        }
-                        _ => {
-                            let limit = flag.parse::<usize>().ok();
-                            HistoryAction::Display { limit }
-                        }
+            current.push(c);
+        }
+
+        parts.push(current);
+        parts
+    }
+
+    /// Builds a pipeline stage or redirected command. Functions are never called
+    /// there, so `command name args` is simply `name args`.
+    fn build_inner_command(args: &[String], builtins: &BuiltinTable) -> SupportedCommand {
+        match Self::build_command(args, builtins) {
+            SupportedCommand::Builtin { name, args } if name == "command" => {
+                match SupportedCommand::command_operands(&args) {
+                    Some(operands) if !operands.is_empty() => {
+                        Self::build_inner_command(operands, builtins)
                     }
-                } else {
-                    HistoryAction::Display { limit: None }
-                };
-                SupportedCommand::History { action }
+                    _ => SupportedCommand::Builtin { name, args },
+                }
             }
-            "cd" => SupportedCommand::ChangeDir {
-                path: cmd_args.get(0).cloned().unwrap_or_default(),
+            command => command,
+        }
+    }
+
+    /// Builds the command for a list of words: a builtin when `builtins` knows the
+    /// name, an assignment, or an external command. Leading `NAME=value` words
+    /// before a command are assignments for that command only.
+    pub fn build_command(args: &[String], builtins: &BuiltinTable) -> SupportedCommand {
+        let prefix = args
+            .iter()
+            .take_while(|arg| ASSIGNMENT.is_match(arg))
+            .count();
+        if prefix > 0 && (prefix < args.len() || prefix > 1) {
+            let assignments = args[..prefix]
+                .iter()
+                .map(|arg| {
+                    let (name, value) = arg.split_once('=').unwrap();
+                    (name.to_string(), value.to_string())
+                })
+                .collect();
+            let inner_cmd = match &args[prefix..] {
+                [] => SupportedCommand::NoArgument,
+                rest => Self::build_command(rest, builtins),
+            };
+            return SupportedCommand::Prefixed {
+                assignments,
+                inner_cmd: Box::new(inner_cmd),
+            };
+        }
+
+        let cmd = &args[0];
+        let cmd_args = args[1..].to_vec();
+
+        if builtins.contains(cmd) {
+            return SupportedCommand::Builtin {
+                name: cmd.clone(),
+                args: cmd_args,
+            };
+        }
+
+        match ASSIGNMENT.captures(cmd) {
+            Some(caps) if cmd_args.is_empty() => SupportedCommand::Assignment {
+                name: caps[1].to_string(),
+                value: VariableValue::Scalar(caps[2].to_string()),
             },
             _ => SupportedCommand::Unspecified {
                 cmd: cmd.clone(),
@@ -146,28 +327,37 @@ impl CommandParser {
     fn build_arguments(mut stack: Vec<ClassifiedChar>) -> Vec<String> {
         let mut args = Vec::new();
         let mut current = String::new();
+        // Set once the current word has quotes, so `''` and `""` are empty words.
+        let mut quoted = false;
 
         let mut mode: Option<CharType> = None;
 
         while let Some(token) = stack.pop() {
             match token.kind {
                 CharType::SingleQuote => match mode {
-                    None => mode = Some(CharType::SingleQuote),
+                    None => {
+                        mode = Some(CharType::SingleQuote);
+                        quoted = true;
+                    }
                     Some(CharType::SingleQuote) => mode = None,
                     _ => current.push('\''),
                 },
 
                 CharType::DoubleQuote => match mode {
-                    None => mode = Some(CharType::DoubleQuote),
+                    None => {
+                        mode = Some(CharType::DoubleQuote);
+                        quoted = true;
+                    }
                     Some(CharType::DoubleQuote) => mode = None,
                     _ => current.push('"'),
                 },
 
                 CharType::Whitespace => {
                     if mode.is_none() {
-                        if !current.is_empty() {
+                        if !current.is_empty() || quoted {
                             args.push(std::mem::take(&mut current));
                         }
+                        quoted = false;
                     } else {
                         current.push(token.ch);
                     }
@@ -176,7 +366,7 @@ impl CommandParser {
                 CharType::Backslash => match mode {
                     Some(CharType::DoubleQuote) => {
                         if let Some(next) = stack.pop() {
-                            if next.ch == '"' || next.ch == '\\' {
+                            if matches!(next.ch, '"' | '\\' | '$' | '`') {
                                 current.push(next.ch);
                             } else {
                                 current.push('\\');
@@ -208,7 +398,7 @@ impl CommandParser {
             }
         }
 
-        if !current.is_empty() {
+        if !current.is_empty() || quoted {
             args.push(current);
         }
 
@@ -216,6 +406,26 @@ impl CommandParser {
     }
 }
 
+/// The kind of a token found by [`CommandParser::tokenize`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TokenKind {
+    Word,
+    /// `|`, `||`, `&`, `&&` or `;`.
+    Operator,
+    /// `>`, `>>`, `<`, `2>` or `2>>`.
+    Redirection,
+    /// `#` at the start of a word, up to the end of the line.
+    Comment,
+}
+
+/// A token and its byte range in the input.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Token {
+    pub kind: TokenKind,
+    pub start: usize,
+    pub end: usize,
+}
+
 #[derive(Clone, PartialEq, Eq)]
 struct ClassifiedChar {
     ch: char,
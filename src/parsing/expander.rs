@@ -0,0 +1,583 @@
+use crate::commands::shell_state::SharedState;
+use crate::commands::variable_state::VariableState;
+use crate::core::glob::{self, GlobOptions};
+use crate::parsing::command_parser::CommandParser;
+use std::cell::RefCell;
+
+/// The result of expanding a single `$` reference.
+enum Expansion {
+    Text(String),
+    /// `$@` and `${name[@]}` expand to one word per element.
+    Words(Vec<String>),
+}
+
+/// Performs tilde expansion, parameter expansion, command substitution and pathname
+/// expansion on the source text of a statement before it reaches `CommandParser`.
+///
+/// Expanded values are re-quoted so that the parser sees them as literal text:
+/// inside double quotes they stay a single word, unquoted they are split on whitespace.
+pub struct Expander<'a> {
+    state: &'a SharedState,
+    substitute: &'a dyn Fn(&str) -> String,
+    /// `set -u`: expanding an unset variable is an error.
+    nounset: bool,
+    /// The first error, such as an unset variable expanded under `nounset`.
+    error: RefCell<Option<String>>,
+}
+
+impl<'a> Expander<'a> {
+    pub fn new(state: &'a SharedState, substitute: &'a dyn Fn(&str) -> String) -> Self {
+        let nounset = state.lock().unwrap().options.nounset;
+        Self {
+            state,
+            substitute,
+            nounset,
+            error: RefCell::new(None),
+        }
+    }
+
+    /// Expands `input`, failing when `nounset` is on and it refers to an unset variable.
+    pub fn expand(&self, input: &str) -> anyhow::Result<String> {
+        let output = self.expand_parameters(input);
+        if let Some(error) = self.error.take() {
+            anyhow::bail!(error);
+        }
+        let (options, nullglob, cwd) = {
+            let state = self.state.lock().unwrap();
+            if state.options.noglob {
+                return Ok(output);
+            }
+            let options = state.options.glob_options();
+            (options, state.options.nullglob, state.cwd().to_string())
+        };
+        Ok(Self::expand_globs(&output, &cwd, options, nullglob))
+    }
+
+    /// Performs tilde and parameter expansion and command substitution on `input`.
+    fn expand_parameters(&self, input: &str) -> String {
+        let chars: Vec<char> = input.chars().collect();
+        let mut output = String::new();
+        let mut quote: Option<char> = None;
+        // The value of a leading `NAME=value` word is not split into words.
+        let mut in_assignment = Self::is_assignment(input);
+        let mut i = 0;
+
+        while i < chars.len() {
+            let c = chars[i];
+
+            match (c, quote) {
+                (' ' | '\t', None) => {
+                    in_assignment = false;
+                    output.push(c);
+                }
+                ('\\', Some('\'')) => output.push(c),
+                ('\\', _) => {
+                    output.push(c);
+                    if let Some(&next) = chars.get(i + 1) {
+                        output.push(next);
+                        i += 1;
+                    }
+                }
+                ('\'', None) | ('"', None) => {
+                    quote = Some(c);
+                    output.push(c);
+                }
+                (_, Some(q)) if c == q => {
+                    quote = None;
+                    output.push(c);
+                }
+                ('$', q) if q != Some('\'') => {
+                    let (consumed, expansion) = self.expand_dollar(&chars[i + 1..], q.is_some());
+                    match expansion {
+                        // `"$@"` with no words is no word at all, not an empty one.
+                        Some(Expansion::Words(words))
+                            if words.is_empty()
+                                && q.is_some()
+                                && i > 0
+                                && chars[i - 1] == '"'
+                                && chars.get(i + consumed + 1) == Some(&'"') =>
+                        {
+                            output.pop();
+                            quote = None;
+                            i += consumed + 1;
+                        }
+                        Some(expansion) => {
+                            Self::push_expansion(
+                                &mut output,
+                                expansion,
+                                q.is_some(),
+                                in_assignment,
+                            );
+                            i += consumed;
+                        }
+                        None => output.push(c),
+                    }
+                }
+                ('~', None) if i == 0 || Self::starts_word(chars[i - 1], in_assignment) => {
+                    let (consumed, expansion) = self.expand_tilde(&chars[i + 1..]);
+                    match expansion {
+                        // The result is never split into words.
+                        Some(expansion) => {
+                            Self::push_expansion(&mut output, expansion, false, true);
+                            i += consumed;
+                        }
+                        None => output.push(c),
+                    }
+                }
+                _ => output.push(c),
+            }
+            i += 1;
+        }
+        output
+    }
+
+    /// Records `name` as unbound when `nounset` is on and it is not `set`.
+    fn check_set(&self, name: impl FnOnce() -> String, set: bool) {
+        if self.nounset && !set {
+            self.fail(format!("{}: unbound variable", name()));
+        }
+    }
+
+    /// Records an error that fails the expansion, unless there already is one.
+    fn fail(&self, message: String) {
+        self.error.borrow_mut().get_or_insert(message);
+    }
+
+    /// Replaces each word with an unquoted `*`, `?` or `[` by the paths it matches,
+    /// relative to `cwd`. A word that matches nothing is kept as it is unless
+    /// `nullglob` is on, and the leading word of an assignment is always kept.
+    fn expand_globs(input: &str, cwd: &str, options: GlobOptions, nullglob: bool) -> String {
+        let is_separator = |c: char| " \t\n|;&<>()".contains(c);
+        let chars: Vec<char> = input.chars().collect();
+        let mut output = String::new();
+        let mut command_start = true;
+        let mut i = 0;
+
+        while i < chars.len() {
+            if is_separator(chars[i]) {
+                command_start |= matches!(chars[i], '|' | ';' | '&');
+                output.push(chars[i]);
+                i += 1;
+                continue;
+            }
+
+            // Build the word's pattern, escaping everything that was quoted.
+            let start = i;
+            let mut pattern = String::new();
+            let mut quote: Option<char> = None;
+            let push_literal = |pattern: &mut String, c: char| {
+                if matches!(c, '*' | '?' | '[' | ']' | '\\') {
+                    pattern.push('\\');
+                }
+                pattern.push(c);
+            };
+            while i < chars.len() {
+                let c = chars[i];
+                match (c, quote) {
+                    (_, Some(q)) if c == q => quote = None,
+                    ('\\', Some('"')) | ('\\', None) if i + 1 < chars.len() => {
+                        i += 1;
+                        push_literal(&mut pattern, chars[i]);
+                    }
+                    (_, Some(_)) => push_literal(&mut pattern, c),
+                    ('\'' | '"', None) => quote = Some(c),
+                    // An `extglob` group keeps its `|` and parentheses.
+                    ('?' | '*' | '+' | '@' | '!', None)
+                        if options.extglob && chars.get(i + 1) == Some(&'(') =>
+                    {
+                        let end = Self::group_end(&chars, i + 1);
+                        pattern.extend(&chars[i..end]);
+                        i = end;
+                        continue;
+                    }
+                    (_, None) if is_separator(c) => break,
+                    (_, None) => pattern.push(c),
+                }
+                i += 1;
+            }
+
+            let word: String = chars[start..i].iter().collect();
+            let is_assignment = command_start && Self::is_assignment(&word);
+            command_start = false;
+            let is_pattern = glob::has_wildcards(&pattern, options) && !is_assignment;
+            let paths = match is_pattern {
+                true => glob::expand(&pattern, cwd, options),
+                false => Vec::new(),
+            };
+            if paths.is_empty() {
+                if !(is_pattern && nullglob) {
+                    output.push_str(&word);
+                }
+            } else {
+                let paths: Vec<String> = paths.iter().map(|p| CommandParser::quote(p)).collect();
+                output.push_str(&paths.join(" "));
+            }
+        }
+
+        output
+    }
+
+    /// The index just past the `)` closing the parenthesis at `open`, or the end of
+    /// the input when it is not closed.
+    fn group_end(chars: &[char], open: usize) -> usize {
+        let mut depth = 0;
+        let mut i = open;
+        while i < chars.len() {
+            match chars[i] {
+                '\\' => i += 1,
+                '(' => depth += 1,
+                ')' => {
+                    depth -= 1;
+                    if depth == 0 {
+                        return i + 1;
+                    }
+                }
+                _ => {}
+            }
+            i += 1;
+        }
+        chars.len()
+    }
+
+    /// Whether a `~` after `previous` begins a word, or the value of an assignment.
+    fn starts_word(previous: char, in_assignment: bool) -> bool {
+        matches!(previous, ' ' | '\t' | '|') || (in_assignment && previous == '=')
+    }
+
+    /// Expands the prefix following a `~` up to the first `/`: `~` is `HOME`, `~+` is
+    /// `PWD`, `~-` is `OLDPWD`, and `~N`, `~+N` and `~-N` are directory stack entries.
+    /// Anything else, including `~user`, is left alone.
+    fn expand_tilde(&self, rest: &[char]) -> (usize, Option<Expansion>) {
+        let prefix: String = rest
+            .iter()
+            .take_while(|c| !matches!(c, '/' | ' ' | '\t' | '|' | ';' | '&' | '<' | '>'))
+            .collect();
+        if prefix.contains(['\'', '"', '\\', '$']) {
+            return (0, None);
+        }
+
+        let state = self.state.lock().unwrap();
+        let variables = &state.variables;
+        let value = match prefix.as_str() {
+            "" => variables.get("HOME"),
+            "+" => variables.get("PWD"),
+            "-" => variables.get("OLDPWD"),
+            spec => state.directory_stack().get(state.cwd(), spec),
+        };
+        match value {
+            Some(value) => (prefix.chars().count(), Some(Expansion::Text(value))),
+            None => (0, None),
+        }
+    }
+
+    /// Expands the reference following a `$`, returning how many characters it used.
+    /// `quoted` is whether the reference is inside double quotes.
+    fn expand_dollar(&self, rest: &[char], quoted: bool) -> (usize, Option<Expansion>) {
+        match rest.first() {
+            Some('(') => {
+                let Some(end) = Self::find_closing(rest, '(', ')') else {
+                    return (0, None);
+                };
+                let command: String = rest[1..end].iter().collect();
+                let output = (self.substitute)(&command);
+                let output = output.trim_end_matches('\n').to_string();
+                (end + 1, Some(Expansion::Text(output)))
+            }
+            Some('{') => {
+                let Some(end) = Self::find_closing(rest, '{', '}') else {
+                    return (0, None);
+                };
+                let reference: String = rest[1..end].iter().collect();
+                (end + 1, Some(self.expand_braced(&reference, quoted)))
+            }
+            Some(c) if c.is_ascii_alphabetic() || *c == '_' => {
+                let name: String = rest
+                    .iter()
+                    .take_while(|c| c.is_ascii_alphanumeric() || **c == '_')
+                    .collect();
+                let value = self.state.lock().unwrap().variables.get(&name);
+                self.check_set(|| name.clone(), value.is_some());
+                (name.len(), Some(Expansion::Text(value.unwrap_or_default())))
+            }
+            Some(c) if c.is_ascii_digit() => {
+                let index = c.to_digit(10).unwrap() as usize;
+                (1, Some(Expansion::Text(self.positional(index))))
+            }
+            Some('#') => {
+                let count = self.state.lock().unwrap().positional().len();
+                (1, Some(Expansion::Text(count.to_string())))
+            }
+            Some('?') => {
+                let status = self.state.lock().unwrap().variables.last_status();
+                (1, Some(Expansion::Text(status.to_string())))
+            }
+            Some('$') => (1, Some(Expansion::Text(std::process::id().to_string()))),
+            Some('-') => {
+                let flags = self.state.lock().unwrap().options.flags();
+                (1, Some(Expansion::Text(flags)))
+            }
+            Some('@') | Some('*') => {
+                let args = self.state.lock().unwrap().positional().to_vec();
+                (1, Some(Expansion::Words(args)))
+            }
+            _ => (0, None),
+        }
+    }
+
+    /// Expands `${name}`, `${#name}`, `${name[index]}` and `${name[@]}`, and the
+    /// `${name:-word}` family of defaults.
+    fn expand_braced(&self, reference: &str, quoted: bool) -> Expansion {
+        if let Some((parameter, operator, word)) = Self::split_operator(reference) {
+            let word = match quoted {
+                true => format!("\"{}\"", word),
+                false => word.to_string(),
+            };
+            return Expansion::Text(self.expand_operator(parameter, operator, &word));
+        }
+
+        let state = self.state.lock().unwrap();
+        let variables = &state.variables;
+
+        if let Some(name) = reference.strip_prefix('#') {
+            let length = match name.strip_suffix("[@]").or(name.strip_suffix("[*]")) {
+                Some(array) => variables.get_array(array).len(),
+                None => {
+                    self.check_set(|| name.to_string(), variables.is_set(name));
+                    variables.get(name).unwrap_or_default().chars().count()
+                }
+            };
+            return Expansion::Text(length.to_string());
+        }
+
+        let Some((name, index)) = reference.strip_suffix(']').and_then(|r| r.split_once('['))
+        else {
+            if let Ok(index) = reference.parse::<usize>() {
+                drop(state);
+                return Expansion::Text(self.positional(index));
+            }
+            let value = variables.get(reference);
+            self.check_set(|| reference.to_string(), value.is_some());
+            return Expansion::Text(value.unwrap_or_default());
+        };
+
+        match index {
+            "@" | "*" => Expansion::Words(variables.get_array(name)),
+            _ => {
+                let index = index.trim().trim_start_matches('$');
+                let index = index.parse::<usize>().ok().or_else(|| {
+                    variables
+                        .get(index)
+                        .and_then(|value| value.trim().parse::<usize>().ok())
+                });
+                let value = index.and_then(|i| variables.get_element(name, i));
+                self.check_set(|| reference.to_string(), value.is_some());
+                Expansion::Text(value.unwrap_or_default())
+            }
+        }
+    }
+
+    /// Splits a reference such as `name:-word` into the parameter, the operator and
+    /// the word. The operators are `-`, `+`, `=` and `?`, each with an optional `:`.
+    fn split_operator(reference: &str) -> Option<(&str, &str, &str)> {
+        let mut end = reference
+            .chars()
+            .take_while(|c| c.is_ascii_alphanumeric() || *c == '_')
+            .count();
+        if end == 0 {
+            return None;
+        }
+        if reference[end..].starts_with('[') {
+            end += reference[end..].find(']')? + 1;
+        }
+        let (parameter, rest) = reference.split_at(end);
+        let colon = usize::from(rest.starts_with(':'));
+        if !rest[colon..].starts_with(['-', '+', '=', '?']) {
+            return None;
+        }
+        Some((parameter, &rest[..colon + 1], &rest[colon + 1..]))
+    }
+
+    /// Expands `${parameter<operator>word}`. `-` uses the word when the parameter is
+    /// unset, `+` when it is set, `=` also assigns it, and `?` fails with the word as
+    /// the message. With a `:`, an empty value counts as unset. These references
+    /// test the parameter themselves, so `nounset` does not apply to them.
+    fn expand_operator(&self, parameter: &str, operator: &str, word: &str) -> String {
+        let value = self.lookup(parameter);
+        let set = match operator.len() {
+            2 => value.as_deref().is_some_and(|value| !value.is_empty()),
+            _ => value.is_some(),
+        };
+
+        match (operator.trim_start_matches(':'), set) {
+            ("-", false) | ("+", true) => self.expand_word(word),
+            ("+", false) => String::new(),
+            ("=", false) => {
+                let word = self.expand_word(word);
+                if VariableState::is_valid_name(parameter) {
+                    let mut state = self.state.lock().unwrap();
+                    state.variables.set_scalar(parameter, word.as_str());
+                } else {
+                    self.fail(format!("${}: cannot assign in this way", parameter));
+                }
+                word
+            }
+            ("?", false) => {
+                let message = match self.expand_word(word) {
+                    message if message.is_empty() => "parameter null or not set".to_string(),
+                    message => message,
+                };
+                self.fail(format!("{}: {}", parameter, message));
+                String::new()
+            }
+            _ => value.unwrap_or_default(),
+        }
+    }
+
+    /// The value of a positional parameter, variable or array element, or `None`
+    /// when it is unset. `name[@]` is set when the array has elements.
+    fn lookup(&self, parameter: &str) -> Option<String> {
+        if let Ok(index) = parameter.parse::<usize>() {
+            let state = self.state.lock().unwrap();
+            return match index {
+                0 => std::env::args().next(),
+                _ => state.positional().get(index - 1).cloned(),
+            };
+        }
+
+        let state = self.state.lock().unwrap();
+        let variables = &state.variables;
+        let Some((name, index)) = parameter.strip_suffix(']').and_then(|p| p.split_once('['))
+        else {
+            return variables.get(parameter);
+        };
+        match index {
+            "@" | "*" => Some(variables.get_array(name).join(" ")).filter(|v| !v.is_empty()),
+            index => {
+                let index = index.trim().parse::<usize>().ok()?;
+                variables.get_element(name, index)
+            }
+        }
+    }
+
+    /// Expands the word of a `${name:-word}` reference to its value, with quotes
+    /// removed. The word of a reference inside double quotes comes in quoted, so its
+    /// single quotes and `~` stay as they are.
+    fn expand_word(&self, word: &str) -> String {
+        let expanded = self.expand_parameters(word);
+        let mut value = String::new();
+        let mut quote: Option<char> = None;
+        let mut chars = expanded.chars().peekable();
+        while let Some(c) = chars.next() {
+            match (c, quote) {
+                ('\\', None) => value.extend(chars.next()),
+                ('\\', Some('"')) if matches!(chars.peek(), Some('\\' | '"' | '$' | '`')) => {
+                    value.extend(chars.next())
+                }
+                ('\'', None) | ('"', None) => quote = Some(c),
+                (_, Some(q)) if c == q => quote = None,
+                _ => value.push(c),
+            }
+        }
+        value
+    }
+
+    fn positional(&self, index: usize) -> String {
+        if index == 0 {
+            return std::env::args().next().unwrap_or_default();
+        }
+        let value = self
+            .state
+            .lock()
+            .unwrap()
+            .positional()
+            .get(index - 1)
+            .cloned();
+        self.check_set(|| format!("${}", index), value.is_some());
+        value.unwrap_or_default()
+    }
+
+    fn is_assignment(input: &str) -> bool {
+        let name_len = input
+            .chars()
+            .take_while(|c| c.is_ascii_alphanumeric() || *c == '_')
+            .count();
+        name_len > 0
+            && !input.starts_with(|c: char| c.is_ascii_digit())
+            && input[name_len..].starts_with('=')
+            && !input[name_len + 1..].starts_with('(')
+    }
+
+    fn push_expansion(
+        output: &mut String,
+        expansion: Expansion,
+        quoted: bool,
+        in_assignment: bool,
+    ) {
+        match expansion {
+            Expansion::Text(text) if quoted => output.push_str(&Self::escape_quoted(&text)),
+            Expansion::Text(text) if in_assignment => {
+                output.push('"');
+                output.push_str(&Self::escape_quoted(&text));
+                output.push('"');
+            }
+            Expansion::Text(text) => output.push_str(&Self::escape_unquoted(&text)),
+            Expansion::Words(words) if quoted => {
+                let words: Vec<String> = words.iter().map(|w| Self::escape_quoted(w)).collect();
+                output.push_str(&words.join("\" \""));
+            }
+            Expansion::Words(words) => {
+                let words: Vec<String> = words.iter().map(|w| Self::escape_unquoted(w)).collect();
+                output.push_str(&words.join(" "));
+            }
+        }
+    }
+
+    /// Escapes a value placed inside double quotes.
+    fn escape_quoted(value: &str) -> String {
+        value.replace('\\', "\\\\").replace('"', "\\\"")
+    }
+
+    /// Escapes a value placed outside quotes. Whitespace is kept so the value is
+    /// split into words; everything the parser treats specially is escaped.
+    fn escape_unquoted(value: &str) -> String {
+        let mut escaped = String::new();
+        for c in value.chars() {
+            match c {
+                ' ' | '\t' | '\n' => escaped.push(' '),
+                '\\' | '\'' | '"' | '|' | '>' | '<' | ';' | '&' | '(' | ')' => {
+                    escaped.push('\\');
+                    escaped.push(c);
+                }
+                _ => escaped.push(c),
+            }
+        }
+        escaped
+    }
+
+    /// Finds the index of the bracket closing the one at `chars[0]`, skipping quotes.
+    pub fn find_closing(chars: &[char], open: char, close: char) -> Option<usize> {
+        let mut depth = 0;
+        let mut quote: Option<char> = None;
+        let mut i = 0;
+
+        while i < chars.len() {
+            let c = chars[i];
+            match (c, quote) {
+                ('\\', q) if q != Some('\'') => i += 1,
+                ('\'', None) | ('"', None) => quote = Some(c),
+                (_, Some(q)) if c == q => quote = None,
+                (_, None) if c == open => depth += 1,
+                (_, None) if c == close => {
+                    depth -= 1;
+                    if depth == 0 {
+                        return Some(i);
+                    }
+                }
+                _ => {}
+            }
+            i += 1;
+        }
+
+        None
+    }
+}
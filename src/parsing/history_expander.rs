@@ -0,0 +1,461 @@
+use anyhow::{Result, bail};
+
+/// The outcome of history expansion on one input line.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct HistoryExpansion {
+    pub line: String,
+    /// True when at least one `!` or `^` reference was replaced.
+    pub changed: bool,
+    /// Set by the `:p` modifier: show the line and add it to history without running it.
+    pub print_only: bool,
+}
+
+/// Performs csh/bash-style history expansion (`!!`, `!n`, `!-n`, `!prefix`, `!?text?`,
+/// `!$`, `!*`, `^old^new`) with word designators and modifiers, before the line is parsed.
+pub struct HistoryExpander<'a> {
+    history: &'a [String],
+    /// History number of the entry before `history[0]`, once older entries were trimmed.
+    base: usize,
+    /// The last `:s` substitution, reused by `:&`.
+    last_substitution: Option<(String, String)>,
+}
+
+impl<'a> HistoryExpander<'a> {
+    pub fn new(history: &'a [String], base: usize) -> Self {
+        Self {
+            history,
+            base,
+            last_substitution: None,
+        }
+    }
+
+    pub fn expand(&mut self, line: &str) -> Result<HistoryExpansion> {
+        let mut expansion = HistoryExpansion {
+            line: String::new(),
+            changed: false,
+            print_only: false,
+        };
+
+        if let Some(rest) = line.strip_prefix('^') {
+            let (mut old, mut new, tail) = Self::split_quick_substitution(rest);
+            let event = self.event(1)?;
+            // Errors show the substitution as far as it was read, as bash does.
+            let shown = match rest.matches('^').count() {
+                0 | 1 => line.to_string(),
+                _ => format!("^{}^{}^", old, new),
+            };
+            // An empty `old` repeats the last substitution.
+            if old.is_empty() {
+                let Some((last_old, last_new)) = self.last_substitution.clone() else {
+                    bail!(":s{}: no previous substitution", shown);
+                };
+                old = last_old;
+                if !rest.contains('^') {
+                    new = last_new;
+                }
+            }
+            let Some(pos) = event.find(&old) else {
+                bail!(":s{}: substitution failed", shown);
+            };
+            expansion.line = format!(
+                "{}{}{}{}",
+                &event[..pos],
+                new,
+                &event[pos + old.len()..],
+                tail
+            );
+            expansion.changed = true;
+            self.last_substitution = Some((old, new));
+            return Ok(expansion);
+        }
+
+        let chars: Vec<char> = line.chars().collect();
+        let mut quote: Option<char> = None;
+        let mut i = 0;
+
+        while i < chars.len() {
+            let c = chars[i];
+            match (c, quote) {
+                ('\\', q) if q != Some('\'') && chars.get(i + 1) == Some(&'!') => {
+                    expansion.line.push_str("\\!");
+                    i += 2;
+                    continue;
+                }
+                ('\'', None) | ('"', None) => quote = Some(c),
+                (_, Some(q)) if c == q => quote = None,
+                ('!', q) if q != Some('\'') && Self::starts_reference(chars.get(i + 1)) => {
+                    let current: String = expansion.line.clone();
+                    let (consumed, text) =
+                        self.expand_reference(&chars[i + 1..], &current, &mut expansion)?;
+                    expansion.line.push_str(&text);
+                    expansion.changed = true;
+                    i += consumed + 1;
+                    continue;
+                }
+                _ => {}
+            }
+            expansion.line.push(c);
+            i += 1;
+        }
+
+        Ok(expansion)
+    }
+
+    /// `!` followed by a blank, `=`, `(` or the end of the line is left alone.
+    fn starts_reference(next: Option<&char>) -> bool {
+        match next {
+            None | Some(' ' | '\t' | '\n' | '=' | '(' | '"') => false,
+            Some(_) => true,
+        }
+    }
+
+    /// Expands one reference following a `!`, returning how many characters it used.
+    fn expand_reference(
+        &mut self,
+        rest: &[char],
+        current_line: &str,
+        expansion: &mut HistoryExpansion,
+    ) -> Result<(usize, String)> {
+        let (mut consumed, event) = self.parse_event(rest, current_line)?;
+        let mut selected = event.clone();
+
+        // The `:` before a word designator may be omitted when it starts with `^$*-%`.
+        let has_designator = match rest.get(consumed) {
+            Some(':') => rest
+                .get(consumed + 1)
+                .is_some_and(|c| c.is_ascii_digit() || matches!(c, '^' | '$' | '*' | '-' | '%')),
+            Some('^' | '$' | '*' | '-' | '%') => true,
+            _ => false,
+        };
+        if has_designator {
+            if rest[consumed] == ':' {
+                consumed += 1;
+            }
+            let words = Self::split_words(&event);
+            let (used, text) = Self::select_words(&rest[consumed..], &words, &event)?;
+            consumed += used;
+            selected = text;
+        }
+
+        while rest.get(consumed) == Some(&':') {
+            let (used, text) = self.apply_modifier(&rest[consumed + 1..], &selected, expansion)?;
+            consumed += used + 1;
+            selected = text;
+        }
+
+        Ok((consumed, selected))
+    }
+
+    /// Parses an event designator, returning the characters used and the event text.
+    /// `!$`, `!^`, `!*` and `!:n` refer to the previous command without consuming anything.
+    fn parse_event(&self, rest: &[char], current_line: &str) -> Result<(usize, String)> {
+        match rest.first() {
+            Some('!') => Ok((1, self.event(1)?)),
+            Some('#') => Ok((1, current_line.to_string())),
+            Some('$' | '^' | '*' | ':') => Ok((0, self.event(1)?)),
+            Some('-') => {
+                let digits: String = rest[1..]
+                    .iter()
+                    .take_while(|c| c.is_ascii_digit())
+                    .collect();
+                let Ok(offset) = digits.parse::<usize>() else {
+                    bail!("!-: event not found");
+                };
+                Ok((digits.len() + 1, self.event(offset)?))
+            }
+            Some(c) if c.is_ascii_digit() => {
+                let digits: String = rest.iter().take_while(|c| c.is_ascii_digit()).collect();
+                let number: usize = digits.parse().unwrap_or(0);
+                let Some(command) = number
+                    .checked_sub(self.base + 1)
+                    .and_then(|i| self.history.get(i))
+                else {
+                    bail!("!{}: event not found", digits);
+                };
+                Ok((digits.len(), command.clone()))
+            }
+            Some('?') => {
+                let text: String = rest[1..]
+                    .iter()
+                    .take_while(|c| **c != '?' && **c != '\n')
+                    .collect();
+                let closed = rest.get(text.chars().count() + 1) == Some(&'?');
+                let used = text.chars().count() + 1 + usize::from(closed);
+                match self.history.iter().rev().find(|c| c.contains(&text)) {
+                    Some(command) => Ok((used, command.clone())),
+                    None => bail!("!?{}: event not found", text),
+                }
+            }
+            _ => {
+                let prefix: String = rest
+                    .iter()
+                    .take_while(|c| {
+                        !c.is_whitespace() && !matches!(c, ':' | '"' | '\'' | ';' | '|' | '&')
+                    })
+                    .collect();
+                match self.history.iter().rev().find(|c| c.starts_with(&prefix)) {
+                    Some(command) => Ok((prefix.chars().count(), command.clone())),
+                    None => bail!("!{}: event not found", prefix),
+                }
+            }
+        }
+    }
+
+    /// Returns the command `offset` entries back from the end of history.
+    fn event(&self, offset: usize) -> Result<String> {
+        offset
+            .checked_sub(1)
+            .and_then(|back| self.history.len().checked_sub(back + 1))
+            .and_then(|i| self.history.get(i))
+            .cloned()
+            .ok_or_else(|| match offset {
+                1 => anyhow::anyhow!("!!: event not found"),
+                _ => anyhow::anyhow!("!-{}: event not found", offset),
+            })
+    }
+
+    /// Parses a word designator (`0`, `n`, `^`, `$`, `*`, `x-y`, `x-`, `x*`, `%`).
+    fn select_words(rest: &[char], words: &[String], event: &str) -> Result<(usize, String)> {
+        let last = words.len().saturating_sub(1);
+        let number = |start: usize| -> (usize, Option<usize>) {
+            let digits: String = rest[start..]
+                .iter()
+                .take_while(|c| c.is_ascii_digit())
+                .collect();
+            (digits.len(), digits.parse().ok())
+        };
+
+        let (mut used, first) = match rest.first() {
+            Some('^') => (1, 1),
+            Some('$') => (1, last),
+            Some('*') => return Ok((1, words.get(1..).map(|w| w.join(" ")).unwrap_or_default())),
+            Some('%') => return Ok((1, String::new())),
+            Some('-') => (0, 0),
+            _ => match number(0) {
+                (len, Some(n)) => (len, n),
+                _ => bail!("{}: bad word specifier", event),
+            },
+        };
+
+        let mut end = first;
+        match rest.get(used) {
+            Some('*') => {
+                used += 1;
+                end = last;
+                if first > last {
+                    return Ok((used, String::new()));
+                }
+            }
+            Some('-') => {
+                used += 1;
+                end = match rest.get(used) {
+                    Some('$') => {
+                        used += 1;
+                        last
+                    }
+                    Some(c) if c.is_ascii_digit() => {
+                        let (len, n) = number(used);
+                        used += len;
+                        n.unwrap_or(last)
+                    }
+                    _ => last.saturating_sub(1),
+                };
+            }
+            _ => {}
+        }
+
+        if first > end || end > last || words.is_empty() {
+            bail!("{}: bad word specifier", event);
+        }
+        Ok((used, words[first..=end].join(" ")))
+    }
+
+    fn apply_modifier(
+        &mut self,
+        rest: &[char],
+        text: &str,
+        expansion: &mut HistoryExpansion,
+    ) -> Result<(usize, String)> {
+        match rest.first() {
+            Some('h') => Ok((
+                1,
+                match text.rfind('/') {
+                    Some(0) => "/".to_string(),
+                    Some(i) => text[..i].to_string(),
+                    None => text.to_string(),
+                },
+            )),
+            Some('t') => Ok((1, text.rsplit('/').next().unwrap_or(text).to_string())),
+            // Like bash, `:r` and `:e` split at the last `.` anywhere in the text, and
+            // leave text without one alone.
+            Some('r') => Ok((
+                1,
+                match text.rfind('.') {
+                    Some(i) => text[..i].to_string(),
+                    None => text.to_string(),
+                },
+            )),
+            Some('e') => Ok((
+                1,
+                match text.rfind('.') {
+                    Some(i) => text[i..].to_string(),
+                    None => text.to_string(),
+                },
+            )),
+            Some('p') => {
+                expansion.print_only = true;
+                Ok((1, text.to_string()))
+            }
+            Some('q') => Ok((1, format!("'{}'", text.replace('\'', "'\\''")))),
+            Some('&') => self.substitute(text, false).map(|t| (1, t)),
+            Some('g') if rest.get(1) == Some(&'&') => self.substitute(text, true).map(|t| (2, t)),
+            Some('g') if rest.get(1) == Some(&'s') => {
+                let (used, old, new) = Self::parse_substitution(&rest[2..]);
+                self.last_substitution = Some((old, new));
+                self.substitute(text, true).map(|t| (used + 2, t))
+            }
+            Some('s') => {
+                let (used, old, new) = Self::parse_substitution(&rest[1..]);
+                self.last_substitution = Some((old, new));
+                self.substitute(text, false).map(|t| (used + 1, t))
+            }
+            Some(c) => bail!("{}: unrecognized history modifier", c),
+            None => bail!("unrecognized history modifier"),
+        }
+    }
+
+    /// Parses `/old/new/` with any delimiter; the final delimiter is optional.
+    /// An `&` in the replacement stands for the old text.
+    fn parse_substitution(rest: &[char]) -> (usize, String, String) {
+        let Some(&delimiter) = rest.first() else {
+            return (0, String::new(), String::new());
+        };
+        let mut parts = vec![String::new()];
+        let mut i = 1;
+        while i < rest.len() && parts.len() <= 2 {
+            match rest[i] {
+                '\\' if rest.get(i + 1) == Some(&delimiter) => {
+                    parts.last_mut().unwrap().push(delimiter);
+                    i += 1;
+                }
+                c if c == delimiter => parts.push(String::new()),
+                c => parts.last_mut().unwrap().push(c),
+            }
+            i += 1;
+        }
+        let old = parts[0].clone();
+        let new = parts.get(1).cloned().unwrap_or_default().replace('&', &old);
+        (i, old, new)
+    }
+
+    fn substitute(&self, text: &str, global: bool) -> Result<String> {
+        let Some((ref old, ref new)) = self.last_substitution else {
+            bail!("no previous substitution");
+        };
+        if old.is_empty() || !text.contains(old.as_str()) {
+            bail!(":s/{}/{}/: substitution failed", old, new);
+        }
+        Ok(if global {
+            text.replace(old.as_str(), new)
+        } else {
+            text.replacen(old.as_str(), new, 1)
+        })
+    }
+
+    /// Parses `old^new[^tail]` of a quick substitution.
+    fn split_quick_substitution(rest: &str) -> (String, String, String) {
+        let mut parts = rest.splitn(3, '^');
+        let old = parts.next().unwrap_or_default().to_string();
+        let new = parts.next().unwrap_or_default().to_string();
+        let tail = parts.next().unwrap_or_default().to_string();
+        (old, new, tail)
+    }
+
+    /// Splits a command into words, keeping quotes so the words can be spliced back.
+    fn split_words(command: &str) -> Vec<String> {
+        let mut words = Vec::new();
+        let mut current = String::new();
+        let mut quote: Option<char> = None;
+        let mut chars = command.chars();
+
+        while let Some(c) = chars.next() {
+            match (c, quote) {
+                ('\\', q) if q != Some('\'') => {
+                    current.push(c);
+                    if let Some(next) = chars.next() {
+                        current.push(next);
+                    }
+                    continue;
+                }
+                ('\'', None) | ('"', None) => quote = Some(c),
+                (_, Some(q)) if c == q => quote = None,
+                (' ' | '\t', None) => {
+                    if !current.is_empty() {
+                        words.push(std::mem::take(&mut current));
+                    }
+                    continue;
+                }
+                _ => {}
+            }
+            current.push(c);
+        }
+
+        if !current.is_empty() {
+            words.push(current);
+        }
+        words
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn expand(history: &[&str], line: &str) -> Result<String> {
+        let history: Vec<String> = history.iter().map(|c| c.to_string()).collect();
+        HistoryExpander::new(&history, 0)
+            .expand(line)
+            .map(|expansion| expansion.line)
+    }
+
+    fn error(history: &[&str], line: &str) -> String {
+        expand(history, line).unwrap_err().to_string()
+    }
+
+    #[test]
+    fn quick_substitution_replaces_the_first_match() {
+        assert_eq!(expand(&["echo hello"], "^l^L").unwrap(), "echo heLlo");
+        assert_eq!(expand(&["echo hello"], "^l").unwrap(), "echo helo");
+        assert_eq!(expand(&["echo hello"], "^l^L^ x").unwrap(), "echo heLlo x");
+    }
+
+    #[test]
+    fn quick_substitution_without_old_text_fails() {
+        assert_eq!(error(&["echo hello"], "^"), ":s^: no previous substitution");
+        assert_eq!(
+            error(&["echo hello"], "^^x"),
+            ":s^^x: no previous substitution"
+        );
+    }
+
+    #[test]
+    fn quick_substitution_reports_a_missing_match() {
+        assert_eq!(error(&["echo hello"], "^xx"), ":s^xx: substitution failed");
+        assert_eq!(
+            error(&["echo hello"], "^q^w^tail"),
+            ":s^q^w^: substitution failed"
+        );
+    }
+
+    #[test]
+    fn suffix_modifiers_split_at_the_last_dot() {
+        assert_eq!(expand(&["echo hello"], "!!:e").unwrap(), "echo hello");
+        assert_eq!(expand(&["echo hello"], "!$:e").unwrap(), "hello");
+        assert_eq!(expand(&["echo dir/a.txt"], "!$:e").unwrap(), ".txt");
+        assert_eq!(expand(&["echo a.txt b"], "!!:e").unwrap(), ".txt b");
+        assert_eq!(expand(&["echo /x.y/z"], "!$:e").unwrap(), ".y/z");
+        assert_eq!(expand(&["echo dir/a.txt"], "!$:r").unwrap(), "dir/a");
+        assert_eq!(expand(&["echo /x.y/z"], "!$:r").unwrap(), "/x");
+        assert_eq!(expand(&["echo hello"], "!$:r").unwrap(), "hello");
+    }
+}
@@ -1 +1,5 @@
+pub mod alias_expander;
 pub mod command_parser;
+pub mod expander;
+pub mod history_expander;
+pub mod script_parser;
@@ -0,0 +1,373 @@
+use anyhow::{Result, bail};
+use regex::Regex;
+use std::sync::LazyLock;
+
+/// Matches `name() { body }` and `function name [()] { body }`.
+static FUNCTION_DEFINITION: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(
+        r"(?s)^(?:function\s+([A-Za-z_][\w:.-]*)\s*(?:\(\s*\))?|([A-Za-z_][\w:.-]*)\s*\(\s*\))\s*\{(.*)\}$",
+    )
+    .unwrap()
+});
+
+/// A single top-level statement of an input line.
+#[derive(Debug, Clone)]
+pub enum Statement {
+    /// A simple command, pipeline or redirection, kept as unexpanded source text.
+    Simple(String),
+    /// A `{ ...; }` group run in the current shell.
+    Group(String),
+    FunctionDef {
+        name: String,
+        body: String,
+    },
+    /// A statement preceded by `!`, whose exit status is inverted.
+    Negated(Box<Statement>),
+    /// Statements joined by `&&` and `||`. Each one after the first runs depending
+    /// on the status of the one before.
+    AndOr {
+        first: Box<Statement>,
+        rest: Vec<(Connector, Statement)>,
+    },
+    /// A `while` loop, or an `until` loop when `until` is set, with the word
+    /// after `done <` as the file it reads its input from.
+    Loop {
+        until: bool,
+        condition: String,
+        body: String,
+        input: Option<String>,
+    },
+    /// A pipeline with a loop or group as one of its stages. Pipelines of simple
+    /// commands are kept as `Simple` statements.
+    Pipeline(Vec<Statement>),
+}
+
+/// The operator joining two statements of an `&&`/`||` list.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Connector {
+    /// `&&`: run the next statement if this one succeeded.
+    And,
+    /// `||`: run the next statement if this one failed.
+    Or,
+}
+
+/// Splits a line into statements separated by `;` or newlines, keeping quoted text,
+/// command substitutions, `{ ... }` groups and `while`/`until` loops intact.
+pub struct ScriptParser;
+
+impl ScriptParser {
+    pub fn parse(input: &str) -> Result<Vec<Statement>> {
+        Self::split_statements(input)?
+            .into_iter()
+            .map(|source| Self::build_statement(&source))
+            .collect()
+    }
+
+    fn build_statement(source: &str) -> Result<Statement> {
+        let parts = Self::split_top_level(source, Self::connector_at)?;
+        if parts.len() < 2 {
+            return Self::build_pipeline(source);
+        }
+
+        let mut statements = Vec::new();
+        let mut connectors = Vec::new();
+        for (part, connector) in parts {
+            match connector {
+                _ if !part.is_empty() => {}
+                Some(connector) => bail!(
+                    "syntax error near unexpected token `{}'",
+                    Self::connector_token(connector)
+                ),
+                None => bail!("syntax error: unexpected end of file"),
+            }
+            statements.push(Self::build_pipeline(&part)?);
+            connectors.extend(connector);
+        }
+
+        let mut statements = statements.into_iter();
+        let first = Box::new(statements.next().unwrap());
+        Ok(Statement::AndOr {
+            first,
+            rest: connectors.into_iter().zip(statements).collect(),
+        })
+    }
+
+    /// Builds one element of an `&&`/`||` list.
+    fn build_pipeline(source: &str) -> Result<Statement> {
+        let source = source.trim();
+        if let Some(rest) = source.strip_prefix('!')
+            && rest.starts_with([' ', '\t'])
+        {
+            return Ok(Statement::Negated(Box::new(Self::build_pipeline(rest)?)));
+        }
+
+        let stages = Self::split_top_level(source, Self::pipe_at)?;
+        if stages.len() > 1 {
+            let mut statements = Vec::new();
+            for (stage, _) in &stages {
+                if stage.is_empty() {
+                    bail!("syntax error near unexpected token `|'");
+                }
+                statements.push(Self::build_command(stage)?);
+            }
+            if statements
+                .iter()
+                .any(|s| !matches!(s, Statement::Simple(_)))
+            {
+                return Ok(Statement::Pipeline(statements));
+            }
+        }
+        Self::build_command(source)
+    }
+
+    /// Builds one stage of a pipeline.
+    fn build_command(source: &str) -> Result<Statement> {
+        if let Some(keyword @ ("while" | "until")) =
+            Self::keyword_at(&source.chars().collect::<Vec<_>>(), 0)
+        {
+            return Self::build_loop(keyword == "until", &source[keyword.len()..]);
+        }
+
+        if let Some(caps) = FUNCTION_DEFINITION.captures(source) {
+            let name = caps.get(1).or_else(|| caps.get(2)).unwrap().as_str();
+            return Ok(Statement::FunctionDef {
+                name: name.to_string(),
+                body: caps[3].trim().to_string(),
+            });
+        }
+
+        if let Some(body) = source
+            .strip_prefix('{')
+            .and_then(|rest| rest.strip_suffix('}'))
+        {
+            return Ok(Statement::Group(body.trim().to_string()));
+        }
+
+        Ok(Statement::Simple(source.to_string()))
+    }
+
+    /// Builds a loop from what follows `while` or `until`: the condition, then
+    /// `do`, the body and `done`, optionally followed by `< file`.
+    fn build_loop(until: bool, source: &str) -> Result<Statement> {
+        let keyword = |chars: &[char], i: usize| match Self::keyword_at(chars, i) {
+            Some(word @ ("do" | "done")) => Some((word.len(), word)),
+            _ => None,
+        };
+        let parts = Self::split_top_level(source, keyword)?;
+        let [(condition, Some("do")), (body, Some("done")), (rest, None)] = parts.as_slice() else {
+            bail!("syntax error: unexpected end of file");
+        };
+
+        let is_empty = |part: &str| part.trim_end_matches([';', '\n', ' ', '\t']).is_empty();
+        if is_empty(condition) {
+            bail!("syntax error near unexpected token `do'");
+        }
+        if is_empty(body) {
+            bail!("syntax error near unexpected token `done'");
+        }
+
+        let input = match rest.strip_prefix('<').map(str::trim) {
+            None if rest.is_empty() => None,
+            Some("") => bail!("syntax error near unexpected token `newline'"),
+            Some(file) => Some(file.to_string()),
+            None => bail!(
+                "syntax error near unexpected token `{}'",
+                rest.split_whitespace().next().unwrap_or_default()
+            ),
+        };
+
+        Ok(Statement::Loop {
+            until,
+            condition: condition.clone(),
+            body: body.clone(),
+            input,
+        })
+    }
+
+    fn split_statements(input: &str) -> Result<Vec<String>> {
+        let separator =
+            |chars: &[char], i: usize| matches!(chars[i], ';' | '\n').then_some((1, ()));
+        Ok(Self::split_top_level(input, separator)?
+            .into_iter()
+            .map(|(statement, _)| statement)
+            .filter(|statement| !statement.is_empty())
+            .collect())
+    }
+
+    /// The `&&` or `||` starting at `chars[i]`.
+    fn connector_at(chars: &[char], i: usize) -> Option<(usize, Connector)> {
+        match (chars[i], chars.get(i + 1)) {
+            ('&', Some('&')) => Some((2, Connector::And)),
+            ('|', Some('|')) => Some((2, Connector::Or)),
+            _ => None,
+        }
+    }
+
+    /// The `|` starting at `chars[i]`, when it is not part of `||`.
+    fn pipe_at(chars: &[char], i: usize) -> Option<(usize, ())> {
+        let single =
+            chars[i] == '|' && chars.get(i + 1) != Some(&'|') && (i == 0 || chars[i - 1] != '|');
+        single.then_some((1, ()))
+    }
+
+    fn connector_token(connector: Connector) -> &'static str {
+        match connector {
+            Connector::And => "&&",
+            Connector::Or => "||",
+        }
+    }
+
+    /// Splits `input` wherever `separator` matches outside quotes, parentheses,
+    /// `{ ... }` groups and loops. `separator` returns the length of the separator at an index
+    /// and what it was, which is paired with the trimmed part it ends. Comments are
+    /// left out of the parts.
+    fn split_top_level<T>(
+        input: &str,
+        separator: impl Fn(&[char], usize) -> Option<(usize, T)>,
+    ) -> Result<Vec<(String, Option<T>)>> {
+        let chars: Vec<char> = input.chars().collect();
+        let mut parts = Vec::new();
+        let mut current = String::new();
+        let mut quote: Option<char> = None;
+        let mut parens = 0usize;
+        let mut braces = 0usize;
+        let mut loops = 0usize;
+        let mut i = 0;
+
+        while i < chars.len() {
+            let c = chars[i];
+
+            if let Some(q) = quote {
+                current.push(c);
+                if c == '\\' && q == '"' && i + 1 < chars.len() {
+                    current.push(chars[i + 1]);
+                    i += 1;
+                } else if c == q {
+                    quote = None;
+                }
+                i += 1;
+                continue;
+            }
+
+            // A `#` starting a word comments out the rest of the line. The newline
+            // is kept, as it still ends the statement.
+            if c == '#'
+                && (i == 0
+                    || matches!(
+                        chars[i - 1],
+                        ' ' | '\t' | '\n' | ';' | '|' | '&' | '(' | ')'
+                    ))
+            {
+                while i < chars.len() && chars[i] != '\n' {
+                    i += 1;
+                }
+                continue;
+            }
+
+            // Whether a separator here ends a part, decided before a `done` closes
+            // the loop it belongs to.
+            let top_level = parens == 0 && braces == 0 && loops == 0;
+            match Self::keyword_at(&chars, i) {
+                Some("while" | "until") => loops += 1,
+                Some("done") if loops > 0 => loops -= 1,
+                Some(token @ ("do" | "done"))
+                    if loops == 0 && (!top_level || separator(&chars, i).is_none()) =>
+                {
+                    bail!("syntax error near unexpected token `{}'", token)
+                }
+                _ => {}
+            }
+
+            match c {
+                '\\' => {
+                    current.push(c);
+                    if i + 1 < chars.len() {
+                        current.push(chars[i + 1]);
+                        i += 1;
+                    }
+                }
+                '\'' | '"' => {
+                    quote = Some(c);
+                    current.push(c);
+                }
+                '(' => {
+                    parens += 1;
+                    current.push(c);
+                }
+                ')' => {
+                    if parens == 0 {
+                        bail!("syntax error near unexpected token `)'");
+                    }
+                    parens -= 1;
+                    current.push(c);
+                }
+                '{' if parens == 0 && Self::is_word_boundary(&chars, i) => {
+                    braces += 1;
+                    current.push(c);
+                }
+                '}' if parens == 0 && braces > 0 && Self::is_word_boundary(&chars, i) => {
+                    braces -= 1;
+                    current.push(c);
+                }
+                _ if top_level && let Some((len, value)) = separator(&chars, i) => {
+                    parts.push((current.trim().to_string(), Some(value)));
+                    current.clear();
+                    i += len - 1;
+                }
+                _ => current.push(c),
+            }
+            i += 1;
+        }
+
+        if quote.is_some() || parens > 0 || braces > 0 || loops > 0 {
+            bail!("syntax error: unexpected end of file");
+        }
+
+        parts.push((current.trim().to_string(), None));
+        Ok(parts)
+    }
+
+    /// The loop keyword starting at `chars[i]`, when it is a whole word in command
+    /// position: at the start, after an operator or after `do`.
+    fn keyword_at(chars: &[char], i: usize) -> Option<&'static str> {
+        let is_word_end = |j: usize| {
+            j == chars.len()
+                || matches!(
+                    chars[j],
+                    ' ' | '\t' | '\n' | ';' | '|' | '&' | ')' | '<' | '>'
+                )
+        };
+        ["while", "until", "done", "do"]
+            .into_iter()
+            .find(|keyword| {
+                let end = i + keyword.len();
+                end <= chars.len()
+                    && chars[i..end].iter().copied().eq(keyword.chars())
+                    && is_word_end(end)
+                    && Self::is_command_position(chars, i)
+            })
+    }
+
+    /// Whether a word starting at `chars[i]` is in command position.
+    fn is_command_position(chars: &[char], i: usize) -> bool {
+        let mut j = i;
+        while j > 0 && matches!(chars[j - 1], ' ' | '\t') {
+            j -= 1;
+        }
+        if j == i && i > 0 && !matches!(chars[i - 1], '\n' | ';' | '|' | '&' | '(' | '{') {
+            return false;
+        }
+        if j == 0 || matches!(chars[j - 1], '\n' | ';' | '|' | '&' | '(' | '{') {
+            return true;
+        }
+        j >= 2 && chars[j - 2..j] == ['d', 'o'] && Self::is_command_position(chars, j - 2)
+    }
+
+    /// Braces only open or close a group when they stand alone as a word, so that
+    /// `${name}` and `a{b}` are left to the expander.
+    fn is_word_boundary(chars: &[char], i: usize) -> bool {
+        let before = i == 0 || matches!(chars[i - 1], ' ' | '\t' | '\n' | ';' | ')');
+        let after = i + 1 == chars.len() || matches!(chars[i + 1], ' ' | '\t' | '\n' | ';');
+        before && after
+    }
+}
@@ -1,51 +1,98 @@
 use anyhow::Result;
-use rustyline::Editor;
 use rustyline::error::ReadlineError;
+use rustyline::history::History;
+use rustyline::{Config, Editor, EventHandler, KeyCode, KeyEvent, Modifiers};
 use std::io::{self, Write};
+use std::rc::Rc;
 use std::sync::{Arc, Mutex};
 
-use crate::auto_complete::auto_complete_helper::AutoCompleteHelper;
+use crate::auto_complete::auto_complete_helper::{AcceptHintWordHandler, AutoCompleteHelper};
+use crate::auto_complete::completion_spec::SharedCompletions;
+use crate::auto_complete::history_search::HistorySearchHandler;
+use crate::commands::alias_state::SharedAliases;
+use crate::commands::builtin::SharedBuiltins;
 use crate::commands::command_handler::CommandHandler;
-use crate::commands::history_state::HistoryState;
-use crate::commands::path_helper::PathHelper;
-use crate::commands::pipeline_command_handler::PipelineCommandHandler;
-use crate::commands::redirection_command_handler::RedirectionCommandHandler;
-use crate::commands::supported_command::SupportedCommand;
-use crate::parsing::command_parser::CommandParser;
+use crate::commands::command_hash::SharedCommandHash;
+use crate::commands::function_state::SharedFunctions;
+use crate::commands::history_state::{self, HistoryControl, HistoryState};
+use crate::commands::plugin_state::SharedPlugins;
+use crate::commands::shell_state::SharedState;
+use crate::parsing::history_expander::HistoryExpander;
+use crate::shell::executor::Executor;
 
 /// A type alias for a thread-safe, shared command handler.
 type Handler = Arc<dyn CommandHandler>;
 
-/// The main Shell engine responsible for the REPL loop, history management,
-/// and command dispatching.
+/// The main Shell engine responsible for the REPL loop and history management.
+/// Command execution is delegated to the [`Executor`].
 pub struct Shell {
-    /// Registered command handlers.
-    handlers: Vec<Handler>,
+    /// Runs each line of input.
+    executor: Rc<Executor>,
+    /// The registered builtins, offered as command completions.
+    builtins: SharedBuiltins,
     /// Shared in-memory history state.
     state: Arc<Mutex<HistoryState>>,
-    /// Path to the history file, typically from the `HISTFILE` environment variable.
-    history_file: Option<String>,
+    /// The working directory, variables and options commands run with. `HISTFILE`,
+    /// the `HISTSIZE`/`HISTCONTROL` family and the history options are read from it.
+    shell_state: SharedState,
+    /// Shared functions, offered as command completions.
+    functions: SharedFunctions,
+    /// Shared completion specs registered with `complete`.
+    completions: SharedCompletions,
+    /// Shared command hash table, offering PATH executables as completions.
+    hash: SharedCommandHash,
+    /// Loaded plugins, which may contribute segments to the prompt.
+    plugins: SharedPlugins,
 }
 
 impl Shell {
-    pub fn new(handlers: Vec<Handler>, state: Arc<Mutex<HistoryState>>) -> Self {
-        let history_file = std::env::var("HISTFILE").ok();
+    #[allow(clippy::too_many_arguments)]
+    pub fn new(
+        handlers: Vec<Handler>,
+        builtins: SharedBuiltins,
+        state: Arc<Mutex<HistoryState>>,
+        shell_state: SharedState,
+        functions: SharedFunctions,
+        completions: SharedCompletions,
+        hash: SharedCommandHash,
+        aliases: SharedAliases,
+        plugins: SharedPlugins,
+    ) -> Self {
+        let executor = Rc::new(Executor::new(
+            handlers,
+            builtins.clone(),
+            shell_state.clone(),
+            functions.clone(),
+            aliases,
+        ));
 
         Self {
-            handlers,
+            executor,
+            builtins,
             state,
-            history_file,
+            shell_state,
+            functions,
+            completions,
+            hash,
+            plugins,
         }
     }
 
-    pub fn run(&mut self) -> Result<()> {
+    /// Reads and runs commands until `exit` or end of input, and returns the status
+    /// of the last command.
+    pub fn run(&mut self) -> Result<i32> {
         let mut rl = self.setup_readline()?;
         self.load_history(&mut rl);
 
         loop {
+            self.share_history();
+            if self.executor.run_pending_traps()? || self.run_prompt_command()? {
+                break;
+            }
+            self.sync_history(&mut rl)?;
             io::stdout().flush().unwrap();
 
-            let input = match rl.readline("$ ") {
+            let input = match rl.readline(&self.prompt()) {
                 Ok(line) => line,
                 Err(ReadlineError::Interrupted) | Err(ReadlineError::Eof) => break,
                 Err(err) => {
@@ -58,125 +105,204 @@ impl Shell {
                 continue;
             }
 
-            self.record_history(&mut rl, &input)?;
+            // Leading whitespace is kept so `HISTCONTROL=ignorespace` can see it.
+            let Some((input, print_only)) = self.expand_history(input.trim_end()) else {
+                continue;
+            };
 
-            let command = CommandParser::parse(input.trim());
+            self.record_history(&input);
+            self.share_history();
+            self.sync_history(&mut rl)?;
 
-            if let Some(should_exit) = self.dispatch_command(&command)? {
-                if should_exit {
-                    break;
-                }
+            if print_only {
+                continue;
+            }
+
+            if self.executor.execute(input.trim())? {
+                break;
             }
         }
 
+        self.executor.run_exit_trap()?;
         self.save_history()?;
-        Ok(())
+        Ok(self.shell_state.lock().unwrap().variables.last_status())
     }
 
     fn setup_readline(
         &self,
     ) -> Result<Editor<AutoCompleteHelper, rustyline::history::FileHistory>> {
-        let mut rl = Editor::new()?;
+        // The shared history applies `HISTCONTROL` and `HISTSIZE` itself, so the
+        // editor keeps every entry it is given.
+        let config = Config::builder()
+            .max_history_size(usize::MAX)?
+            .history_ignore_dups(false)?
+            .build();
+        let mut rl = Editor::with_config(config)?;
 
-        let executables = PathHelper::get_all_executables().unwrap_or_default();
-        let supported_cmds = SupportedCommand::commands();
-        let all_commands: Vec<String> = executables
-            .into_iter()
-            .chain(supported_cmds.into_iter())
-            .collect();
-
-        let helper = AutoCompleteHelper::new(all_commands);
+        let helper = AutoCompleteHelper::new(
+            self.builtins.clone(),
+            self.hash.clone(),
+            self.completions.clone(),
+            self.functions.clone(),
+            self.state.clone(),
+            self.shell_state.clone(),
+            self.executor.clone(),
+        );
         rl.set_helper(Some(helper));
+        rl.bind_sequence(
+            KeyEvent::ctrl('r'),
+            EventHandler::Conditional(Box::new(HistorySearchHandler::new(
+                self.state.clone(),
+                self.shell_state.clone(),
+            ))),
+        );
+        rl.bind_sequence(
+            KeyEvent(KeyCode::Right, Modifiers::ALT),
+            EventHandler::Conditional(Box::new(AcceptHintWordHandler)),
+        );
 
         Ok(rl)
     }
 
     fn load_history(&self, rl: &mut Editor<AutoCompleteHelper, rustyline::history::FileHistory>) {
-        if let Some(ref path) = self.history_file {
-            if std::path::Path::new(path).exists() {
-                if let Ok(file) = std::fs::File::open(path) {
-                    use std::io::BufRead;
-                    let reader = std::io::BufReader::new(file);
-                    let mut s = self.state.lock().unwrap();
-                    for line in reader.lines().flatten() {
-                        if !line.trim().is_empty() {
-                            let _ = rl.add_history_entry(&line);
-                            s.commands.push(line);
-                        }
-                    }
-                    s.last_appended_index = s.commands.len();
-                }
-            }
+        if let Some(path) = self.history_file()
+            && let Ok((entries, line_count)) = history_state::read_history_file(&path, 0)
+        {
+            let control = self.history_control();
+            let mut s = self.state.lock().unwrap();
+            s.entries.extend(entries);
+            s.lines_in_file = line_count;
+            s.truncate(control.size);
+            s.last_appended_index = s.entries.len();
         }
+        let _ = self.sync_history(rl);
     }
 
-    fn record_history(
-        &self,
-        rl: &mut Editor<AutoCompleteHelper, rustyline::history::FileHistory>,
-        input: &str,
-    ) -> Result<()> {
-        rl.add_history_entry(input)?;
-        self.state
-            .lock()
-            .unwrap()
-            .commands
-            .push(input.trim().to_string());
-        Ok(())
+    /// The history file, which follows `HISTFILE` as it changes.
+    fn history_file(&self) -> Option<String> {
+        self.shell_state.lock().unwrap().history_file()
     }
 
-    fn dispatch_command(&self, command: &SupportedCommand) -> Result<Option<bool>> {
-        match command {
-            SupportedCommand::Exit => return Ok(Some(true)),
-            SupportedCommand::NoArgument => return Ok(None),
-            SupportedCommand::Redirection { .. } => {
-                let handler = RedirectionCommandHandler::new(self.handlers.clone());
-                self.execute_handler(&handler, command)?;
-            }
-            SupportedCommand::Pipeline { .. } => {
-                let handler = PipelineCommandHandler::new(self.handlers.clone());
-                self.execute_handler(&handler, command)?;
-            }
-            _ => {
-                if let Some(handler) = self.handlers.iter().find(|h| h.can_handle(command)) {
-                    self.execute_handler(handler.as_ref(), command)?;
-                } else {
-                    eprintln!("No handler found for the command");
+    fn history_control(&self) -> HistoryControl {
+        HistoryControl::from_variables(&self.shell_state.lock().unwrap().variables)
+    }
+
+    /// Applies history expansion when `histexpand` is on, echoing the line when it
+    /// changed. Returns the line and whether `:p` asked for it to be printed only,
+    /// or `None` when expansion failed.
+    fn expand_history(&self, input: &str) -> Option<(String, bool)> {
+        if !self.shell_state.lock().unwrap().options.histexpand {
+            return Some((input.to_string(), false));
+        }
+
+        let (history, base) = {
+            let s = self.state.lock().unwrap();
+            (s.commands(), s.base)
+        };
+        // Expansion works on the trimmed line; leading whitespace is put back so
+        // the recorded line can still be checked against `ignorespace`.
+        let indent = &input[..input.len() - input.trim_start().len()];
+        match HistoryExpander::new(&history, base).expand(input.trim_start()) {
+            Ok(expansion) => {
+                if expansion.print_only {
+                    println!("{}", expansion.line);
+                } else if expansion.changed {
+                    eprintln!("{}", expansion.line);
                 }
+                Some((
+                    format!("{}{}", indent, expansion.line),
+                    expansion.print_only,
+                ))
+            }
+            Err(err) => {
+                eprintln!("{}", err);
+                None
             }
         }
-        Ok(None)
     }
 
-    fn execute_handler(
+    fn record_history(&self, input: &str) {
+        let control = self.history_control();
+        let cwd = self.shell_state.lock().unwrap().cwd().to_string();
+        self.state.lock().unwrap().record(input, &cwd, &control);
+    }
+
+    /// Mirrors the shared history into the line editor, which keeps its own copy for
+    /// arrow-key navigation. `HISTSIZE`, `erasedups` and builtins such as
+    /// `history -d` only change the shared one.
+    fn sync_history(
         &self,
-        handler: &dyn CommandHandler,
-        command: &SupportedCommand,
+        rl: &mut Editor<AutoCompleteHelper, rustyline::history::FileHistory>,
     ) -> Result<()> {
-        match handler.handle(command) {
-            Ok(output) if !output.is_empty() => {
-                println!("{}", output.trim_end_matches('\n'));
+        let commands = self.state.lock().unwrap().commands();
+        let len = rl.history().len();
+        let is_prefix = len <= commands.len() && rl.history().iter().eq(&commands[..len]);
+
+        if is_prefix {
+            for line in &commands[len..] {
+                rl.add_history_entry(line.as_str())?;
             }
-            Err(err) => {
-                println!("{}", err.to_string().trim_end_matches('\n'));
+        } else {
+            rl.clear_history()?;
+            for line in &commands {
+                rl.add_history_entry(line.as_str())?;
             }
-            _ => {}
         }
         Ok(())
     }
 
-    fn save_history(&self) -> Result<()> {
-        if let Some(ref path) = self.history_file {
-            let mut file = std::fs::OpenOptions::new()
-                .create(true)
-                .write(true)
-                .append(true)
-                .open(path)?;
+    /// With `histappend` or `histshare` on, writes new entries to the history file as
+    /// soon as they are recorded; `histshare` also merges in other sessions' entries
+    /// before each prompt.
+    fn share_history(&self) {
+        let (append, share) = {
+            let options = &self.shell_state.lock().unwrap().options;
+            (options.histappend, options.histshare)
+        };
+        if !append && !share {
+            return;
+        }
+        let Some(path) = self.history_file() else {
+            return;
+        };
 
-            let s = self.state.lock().unwrap();
-            let start = s.last_appended_index;
-            for i in start..s.commands.len() {
-                writeln!(file, "{}", s.commands[i])?;
-            }
+        let control = self.history_control();
+        let mut s = self.state.lock().unwrap();
+        match s.sync_file(&path, share) {
+            Ok(()) => s.truncate(control.size),
+            Err(err) => eprintln!("{}: {}", path, err),
+        }
+    }
+
+    /// The prompt: any plugin segments followed by `$ `.
+    fn prompt(&self) -> String {
+        let (cwd, status) = {
+            let state = self.shell_state.lock().unwrap();
+            (state.cwd().to_string(), state.variables.last_status())
+        };
+        let segments = self.plugins.lock().unwrap().prompt(&cwd, status);
+        format!("{}$ ", segments)
+    }
+
+    /// Runs `PROMPT_COMMAND` before the prompt is shown. Returns `true` when it
+    /// asked the shell to exit.
+    fn run_prompt_command(&self) -> Result<bool> {
+        let command = self
+            .shell_state
+            .lock()
+            .unwrap()
+            .variables
+            .get("PROMPT_COMMAND");
+        match command {
+            Some(command) if !command.trim().is_empty() => self.executor.execute(&command),
+            _ => Ok(false),
+        }
+    }
+
+    fn save_history(&self) -> Result<()> {
+        if let Some(path) = self.history_file() {
+            self.state.lock().unwrap().sync_file(&path, false)?;
+            history_state::truncate_history_file(&path, self.history_control().file_size)?;
         }
         Ok(())
     }
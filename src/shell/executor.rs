@@ -0,0 +1,1147 @@
+use anyhow::Result;
+use std::borrow::Cow;
+use std::cell::{Cell, RefCell};
+use std::fs::File;
+use std::io::{self, Read, Write};
+use std::ops::ControlFlow;
+use std::os::fd::OwnedFd;
+use std::process::{Child, Stdio};
+use std::sync::Arc;
+use std::thread::JoinHandle;
+
+use crate::auto_complete::auto_complete_helper::CompletionFunctionRunner;
+use crate::commands::alias_state::SharedAliases;
+use crate::commands::builtin::SharedBuiltins;
+use crate::commands::command_failure::CommandFailure;
+use crate::commands::command_handler::CommandHandler;
+use crate::commands::function_state::SharedFunctions;
+use crate::commands::path_helper::PathHelper;
+use crate::commands::pipeline_command_handler::PipelineCommandHandler;
+use crate::commands::redirection_command_handler::RedirectionCommandHandler;
+use crate::commands::shell_state::{SharedState, pipe_input};
+use crate::commands::supported_command::{RedirectionKind, SupportedCommand};
+use crate::commands::trap_state::TrapEvent;
+use crate::commands::unspecified_command_handler::UnspecifiedCommandHandler;
+use crate::core::edit_distance;
+use crate::parsing::alias_expander::AliasExpander;
+use crate::parsing::command_parser::CommandParser;
+use crate::parsing::expander::Expander;
+use crate::parsing::script_parser::{Connector, ScriptParser, Statement};
+
+type Handler = Arc<dyn CommandHandler>;
+
+/// How deeply functions may call each other when `FUNCNEST` is not set, well
+/// before the recursion would overflow the stack.
+const DEFAULT_FUNCNEST: usize = 1000;
+
+/// A stage of a pipeline that the executor runs itself.
+enum Stage<'a> {
+    /// A loop, group or simple command, expanded when its turn comes.
+    Statement(&'a Statement),
+    /// A command from a pipeline whose stages were expanded together.
+    Command(&'a SupportedCommand),
+}
+
+/// Runs parsed input: splits it into statements, expands each one, and dispatches
+/// the resulting commands to their handlers or to shell functions.
+pub struct Executor {
+    handlers: Vec<Handler>,
+    /// The registered builtins, which decide how command names are parsed.
+    builtins: SharedBuiltins,
+    /// Locked while a handler runs, which gets it as its `ShellState`.
+    state: SharedState,
+    functions: SharedFunctions,
+    aliases: SharedAliases,
+    /// Set while `command_not_found_handle` runs, so a missing command inside it
+    /// does not call it again.
+    in_not_found_handler: Cell<bool>,
+    /// Non-zero while running commands whose status is being tested, such as those
+    /// before `&&` or after `!`, where `errexit` does not apply.
+    testing_status: Cell<usize>,
+    /// How many function calls are running. `DEBUG` and `ERR` traps only fire
+    /// outside functions.
+    function_depth: Cell<usize>,
+    /// Set while a trap action runs, so traps do not fire inside it.
+    running_trap: Cell<bool>,
+    /// Set by `return` while the statements of the function unwind, which they do
+    /// as they would for `exit`, until `call_function` clears it.
+    returning: Cell<bool>,
+    /// Set when an error, such as too deep function nesting, drops the rest of the
+    /// line. Statements unwind as for `exit` until `execute` clears it.
+    aborting: Cell<bool>,
+    /// Where standard output goes while a command substitution is being captured,
+    /// or while a function's output is redirected.
+    capture: RefCell<Option<File>>,
+    /// Where standard error goes while it is being captured by `capture_streams`,
+    /// or while a function's errors are redirected.
+    stderr_capture: RefCell<Option<File>>,
+}
+
+impl Executor {
+    pub fn new(
+        handlers: Vec<Handler>,
+        builtins: SharedBuiltins,
+        state: SharedState,
+        functions: SharedFunctions,
+        aliases: SharedAliases,
+    ) -> Self {
+        Self {
+            handlers,
+            builtins,
+            state,
+            functions,
+            aliases,
+            in_not_found_handler: Cell::new(false),
+            testing_status: Cell::new(0),
+            function_depth: Cell::new(0),
+            running_trap: Cell::new(false),
+            returning: Cell::new(false),
+            aborting: Cell::new(false),
+            capture: RefCell::new(None),
+            stderr_capture: RefCell::new(None),
+        }
+    }
+
+    /// Adds a handler ahead of the others, so a builtin it declares replaces any
+    /// existing one of the same name.
+    pub fn add_handler(&mut self, handler: Handler) {
+        {
+            let mut builtins = self.builtins.lock().unwrap();
+            for builtin in handler.builtins() {
+                builtins.register(*builtin);
+            }
+        }
+        self.handlers.insert(0, handler);
+    }
+
+    /// Executes a line of input. Returns `true` when the shell should exit.
+    pub fn execute(&self, input: &str) -> Result<bool> {
+        if self.state.lock().unwrap().options.verbose {
+            self.write_stderr(&format!("{}\n", input))?;
+        }
+        Ok(self.execute_input(input)? && !self.aborting.take())
+    }
+
+    /// Executes input that `verbose` does not echo, such as a command substitution.
+    /// Aliases are expanded first when `expand_aliases` is on.
+    fn execute_input(&self, input: &str) -> Result<bool> {
+        if !self.state.lock().unwrap().options.expand_aliases {
+            return self.execute_expanded(input);
+        }
+        let input = AliasExpander::new(&self.aliases.lock().unwrap()).expand(input);
+        self.execute_expanded(&input)
+    }
+
+    /// Executes input whose aliases have already been expanded, such as the body
+    /// of a group or function.
+    fn execute_expanded(&self, input: &str) -> Result<bool> {
+        match ScriptParser::parse(input) {
+            Ok(statements) => self.execute_statements(&statements),
+            Err(err) => {
+                self.write_stderr(&format!("{}\n", err))?;
+                Ok(false)
+            }
+        }
+    }
+
+    fn execute_statements(&self, statements: &[Statement]) -> Result<bool> {
+        for statement in statements {
+            if self.execute_statement(statement)? || self.run_pending_traps()? {
+                return Ok(true);
+            }
+        }
+        Ok(false)
+    }
+
+    /// Runs a statement. Returns `true` when the shell should exit, which includes a
+    /// command failing under `errexit`.
+    fn execute_statement(&self, statement: &Statement) -> Result<bool> {
+        {
+            let options = &self.state.lock().unwrap().options;
+            if options.noexec && !options.interactive {
+                return Ok(false);
+            }
+        }
+
+        match statement {
+            Statement::FunctionDef { name, body } => {
+                self.functions.lock().unwrap().define(name, body);
+                Ok(false)
+            }
+            Statement::Group(body) => self.execute_expanded(body),
+            Statement::Simple(source) => {
+                let command = match self.prepare_simple(source)? {
+                    ControlFlow::Continue(command) => command,
+                    ControlFlow::Break(should_exit) => return Ok(should_exit),
+                };
+                if self.dispatch_command(&command)? {
+                    return Ok(true);
+                }
+                self.statement_finished()
+            }
+            Statement::Negated(statement) => {
+                let should_exit = self.testing_status(|| self.execute_statement(statement))?;
+                let status = self.state.lock().unwrap().variables.last_status();
+                self.set_last_status(if status == 0 { 1 } else { 0 });
+                Ok(should_exit)
+            }
+            Statement::AndOr { first, rest } => {
+                let mut current: &Statement = first;
+                let mut run = true;
+                for (connector, next) in rest {
+                    if run && self.testing_status(|| self.execute_statement(current))? {
+                        return Ok(true);
+                    }
+                    let succeeded = self.state.lock().unwrap().variables.last_status() == 0;
+                    run = match connector {
+                        Connector::And => succeeded,
+                        Connector::Or => !succeeded,
+                    };
+                    current = next;
+                }
+                if run {
+                    return self.execute_statement(current);
+                }
+                Ok(false)
+            }
+            Statement::Loop {
+                until,
+                condition,
+                body,
+                input,
+            } => self.run_loop(*until, condition, body, input.as_deref()),
+            Statement::Pipeline(stages) => {
+                let stages: Vec<_> = stages.iter().map(Stage::Statement).collect();
+                if self.run_pipeline(&stages)? {
+                    return Ok(true);
+                }
+                self.statement_finished()
+            }
+        }
+    }
+
+    /// Expands and parses a simple command, after running the `DEBUG` trap, and
+    /// traces it. Breaks with whether the shell should exit when the trap says so
+    /// or the expansion fails.
+    fn prepare_simple(&self, source: &str) -> Result<ControlFlow<bool, SupportedCommand>> {
+        // A trap's own commands leave `BASH_COMMAND` naming the one that triggered
+        // it.
+        if !self.running_trap.get() {
+            self.state
+                .lock()
+                .unwrap()
+                .variables
+                .set_scalar("BASH_COMMAND", source);
+        }
+        if self.function_depth.get() == 0 && self.run_trap(TrapEvent::Debug)? {
+            return Ok(ControlFlow::Break(true));
+        }
+        let source = match self.expand(source) {
+            Ok(source) => source,
+            Err(err) => return self.expansion_failed(err).map(ControlFlow::Break),
+        };
+        let command = CommandParser::parse(
+            &source,
+            &self.builtins.lock().unwrap(),
+            &self.state.lock().unwrap().options,
+        );
+        self.trace(&command)?;
+        Ok(ControlFlow::Continue(command))
+    }
+
+    /// Runs a `while` loop, or an `until` loop, with standard input read from the
+    /// file named by `input` when there is one.
+    fn run_loop(
+        &self,
+        until: bool,
+        condition: &str,
+        body: &str,
+        input: Option<&str>,
+    ) -> Result<bool> {
+        let previous = match input.map(|word| self.open_input(word)) {
+            Some(Ok(file)) => Some(self.state.lock().unwrap().replace_stdin(Some(file))),
+            Some(Err(err)) => {
+                self.print_result(Err(err))?;
+                return self.statement_finished();
+            }
+            None => None,
+        };
+        let result = self.repeat(until, condition, body);
+        if let Some(previous) = previous {
+            self.state.lock().unwrap().replace_stdin(previous);
+        }
+        result
+    }
+
+    /// Runs `body` for as long as `condition` succeeds, or until it does. The loop's
+    /// status is that of the last body run, or 0 when it never ran.
+    fn repeat(&self, until: bool, condition: &str, body: &str) -> Result<bool> {
+        let mut status = 0;
+        loop {
+            if self.testing_status(|| self.execute_expanded(condition))? {
+                return Ok(true);
+            }
+            let succeeded = self.state.lock().unwrap().variables.last_status() == 0;
+            if succeeded == until {
+                break;
+            }
+            if self.execute_expanded(body)? || self.run_pending_traps()? {
+                return Ok(true);
+            }
+            status = self.state.lock().unwrap().variables.last_status();
+        }
+        self.set_last_status(status);
+        Ok(false)
+    }
+
+    /// Opens the file named by the unexpanded `word` for reading.
+    fn open_input(&self, word: &str) -> Result<OwnedFd> {
+        let word = self.expand(word)?;
+        let path = CommandParser::split_words(&word)
+            .into_iter()
+            .next()
+            .unwrap_or_default();
+        let file = File::open(self.state.lock().unwrap().resolve(&path))
+            .map_err(|err| CommandFailure::open_failed(&path, &err))?;
+        Ok(file.into())
+    }
+
+    /// Runs a pipeline with a loop, group or function among its stages. External
+    /// commands start when their turn comes and run alongside the later stages,
+    /// which read their output through a pipe as it is written. The other stages
+    /// run in turn, each reading the output of the one before it.
+    fn run_pipeline(&self, stages: &[Stage]) -> Result<bool> {
+        let mut statuses = vec![0; stages.len()];
+        let mut children = Vec::new();
+        let result =
+            self.testing_status(|| self.start_stages(stages, &mut statuses, &mut children));
+        for (i, mut child) in children {
+            statuses[i] = CommandFailure::exit_code(child.wait()?);
+        }
+        if result? {
+            return Ok(true);
+        }
+
+        let status = PipelineCommandHandler::status(&statuses, &self.state.lock().unwrap());
+        self.set_last_status(status);
+        Ok(false)
+    }
+
+    /// Runs each stage of a pipeline, recording the status of those the shell runs
+    /// itself and leaving the external commands running in `children`, along with
+    /// their positions.
+    fn start_stages(
+        &self,
+        stages: &[Stage],
+        statuses: &mut [i32],
+        children: &mut Vec<(usize, Child)>,
+    ) -> Result<bool> {
+        let mut input: Option<OwnedFd> = None;
+        for (i, stage) in stages.iter().enumerate() {
+            let last = i + 1 == stages.len();
+            let (command, finish) = match stage {
+                Stage::Statement(Statement::Simple(source)) => {
+                    match self.prepare_simple(source)? {
+                        ControlFlow::Continue(command) => (Cow::Owned(command), true),
+                        ControlFlow::Break(true) => return Ok(true),
+                        ControlFlow::Break(false) => {
+                            statuses[i] = self.state.lock().unwrap().variables.last_status();
+                            input = Some(pipe_input(String::new())?);
+                            continue;
+                        }
+                    }
+                }
+                Stage::Statement(statement) => {
+                    let (should_exit, output) = self.run_shell_stage(input.take(), last, || {
+                        self.execute_statement(statement)
+                    })?;
+                    if should_exit {
+                        return Ok(true);
+                    }
+                    statuses[i] = self.state.lock().unwrap().variables.last_status();
+                    input = output;
+                    continue;
+                }
+                Stage::Command(command) => (Cow::Borrowed(*command), false),
+            };
+
+            let (assignments, inner_cmd) = command.split_assignments();
+            if let SupportedCommand::Unspecified { cmd, args } = inner_cmd
+                && self.is_external(cmd, args)
+            {
+                let stdin = match input.take() {
+                    Some(fd) => Stdio::from(fd),
+                    None => self.state.lock().unwrap().child_stdin(),
+                };
+                let stdout = if last { self.stdout() } else { Stdio::piped() };
+                let saved = self
+                    .state
+                    .lock()
+                    .unwrap()
+                    .variables
+                    .assign_temporarily(assignments);
+                let spawned = self.spawn_external(cmd, args, stdin, stdout);
+                self.state.lock().unwrap().variables.restore(saved);
+                match spawned? {
+                    Some(mut child) => {
+                        input = child.stdout.take().map(OwnedFd::from);
+                        children.push((i, child));
+                    }
+                    None => {
+                        statuses[i] = self.state.lock().unwrap().variables.last_status();
+                        input = Some(pipe_input(String::new())?);
+                    }
+                }
+                continue;
+            }
+
+            let (should_exit, output) = self.run_shell_stage(input.take(), last, || {
+                if self.dispatch_command(&command)? {
+                    return Ok(true);
+                }
+                if finish {
+                    self.statement_finished()
+                } else {
+                    Ok(false)
+                }
+            })?;
+            if should_exit {
+                return Ok(true);
+            }
+            statuses[i] = self.state.lock().unwrap().variables.last_status();
+            input = output;
+        }
+        Ok(false)
+    }
+
+    /// Runs a pipeline stage that the shell runs itself, reading `input` when there
+    /// is one. Unless it is the last stage, its output is collected and returned for
+    /// the next stage to read.
+    fn run_shell_stage(
+        &self,
+        input: Option<OwnedFd>,
+        last: bool,
+        f: impl FnOnce() -> Result<bool>,
+    ) -> Result<(bool, Option<OwnedFd>)> {
+        let previous = input.map(|fd| self.state.lock().unwrap().replace_stdin(Some(fd)));
+        let result = if last {
+            f().map(|should_exit| (should_exit, None))
+        } else {
+            let mut should_exit = false;
+            let output = self.capture_output(|| {
+                should_exit = f()? && !self.returning.get() && !self.aborting.get();
+                Ok(should_exit)
+            });
+            pipe_input(output)
+                .map(|output| (should_exit, Some(output)))
+                .map_err(Into::into)
+        };
+        // Putting standard input back closes the read end of the pipe, so an
+        // external stage still writing to it stops.
+        if let Some(previous) = previous {
+            self.state.lock().unwrap().replace_stdin(previous);
+        }
+        result
+    }
+
+    /// Whether `cmd` runs an external command, rather than a function or `cd`
+    /// through `autocd`.
+    fn is_external(&self, cmd: &str, args: &[String]) -> bool {
+        !(self.functions.lock().unwrap().contains(cmd) || args.is_empty() && self.is_autocd(cmd))
+    }
+
+    /// Runs `f` where a failing command does not trigger `errexit`, because its
+    /// status is being tested.
+    fn testing_status(&self, f: impl FnOnce() -> Result<bool>) -> Result<bool> {
+        self.testing_status.set(self.testing_status.get() + 1);
+        let result = f();
+        self.testing_status.set(self.testing_status.get() - 1);
+        result
+    }
+
+    /// Handles the status of a statement that has run: runs the `ERR` trap if it
+    /// failed, then returns whether `errexit` ends the shell. Both are skipped where
+    /// the status is being tested.
+    fn statement_finished(&self) -> Result<bool> {
+        if self.run_err_trap()? {
+            return Ok(true);
+        }
+        Ok(self.exits_on_error())
+    }
+
+    /// Runs the `ERR` trap when the last command failed where `errexit` would apply.
+    /// Like bash without `set -E`, it does not fire inside functions.
+    fn run_err_trap(&self) -> Result<bool> {
+        let failed = self.state.lock().unwrap().variables.last_status() != 0;
+        if failed && self.testing_status.get() == 0 && self.function_depth.get() == 0 {
+            return self.run_trap(TrapEvent::Err);
+        }
+        Ok(false)
+    }
+
+    /// Runs the trap on `event`, if one is set, leaving `$?` as it was unless the
+    /// action exits. Traps do not fire while another trap runs. Returns `true` when
+    /// the action ran `exit`.
+    fn run_trap(&self, event: TrapEvent) -> Result<bool> {
+        if self.running_trap.get() {
+            return Ok(false);
+        }
+        let (action, status) = {
+            let state = self.state.lock().unwrap();
+            (state.traps.action(event), state.variables.last_status())
+        };
+        let Some(action) = action else {
+            return Ok(false);
+        };
+
+        self.running_trap.set(true);
+        let result = self.execute_input(&action);
+        self.running_trap.set(false);
+        if !matches!(result, Ok(true)) {
+            self.set_last_status(status);
+        }
+        result
+    }
+
+    /// Runs the traps of the signals received since the last check. Returns `true`
+    /// when one of them ran `exit`.
+    pub fn run_pending_traps(&self) -> Result<bool> {
+        let pending = self.state.lock().unwrap().traps.take_pending();
+        for signal in pending {
+            if self.run_trap(TrapEvent::Signal(signal))? {
+                return Ok(true);
+            }
+        }
+        Ok(false)
+    }
+
+    /// Runs the `EXIT` trap as the shell exits, once. The exit status is kept
+    /// unless the action runs `exit` itself.
+    pub fn run_exit_trap(&self) -> Result<()> {
+        let action = self.state.lock().unwrap().traps.action(TrapEvent::Exit);
+        if action.is_some() {
+            self.run_trap(TrapEvent::Exit)?;
+            self.state.lock().unwrap().traps.reset(TrapEvent::Exit)?;
+        }
+        Ok(())
+    }
+
+    /// Whether `errexit` should end the shell after the last command.
+    fn exits_on_error(&self) -> bool {
+        let state = self.state.lock().unwrap();
+        state.options.errexit
+            && self.testing_status.get() == 0
+            && state.variables.last_status() != 0
+    }
+
+    /// Reports an expansion error such as an unbound variable. The command is not
+    /// run, and a shell that is not interactive exits, as in bash.
+    fn expansion_failed(&self, err: anyhow::Error) -> Result<bool> {
+        self.write_stderr(&format!("{}\n", err))?;
+        let interactive = self.state.lock().unwrap().options.interactive;
+        self.set_last_status(if interactive { 1 } else { 127 });
+        Ok(!interactive)
+    }
+
+    /// With `xtrace` on, writes the expanded command to standard error after `PS4`.
+    fn trace(&self, command: &SupportedCommand) -> io::Result<()> {
+        let prefix = {
+            let state = self.state.lock().unwrap();
+            if !state.options.xtrace {
+                return Ok(());
+            }
+            state
+                .variables
+                .get("PS4")
+                .unwrap_or_else(|| "+ ".to_string())
+        };
+        let trace: String = command
+            .trace_lines()
+            .iter()
+            .map(|line| format!("{}{}\n", prefix, line))
+            .collect();
+        self.write_stderr(&trace)
+    }
+
+    fn expand(&self, source: &str) -> Result<String> {
+        let substitute = |command: &str| self.capture_output(|| self.execute_input(command));
+        Expander::new(&self.state, &substitute).expand(source)
+    }
+
+    fn dispatch_command(&self, command: &SupportedCommand) -> Result<bool> {
+        match command {
+            SupportedCommand::NoArgument => return Ok(false),
+            SupportedCommand::Prefixed {
+                assignments,
+                inner_cmd,
+            } => {
+                let mut state = self.state.lock().unwrap();
+                if let SupportedCommand::NoArgument = inner_cmd.as_ref() {
+                    for (name, value) in assignments {
+                        state.variables.set_scalar(name, value.as_str());
+                    }
+                    state.variables.set_last_status(0);
+                    return Ok(false);
+                }
+                let saved = state.variables.assign_temporarily(assignments);
+                drop(state);
+                let result = self.dispatch_command(inner_cmd);
+                self.state.lock().unwrap().variables.restore(saved);
+                return result;
+            }
+            SupportedCommand::Redirection {
+                kind,
+                inner_cmd,
+                output_file,
+            } if self.is_function_call(inner_cmd) => {
+                return self.call_redirected(kind, inner_cmd, output_file);
+            }
+            SupportedCommand::Pipeline { commands }
+                if commands
+                    .iter()
+                    .any(|command| self.is_function_call(command)) =>
+            {
+                let stages: Vec<_> = commands.iter().map(Stage::Command).collect();
+                return self.run_pipeline(&stages);
+            }
+            SupportedCommand::Redirection { .. } => {
+                let handler = RedirectionCommandHandler::new(self.handlers.clone());
+                let result = handler.run(
+                    command,
+                    self.stdout(),
+                    self.stderr(),
+                    &mut self.state.lock().unwrap(),
+                );
+                self.print_output(command, result)?;
+            }
+            SupportedCommand::Pipeline { commands } => {
+                let handler = PipelineCommandHandler::new(self.handlers.clone());
+                let result = handler.run(
+                    commands,
+                    self.stdout(),
+                    || self.stderr(),
+                    &mut self.state.lock().unwrap(),
+                );
+                self.print_output(command, result)?;
+            }
+            SupportedCommand::Unspecified { cmd, args } => {
+                if self.functions.lock().unwrap().contains(cmd) {
+                    return self.call_function(cmd, args);
+                }
+                if args.is_empty() && self.is_autocd(cmd) {
+                    // Like bash, show the command that autocd runs.
+                    self.write_stderr(&format!("cd -- {}\n", cmd))?;
+                    let cd = SupportedCommand::Builtin {
+                        name: "cd".to_string(),
+                        args: vec!["--".to_string(), cmd.clone()],
+                    };
+                    return self.run_builtin(&cd);
+                }
+                self.run_external(cmd, args)?;
+            }
+            // Functions override builtins, except the special ones.
+            SupportedCommand::Builtin { name, args }
+                if !self.builtins.lock().unwrap().is_special(name)
+                    && self.functions.lock().unwrap().contains(name) =>
+            {
+                return self.call_function(name, args);
+            }
+            SupportedCommand::Builtin { .. } => return self.run_builtin(command),
+            _ => {
+                if let Some(handler) = self.handlers.iter().find(|h| h.can_handle(command)) {
+                    self.execute_handler(handler.as_ref(), command)?;
+                } else {
+                    self.write_stderr("No handler found for the command\n")?;
+                }
+            }
+        }
+        Ok(false)
+    }
+
+    /// Whether `command` calls a function, perhaps with assignments before it or
+    /// its output redirected. Functions override builtins, except the special ones.
+    fn is_function_call(&self, command: &SupportedCommand) -> bool {
+        match command {
+            SupportedCommand::Unspecified { cmd, .. } => {
+                self.functions.lock().unwrap().contains(cmd)
+            }
+            SupportedCommand::Builtin { name, .. } => {
+                !self.builtins.lock().unwrap().is_special(name)
+                    && self.functions.lock().unwrap().contains(name)
+            }
+            SupportedCommand::Prefixed { inner_cmd, .. }
+            | SupportedCommand::Redirection { inner_cmd, .. } => self.is_function_call(inner_cmd),
+            _ => false,
+        }
+    }
+
+    /// Runs a function call with standard input read from `path`, or with its
+    /// output or errors written there.
+    fn call_redirected(
+        &self,
+        kind: &RedirectionKind,
+        command: &SupportedCommand,
+        path: &str,
+    ) -> Result<bool> {
+        let resolved = self.state.lock().unwrap().resolve(path);
+        let file = match kind {
+            RedirectionKind::Input => File::open(&resolved),
+            kind => RedirectionCommandHandler::open_output(kind, &resolved),
+        };
+        let file = match file {
+            Ok(file) => file,
+            Err(err) => {
+                self.print_result(Err(CommandFailure::open_failed(path, &err)))?;
+                return Ok(false);
+            }
+        };
+
+        match kind {
+            RedirectionKind::Input => {
+                let previous = self.state.lock().unwrap().replace_stdin(Some(file.into()));
+                let result = self.dispatch_command(command);
+                self.state.lock().unwrap().replace_stdin(previous);
+                result
+            }
+            RedirectionKind::OverwriteOnlySuccess | RedirectionKind::AppendOnlySuccess => {
+                let previous = self.capture.replace(Some(file));
+                let result = self.dispatch_command(command);
+                drop(self.capture.replace(previous));
+                result
+            }
+            RedirectionKind::OverwriteOnlyError | RedirectionKind::AppendOnlyError => {
+                // Builtins that write to standard error as they run write there too.
+                let builtin_stderr = file.try_clone()?.into();
+                let previous_builtin_stderr = self
+                    .state
+                    .lock()
+                    .unwrap()
+                    .replace_stderr(Some(builtin_stderr));
+                let previous = self.stderr_capture.replace(Some(file));
+                let result = self.dispatch_command(command);
+                drop(self.stderr_capture.replace(previous));
+                drop(
+                    self.state
+                        .lock()
+                        .unwrap()
+                        .replace_stderr(previous_builtin_stderr),
+                );
+                result
+            }
+        }
+    }
+
+    /// Whether `name` should be run as `cd name`: `autocd` is on, and `name` is a
+    /// directory rather than a command found in `PATH`.
+    fn is_autocd(&self, name: &str) -> bool {
+        let state = self.state.lock().unwrap();
+        state.options.autocd
+            && state.resolve(name).is_dir()
+            && (name.contains('/')
+                || PathHelper::find_executable(name, &state.search_path()).is_none())
+    }
+
+    /// Runs a builtin without looking for a function of the same name. `exit` ends
+    /// the shell, and `command name args` runs `name` the same way.
+    fn run_builtin(&self, command: &SupportedCommand) -> Result<bool> {
+        let SupportedCommand::Builtin { name, args } = command else {
+            return self.dispatch_command(command);
+        };
+
+        match name.as_str() {
+            "exit" => {
+                if let Some(arg) = args.first() {
+                    match arg.parse::<i32>() {
+                        Ok(status) => self.set_last_status(status & 0xff),
+                        Err(_) => {
+                            self.write_stderr(&format!(
+                                "exit: {}: numeric argument required\n",
+                                arg
+                            ))?;
+                            self.set_last_status(2);
+                        }
+                    }
+                }
+                return Ok(true);
+            }
+            "return" => return self.run_return(args),
+            "command" => {
+                if let Some(operands) = SupportedCommand::command_operands(args) {
+                    return self.run_command_builtin(operands);
+                }
+            }
+            _ => {}
+        }
+
+        match self.handlers.iter().find(|h| h.can_handle(command)) {
+            Some(handler) => self.execute_handler(handler.as_ref(), command)?,
+            None => self.write_stderr("No handler found for the command\n")?,
+        }
+        Ok(false)
+    }
+
+    /// Runs `command name args`, which skips functions and aliases and runs the
+    /// builtin or external command `name`.
+    fn run_command_builtin(&self, args: &[String]) -> Result<bool> {
+        if args.is_empty() {
+            return Ok(false);
+        }
+        let command = CommandParser::build_command(args, &self.builtins.lock().unwrap());
+        match command {
+            SupportedCommand::Unspecified { cmd, args } => {
+                self.run_external(&cmd, &args)?;
+                Ok(false)
+            }
+            command => self.run_builtin(&command),
+        }
+    }
+
+    /// Runs `return [n]`, which sets `$?` and unwinds the running function. Outside
+    /// a function it is an error.
+    fn run_return(&self, args: &[String]) -> Result<bool> {
+        if self.function_depth.get() == 0 {
+            let message = "return: can only `return' from a function";
+            self.print_result(Err(CommandFailure::new(2, message).into()))?;
+            return Ok(false);
+        }
+        if let Some(arg) = args.first() {
+            match arg.parse::<i32>() {
+                Ok(status) => self.set_last_status(status & 0xff),
+                Err(_) => {
+                    self.write_stderr(&format!("return: {}: numeric argument required\n", arg))?;
+                    self.set_last_status(2);
+                }
+            }
+        }
+        self.returning.set(true);
+        Ok(true)
+    }
+
+    fn call_function(&self, name: &str, args: &[String]) -> Result<bool> {
+        let Some(body) = self.functions.lock().unwrap().get(name) else {
+            anyhow::bail!("{}: function not found", name);
+        };
+
+        let limit = self
+            .state
+            .lock()
+            .unwrap()
+            .variables
+            .get("FUNCNEST")
+            .and_then(|value| value.parse::<usize>().ok())
+            .filter(|&limit| limit > 0)
+            .unwrap_or(DEFAULT_FUNCNEST);
+        if self.function_depth.get() >= limit {
+            self.write_stderr(&format!(
+                "{}: maximum function nesting level exceeded ({})\n",
+                name, limit
+            ))?;
+            self.set_last_status(1);
+            self.aborting.set(true);
+            return Ok(true);
+        }
+
+        self.state.lock().unwrap().push_positional(args.to_vec());
+        self.function_depth.set(self.function_depth.get() + 1);
+        let result = self.execute_expanded(&body);
+        self.function_depth.set(self.function_depth.get() - 1);
+        self.state.lock().unwrap().pop_positional();
+
+        match result {
+            Ok(true) if self.returning.take() => self.run_trap(TrapEvent::Return),
+            Ok(false) => self.run_trap(TrapEvent::Return),
+            result => result,
+        }
+    }
+
+    fn run_external(&self, cmd: &str, args: &[String]) -> Result<()> {
+        let stdin = self.state.lock().unwrap().child_stdin();
+        let Some(mut child) = self.spawn_external(cmd, args, stdin, self.stdout())? else {
+            return Ok(());
+        };
+
+        let status = child.wait()?;
+        self.set_last_status(CommandFailure::exit_code(status));
+        Ok(())
+    }
+
+    /// Starts an external command, with its errors going where the shell's do.
+    /// When it cannot be started, the error is reported and `None` returned.
+    fn spawn_external(
+        &self,
+        cmd: &str,
+        args: &[String],
+        stdin: Stdio,
+        stdout: Stdio,
+    ) -> Result<Option<Child>> {
+        let handler = self
+            .unspecified_handler()
+            .expect("UnspecifiedCommandHandler must be available");
+
+        let state = self.state.lock().unwrap();
+        let spawned = handler.spawn_process(cmd, args, stdin, stdout, self.stderr(), &state);
+        drop(state);
+        match spawned {
+            Ok(child) => Ok(Some(child)),
+            Err(err)
+                if err
+                    .downcast_ref::<CommandFailure>()
+                    .is_some_and(|f| f.status == 127) =>
+            {
+                self.command_not_found(cmd, args, err)?;
+                Ok(None)
+            }
+            Err(err) => {
+                self.print_result(Err(err))?;
+                Ok(None)
+            }
+        }
+    }
+
+    /// Calls `command_not_found_handle` with the command and its arguments when it is
+    /// defined, and otherwise reports the error along with similarly named builtins,
+    /// aliases, functions and executables.
+    fn command_not_found(&self, cmd: &str, args: &[String], err: anyhow::Error) -> Result<()> {
+        const HANDLER: &str = "command_not_found_handle";
+        if !self.in_not_found_handler.get() && self.functions.lock().unwrap().contains(HANDLER) {
+            let mut handler_args = vec![cmd.to_string()];
+            handler_args.extend(args.iter().cloned());
+            self.in_not_found_handler.set(true);
+            let result = self.call_function(HANDLER, &handler_args);
+            self.in_not_found_handler.set(false);
+            return result.map(|_| ());
+        }
+
+        let mut candidates = self.builtins.lock().unwrap().names();
+        candidates.extend(self.aliases.lock().unwrap().names());
+        candidates.extend(self.functions.lock().unwrap().names());
+        if let Some(handler) = self.unspecified_handler() {
+            candidates.extend(handler.executables(&self.state.lock().unwrap()));
+        }
+
+        let suggestions = edit_distance::closest(cmd, &candidates, 3);
+        if suggestions.is_empty() {
+            return self.print_result(Err(err));
+        }
+        let message = format!("{}\nDid you mean: {}?", err, suggestions.join(", "));
+        self.print_result(Err(CommandFailure::new(127, message).into()))
+    }
+
+    fn unspecified_handler(&self) -> Option<&UnspecifiedCommandHandler> {
+        self.handlers
+            .iter()
+            .find_map(|h| h.as_any().downcast_ref::<UnspecifiedCommandHandler>())
+    }
+
+    fn execute_handler(
+        &self,
+        handler: &dyn CommandHandler,
+        command: &SupportedCommand,
+    ) -> Result<()> {
+        let result = handler.handle(command, &mut self.state.lock().unwrap());
+        self.print_output(command, result)
+    }
+
+    /// Prints the result of `command`, writing output from a handler such as
+    /// `printf` exactly as it was returned.
+    fn print_output(&self, command: &SupportedCommand, result: Result<String>) -> Result<()> {
+        match result {
+            Ok(output) if self.has_raw_output(command) => {
+                self.set_last_status(0);
+                Ok(self.write_stdout(&output)?)
+            }
+            result => self.print_result(result),
+        }
+    }
+
+    /// Whether the output `command` returns comes from a handler with raw output:
+    /// the command itself, the last stage of a pipeline, or a command whose errors
+    /// or input are redirected.
+    fn has_raw_output(&self, command: &SupportedCommand) -> bool {
+        match command {
+            SupportedCommand::Redirection {
+                kind, inner_cmd, ..
+            } => {
+                matches!(
+                    kind,
+                    RedirectionKind::OverwriteOnlyError
+                        | RedirectionKind::AppendOnlyError
+                        | RedirectionKind::Input
+                ) && self.has_raw_output(inner_cmd)
+            }
+            SupportedCommand::Pipeline { commands } => commands
+                .last()
+                .is_some_and(|command| self.has_raw_output(command)),
+            SupportedCommand::Prefixed { inner_cmd, .. } => self.has_raw_output(inner_cmd),
+            command => self
+                .handlers
+                .iter()
+                .find(|h| h.can_handle(command))
+                .is_some_and(|h| h.raw_output()),
+        }
+    }
+
+    /// Prints a handler's output or error and records its exit status for `$?`.
+    fn print_result(&self, result: Result<String>) -> Result<()> {
+        self.set_last_status(CommandFailure::status_of(&result));
+        match result {
+            Ok(output) if !output.is_empty() => {
+                self.write_stdout(&format!("{}\n", output.trim_end_matches('\n')))?;
+            }
+            Err(err) => {
+                let output = CommandFailure::output_of(&err);
+                if !output.is_empty() {
+                    self.write_stdout(output)?;
+                }
+                // A failure with no message, such as `shopt -q`, only sets the status.
+                if !err.to_string().is_empty() {
+                    self.write_stderr(&format!("{}\n", err.to_string().trim_end_matches('\n')))?;
+                }
+            }
+            _ => {}
+        }
+        Ok(())
+    }
+
+    fn set_last_status(&self, status: i32) {
+        self.state.lock().unwrap().variables.set_last_status(status);
+    }
+
+    /// Standard output for a child process: the capture pipe if one is active.
+    fn stdout(&self) -> Stdio {
+        match self.capture.borrow().as_ref().map(File::try_clone) {
+            Some(Ok(writer)) => Stdio::from(writer),
+            _ => Stdio::inherit(),
+        }
+    }
+
+    /// Standard error for a child process: the capture pipe if one is active.
+    fn stderr(&self) -> Stdio {
+        match self.stderr_capture.borrow().as_ref().map(File::try_clone) {
+            Some(Ok(writer)) => Stdio::from(writer),
+            _ => Stdio::inherit(),
+        }
+    }
+
+    fn write_stdout(&self, text: &str) -> io::Result<()> {
+        match self.capture.borrow_mut().as_mut() {
+            Some(writer) => writer.write_all(text.as_bytes()),
+            None => {
+                let mut stdout = io::stdout();
+                stdout.write_all(text.as_bytes())?;
+                stdout.flush()
+            }
+        }
+    }
+
+    fn write_stderr(&self, text: &str) -> io::Result<()> {
+        match self.stderr_capture.borrow_mut().as_mut() {
+            Some(writer) => writer.write_all(text.as_bytes()),
+            None => io::stderr().write_all(text.as_bytes()),
+        }
+    }
+
+    /// Runs `f` with standard output redirected into a pipe and returns what was written.
+    fn capture_output(&self, f: impl FnOnce() -> Result<bool>) -> String {
+        let Ok((writer, reader_thread)) = Self::open_capture() else {
+            return String::new();
+        };
+
+        let previous = self.capture.replace(Some(writer));
+        if let Err(err) = f() {
+            let _ = self.write_stderr(&format!("{}\n", err));
+        }
+        // Captured commands stand in for a subshell, so `return` in one does not
+        // leave the function around it.
+        self.returning.set(false);
+        self.aborting.set(false);
+        drop(self.capture.replace(previous));
+
+        reader_thread.join().unwrap_or_default()
+    }
+
+    /// Runs `f` with standard output and standard error redirected into pipes, and
+    /// returns its result along with what was written to each.
+    pub fn capture_streams<T>(&self, f: impl FnOnce() -> T) -> io::Result<(T, String, String)> {
+        let (stdout, stdout_thread) = Self::open_capture()?;
+        let (stderr, stderr_thread) = Self::open_capture()?;
+
+        // Builtins that write to standard error as they run get the pipe too.
+        let builtin_stderr = stderr.try_clone()?.into();
+        let previous_builtin_stderr = self
+            .state
+            .lock()
+            .unwrap()
+            .replace_stderr(Some(builtin_stderr));
+        let previous_stdout = self.capture.replace(Some(stdout));
+        let previous_stderr = self.stderr_capture.replace(Some(stderr));
+        let result = f();
+        drop(self.capture.replace(previous_stdout));
+        drop(self.stderr_capture.replace(previous_stderr));
+        drop(
+            self.state
+                .lock()
+                .unwrap()
+                .replace_stderr(previous_builtin_stderr),
+        );
+
+        Ok((
+            result,
+            stdout_thread.join().unwrap_or_default(),
+            stderr_thread.join().unwrap_or_default(),
+        ))
+    }
+
+    /// A pipe to capture output into, with its contents collected on a separate
+    /// thread so a child writing more than the pipe buffer holds does not block
+    /// before we get to wait on it.
+    fn open_capture() -> io::Result<(File, JoinHandle<String>)> {
+        let (mut reader, writer) = io::pipe()?;
+        let reader_thread = std::thread::spawn(move || {
+            let mut output = String::new();
+            let _ = reader.read_to_string(&mut output);
+            output
+        });
+        Ok((OwnedFd::from(writer).into(), reader_thread))
+    }
+}
+
+impl CompletionFunctionRunner for Executor {
+    fn run_completion_function(
+        &self,
+        function: &str,
+        words: &[String],
+        cword: usize,
+        line: &str,
+    ) -> Vec<String> {
+        {
+            let variables = &mut self.state.lock().unwrap().variables;
+            variables.set_array("COMP_WORDS", words.to_vec());
+            variables.set_scalar("COMP_CWORD", cword.to_string());
+            variables.set_scalar("COMP_LINE", line);
+            variables.set_scalar("COMP_POINT", line.len().to_string());
+            variables.unset("COMPREPLY");
+        }
+
+        let word = |i: usize| words.get(i).cloned().unwrap_or_default();
+        let args = vec![
+            word(0),
+            word(cword),
+            cword.checked_sub(1).map(word).unwrap_or_default(),
+        ];
+        self.capture_output(|| self.call_function(function, &args));
+
+        let variables = &mut self.state.lock().unwrap().variables;
+        let reply = variables.get_array("COMPREPLY");
+        for name in [
+            "COMP_WORDS",
+            "COMP_CWORD",
+            "COMP_LINE",
+            "COMP_POINT",
+            "COMPREPLY",
+        ] {
+            variables.unset(name);
+        }
+        reply
+    }
+}
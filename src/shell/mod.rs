@@ -1,3 +1,6 @@
 pub mod engine;
+pub mod executor;
+pub mod session;
 
 pub use engine::Shell;
+pub use session::{EvalOutput, ShellSession};
@@ -0,0 +1,174 @@
+use anyhow::Result;
+use std::sync::{Arc, Mutex};
+
+use crate::auto_complete::completion_spec::CompletionState;
+use crate::commands::alias_state::AliasState;
+use crate::commands::command_handler::CommandHandler;
+use crate::commands::command_hash::CommandHashTable;
+use crate::commands::function_state::FunctionState;
+use crate::commands::history_state::HistoryState;
+use crate::commands::plugin_state::PluginState;
+use crate::commands::registry::CommandRegistry;
+use crate::commands::shell_state::{SharedState, ShellState};
+use crate::shell::executor::Executor;
+
+/// What evaluating a script produced.
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct EvalOutput {
+    /// The exit status of the last command, as `$?` would report it.
+    pub status: i32,
+    pub stdout: String,
+    pub stderr: String,
+}
+
+/// A shell for running scripts from Rust, such as setup steps in a test harness.
+///
+/// Output is captured rather than written to the terminal, and the session keeps
+/// its own `ShellState`: `cd` and assignments inside it do not touch the process,
+/// so several sessions can live side by side.
+///
+/// ```no_run
+/// use codecrafters_shell::shell::ShellSession;
+///
+/// let session = ShellSession::new();
+/// session.set_var("NAME", "world");
+/// let output = session.eval("echo hello $NAME").unwrap();
+/// assert_eq!(output.stdout, "hello world\n");
+/// ```
+pub struct ShellSession {
+    executor: Executor,
+    state: SharedState,
+}
+
+impl ShellSession {
+    /// A session starting with a copy of the process's working directory and
+    /// environment.
+    pub fn new() -> Self {
+        let state = Arc::new(Mutex::new(ShellState::from_process()));
+        let functions = Arc::new(Mutex::new(FunctionState::default()));
+        let aliases = Arc::new(Mutex::new(AliasState::default()));
+        let registry = CommandRegistry::new(
+            Arc::new(Mutex::new(HistoryState::default())),
+            functions.clone(),
+            Arc::new(Mutex::new(CompletionState::default())),
+            Arc::new(Mutex::new(CommandHashTable::default())),
+            aliases.clone(),
+            Arc::new(Mutex::new(PluginState::default())),
+        );
+        let executor = Executor::new(
+            registry.handlers(),
+            registry.builtins(),
+            state.clone(),
+            functions,
+            aliases,
+        );
+
+        Self { executor, state }
+    }
+
+    /// Runs `script`, which may span several lines, and returns its exit status and
+    /// what it wrote. `exit` stops the script, after running any `EXIT` trap.
+    pub fn eval(&self, script: &str) -> Result<EvalOutput> {
+        self.capture(|| self.executor.execute(script))
+    }
+
+    /// Delivers `signal` to the session and runs its trap, if one is set, the way
+    /// the shell would on receiving it. The process's own handling of the signal is
+    /// left alone, and a signal without a trap does nothing.
+    pub fn raise(&self, signal: i32) -> Result<EvalOutput> {
+        self.state.lock().unwrap().traps.raise(signal);
+        self.capture(|| self.executor.run_pending_traps())
+    }
+
+    /// Runs `f`, which returns whether the shell should exit, and collects its
+    /// status and output. An exit runs the `EXIT` trap.
+    fn capture(&self, f: impl FnOnce() -> Result<bool>) -> Result<EvalOutput> {
+        let (result, stdout, mut stderr) = self.executor.capture_streams(|| {
+            if f()? {
+                self.executor.run_exit_trap()?;
+            }
+            anyhow::Ok(())
+        })?;
+
+        let mut status = self.state.lock().unwrap().variables.last_status();
+        if let Err(err) = result {
+            stderr.push_str(&format!("{}\n", err));
+            status = 1;
+        }
+        Ok(EvalOutput {
+            status,
+            stdout,
+            stderr,
+        })
+    }
+
+    /// The value of a shell or environment variable.
+    pub fn var(&self, name: &str) -> Option<String> {
+        self.state.lock().unwrap().variables.get(name)
+    }
+
+    /// Sets and exports a variable, so commands the session runs see it in their
+    /// environment as well as in expansions; the process environment is left alone.
+    pub fn set_var(&self, name: &str, value: &str) {
+        let variables = &mut self.state.lock().unwrap().variables;
+        variables.set_scalar(name, value);
+        variables.export(name);
+    }
+
+    /// The session's working directory.
+    pub fn cwd(&self) -> String {
+        self.state.lock().unwrap().cwd().to_string()
+    }
+
+    /// Changes the session's working directory, relative to the current one.
+    pub fn set_cwd(&self, dir: &str) -> Result<()> {
+        let mut state = self.state.lock().unwrap();
+        state
+            .change_dir(dir, false)
+            .map_err(|e| anyhow::anyhow!("{}: {}", dir, e))?;
+        let cwd = state.cwd().to_string();
+        state.variables.set_scalar("PWD", cwd);
+        Ok(())
+    }
+
+    /// Adds a handler for commands of the embedding program's own. A builtin it
+    /// declares replaces any existing builtin of the same name.
+    pub fn register_handler(&mut self, handler: Arc<dyn CommandHandler>) {
+        self.executor.add_handler(handler);
+    }
+}
+
+impl Default for ShellSession {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn sessions_keep_their_own_traps() {
+        let first = ShellSession::new();
+        let second = ShellSession::new();
+        first.eval("trap 'echo first' USR1").unwrap();
+        second.eval("trap 'echo second' USR2").unwrap();
+
+        assert_eq!(first.raise(libc::SIGUSR1).unwrap().stdout, "first\n");
+        assert_eq!(first.raise(libc::SIGUSR2).unwrap().stdout, "");
+        assert_eq!(second.raise(libc::SIGUSR2).unwrap().stdout, "second\n");
+        assert_eq!(second.raise(libc::SIGUSR1).unwrap().stdout, "");
+
+        // Neither trap touched how the process itself handles the signals.
+        for signal in [libc::SIGUSR1, libc::SIGUSR2] {
+            // SAFETY: a null new action only reads the current one.
+            let action = unsafe {
+                let mut action: libc::sigaction = std::mem::zeroed();
+                libc::sigaction(signal, std::ptr::null(), &mut action);
+                action
+            };
+            assert_eq!(action.sa_sigaction, libc::SIG_DFL);
+        }
+    }
+}